@@ -0,0 +1,226 @@
+use {
+    borsh::{to_vec, BorshDeserialize},
+    contract::{entrypoint, errors::ContractError, instruction, models},
+    solana_program::{
+        instruction::{Instruction, InstructionError},
+        program_error::ProgramError,
+        pubkey::Pubkey,
+        system_program,
+    },
+    solana_program_test::{processor, BanksClient, BanksClientError, ProgramTest},
+    solana_sdk::{
+        account::Account,
+        hash::Hash,
+        signature::Keypair,
+        signer::Signer,
+        transaction::{Transaction, TransactionError},
+    },
+};
+
+const QUEUE_NAME: &str = "ack-queue";
+const GROUP: &str = "workers";
+
+fn queue_key() -> Pubkey {
+    models::Queue::find_address(QUEUE_NAME, &entrypoint::ID).0
+}
+
+fn cursor_key(consumer: &Pubkey) -> Pubkey {
+    models::Cursor::find_address(&queue_key(), GROUP, consumer, &entrypoint::ID).0
+}
+
+/// A queue two pushes in, with the hashes after each.
+fn queue() -> (models::Queue, [[u8; 32]; 2]) {
+    let sender = Pubkey::new_unique();
+    let first = models::Queue::chain_hash(&[0; 32], &sender, 1, b"one");
+    let second = models::Queue::chain_hash(&first, &sender, 2, b"two");
+    let queue = models::Queue {
+        creator: Pubkey::new_unique(),
+        allow: vec![sender],
+        data: b"two".to_vec(),
+        need_data_size: 3,
+        created_at: 0,
+        last_change: 0,
+        seq: 2,
+        hook: Pubkey::default(),
+        hash: second,
+        content_type: models::ContentType::Text,
+    };
+    (queue, [first, second])
+}
+
+struct Test {
+    banks_client: BanksClient,
+    payer: Keypair,
+    recent_blockhash: Hash,
+    consumer: Keypair,
+    hashes: [[u8; 32]; 2],
+}
+
+impl Test {
+    /// Start ddmonitor with the queue, a funded consumer and the cursor
+    /// account data of that consumer, if any.
+    async fn start(cursor: Option<Vec<u8>>) -> Self {
+        let program_id = entrypoint::ID;
+        let consumer = Keypair::new();
+        let (queue, hashes) = queue();
+        let mut test = ProgramTest::new(
+            "contract",
+            program_id,
+            processor!(entrypoint::process_instruction),
+        );
+        test.add_account(
+            queue_key(),
+            Account {
+                lamports: 1_000_000_000,
+                data: queue.pack(),
+                owner: program_id,
+                ..Account::default()
+            },
+        );
+        test.add_account(
+            consumer.pubkey(),
+            Account {
+                lamports: 1_000_000_000,
+                owner: system_program::ID,
+                ..Account::default()
+            },
+        );
+        if let Some(data) = cursor {
+            test.add_account(
+                cursor_key(&consumer.pubkey()),
+                Account {
+                    lamports: 1_000_000,
+                    data,
+                    owner: program_id,
+                    ..Account::default()
+                },
+            );
+        }
+        let (banks_client, payer, recent_blockhash) = test.start().await;
+        Self {
+            banks_client,
+            payer,
+            recent_blockhash,
+            consumer,
+            hashes,
+        }
+    }
+
+    fn ack_instruction(&self, seq: u64, hash: [u8; 32]) -> Instruction {
+        instruction::ack(
+            &entrypoint::ID,
+            &self.consumer.pubkey(),
+            &queue_key(),
+            QUEUE_NAME,
+            GROUP,
+            seq,
+            hash,
+        )
+    }
+
+    async fn send(&mut self, instruction: Instruction) -> Result<(), BanksClientError> {
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&self.payer.pubkey()),
+            &[&self.payer, &self.consumer],
+            self.recent_blockhash,
+        );
+        self.banks_client.process_transaction(transaction).await
+    }
+
+    async fn ack(&mut self, seq: u64, hash: [u8; 32]) -> Result<(), BanksClientError> {
+        self.send(self.ack_instruction(seq, hash)).await
+    }
+
+    async fn cursor(&mut self) -> (models::Cursor, bool) {
+        let account = self
+            .banks_client
+            .get_account(cursor_key(&self.consumer.pubkey()))
+            .await
+            .unwrap()
+            .expect("cursor account");
+        models::Cursor::unpack_versioned(&account.data).unwrap()
+    }
+}
+
+fn custom(error: ContractError) -> TransactionError {
+    TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+}
+
+#[tokio::test]
+async fn ack_moves_the_cursor_forward_only() {
+    let mut test = Test::start(None).await;
+    let [first, second] = test.hashes;
+
+    test.ack(1, first).await.unwrap();
+    let (cursor, legacy) = test.cursor().await;
+    assert!(!legacy);
+    assert_eq!((cursor.acked_seq, cursor.acked_hash), (1, first));
+
+    test.ack(2, second).await.unwrap();
+    assert_eq!(
+        test.ack(1, [1; 32]).await.unwrap_err().unwrap(),
+        custom(ContractError::AckBehindCursor)
+    );
+    assert_eq!(
+        test.ack(3, [0; 32]).await.unwrap_err().unwrap(),
+        custom(ContractError::AckAheadOfQueue)
+    );
+    let (cursor, _legacy) = test.cursor().await;
+    assert_eq!((cursor.acked_seq, cursor.acked_hash), (2, second));
+}
+
+#[tokio::test]
+async fn ack_of_the_latest_push_checks_its_hash() {
+    let mut test = Test::start(None).await;
+    assert_eq!(
+        test.ack(2, [7; 32]).await.unwrap_err().unwrap(),
+        custom(ContractError::AckHashMismatch)
+    );
+}
+
+#[tokio::test]
+async fn ack_rejects_another_consumers_cursor() {
+    let mut test = Test::start(None).await;
+    let victim = Pubkey::new_unique();
+    let mut instruction = test.ack_instruction(1, test.hashes[0]);
+    instruction.accounts[2].pubkey = cursor_key(&victim);
+    assert_eq!(
+        test.send(instruction).await.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::from(u64::from(ProgramError::InvalidSeeds))
+        )
+    );
+}
+
+#[tokio::test]
+async fn ack_grows_a_legacy_cursor() {
+    let legacy = models::LegacyCursor {
+        queue: queue_key(),
+        consumer: Pubkey::default(),
+        group: GROUP.to_string(),
+        acked_seq: 1,
+        updated_at: 0,
+    };
+    let mut test = Test::start(Some(to_vec(&legacy).unwrap())).await;
+    let (cursor, legacy) = test.cursor().await;
+    assert!(legacy);
+    assert_eq!(cursor.acked_seq, 1);
+
+    let second = test.hashes[1];
+    test.ack(2, second).await.unwrap();
+    let (cursor, legacy) = test.cursor().await;
+    assert!(!legacy);
+    assert_eq!((cursor.acked_seq, cursor.acked_hash), (2, second));
+    let account = test
+        .banks_client
+        .get_account(cursor_key(&test.consumer.pubkey()))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        models::Cursor::try_from_slice(&account.data).unwrap().group,
+        GROUP
+    );
+}
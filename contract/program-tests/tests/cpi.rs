@@ -0,0 +1,255 @@
+use {
+    borsh::BorshDeserialize,
+    contract::{cpi, entrypoint, errors::ContractError, models},
+    solana_program::{
+        account_info::{next_account_info, AccountInfo},
+        entrypoint::ProgramResult,
+        instruction::{AccountMeta, Instruction, InstructionError},
+        program_error::ProgramError,
+        pubkey::Pubkey,
+        system_program,
+    },
+    solana_program_test::{processor, BanksClient, BanksClientError, ProgramTest},
+    solana_sdk::{
+        account::Account,
+        hash::Hash,
+        signature::Keypair,
+        signer::Signer,
+        transaction::{Transaction, TransactionError},
+    },
+};
+
+const CALLER_ID: Pubkey = Pubkey::new_from_array([7; 32]);
+const PUSHER_SEED: &[u8] = b"pusher";
+const QUEUE_NAME: &str = "cpi-queue";
+
+/// What the caller program asks ddmonitor for, its first instruction byte.
+const PUSH: u8 = 0;
+const KV_PUT: u8 = 1;
+const KV_DELETE: u8 = 2;
+const KV_KEY: &str = "cpi-key";
+
+/// The caller program: pushes or puts the rest of its instruction data,
+/// or deletes its key, signing as its own pusher PDA.
+fn caller_process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let ddmonitor = next_account_info(account_info_iter)?;
+    let pusher = next_account_info(account_info_iter)?;
+    let queue = next_account_info(account_info_iter)?;
+    let system_account = next_account_info(account_info_iter)?;
+    let (_pusher, bump_seed) = Pubkey::find_program_address(&[PUSHER_SEED], program_id);
+    let signer_seeds: &[&[&[u8]]] = &[&[PUSHER_SEED, &[bump_seed]]];
+    let (op, data) = instruction_data
+        .split_first()
+        .ok_or(ProgramError::InvalidInstructionData)?;
+    match *op {
+        PUSH => cpi::push_message(
+            ddmonitor,
+            pusher,
+            queue,
+            system_account,
+            QUEUE_NAME,
+            data.to_vec(),
+            signer_seeds,
+        ),
+        KV_PUT => cpi::kv_put(
+            ddmonitor,
+            pusher,
+            queue,
+            next_account_info(account_info_iter)?,
+            system_account,
+            QUEUE_NAME,
+            KV_KEY,
+            data.to_vec(),
+            signer_seeds,
+        ),
+        KV_DELETE => cpi::kv_delete(
+            ddmonitor,
+            pusher,
+            queue,
+            next_account_info(account_info_iter)?,
+            system_account,
+            QUEUE_NAME,
+            KV_KEY,
+            signer_seeds,
+        ),
+        _ => Err(ProgramError::InvalidInstructionData),
+    }
+}
+
+fn pusher_key() -> Pubkey {
+    Pubkey::find_program_address(&[PUSHER_SEED], &CALLER_ID).0
+}
+
+fn queue_key() -> Pubkey {
+    models::Queue::find_address(QUEUE_NAME, &entrypoint::ID).0
+}
+
+fn entry_key() -> Pubkey {
+    models::KvEntry::find_address(&queue_key(), KV_KEY, &entrypoint::ID).0
+}
+
+/// Start ddmonitor and the caller program with a queue, the pusher PDA on
+/// its allow list when `allowed`.
+async fn start(allowed: bool) -> (BanksClient, Keypair, Hash) {
+    let program_id = entrypoint::ID;
+    let mut allow = vec![Pubkey::default(); 3];
+    if allowed {
+        allow[0] = pusher_key();
+    }
+    let queue = models::Queue {
+        creator: Pubkey::new_unique(),
+        allow,
+        data: vec![0; 16],
+        need_data_size: 16,
+        created_at: 0,
+        last_change: 0,
+        seq: 0,
+        hook: Pubkey::default(),
+        hash: [0; 32],
+        content_type: models::ContentType::Text,
+    };
+
+    let mut test = ProgramTest::new(
+        "contract",
+        program_id,
+        processor!(entrypoint::process_instruction),
+    );
+    test.add_program("caller", CALLER_ID, processor!(caller_process_instruction));
+    test.add_account(
+        pusher_key(),
+        Account {
+            lamports: 1_000_000_000,
+            owner: system_program::ID,
+            ..Account::default()
+        },
+    );
+    test.add_account(
+        queue_key(),
+        Account {
+            lamports: 1_000_000_000,
+            data: queue.pack(),
+            owner: program_id,
+            ..Account::default()
+        },
+    );
+    test.start().await
+}
+
+/// Send one transaction calling the caller program with `data`.
+async fn call(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: Hash,
+    data: &[u8],
+) -> Result<(), BanksClientError> {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(entrypoint::ID, false),
+        AccountMeta::new(pusher_key(), false),
+        AccountMeta::new(queue_key(), false),
+        AccountMeta::new_readonly(system_program::ID, false),
+    ];
+    if data[0] != PUSH {
+        accounts.push(AccountMeta::new(entry_key(), false));
+    }
+    let instruction = Instruction::new_with_bytes(CALLER_ID, data, accounts);
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&payer.pubkey()),
+        &[payer],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await
+}
+
+/// Run one push through the caller program, return its result and the
+/// queue afterwards.
+async fn push_via_cpi(
+    allowed: bool,
+    message: &[u8],
+) -> (Result<(), BanksClientError>, models::Queue) {
+    let (mut banks_client, payer, recent_blockhash) = start(allowed).await;
+    let data = [&[PUSH], message].concat();
+    let result = call(&mut banks_client, &payer, recent_blockhash, &data).await;
+    let account = banks_client
+        .get_account(queue_key())
+        .await
+        .unwrap()
+        .expect("queue account");
+    (result, models::Queue::unpack(&account.data).unwrap())
+}
+
+#[tokio::test]
+async fn push_message_via_cpi() {
+    let (result, queue) = push_via_cpi(true, b"hello cpi").await;
+    result.unwrap();
+    assert_eq!(queue.seq, 1);
+    assert!(queue.last_change > 0);
+    assert_eq!(&queue.data[..9], b"hello cpi");
+    assert_eq!(queue.data.len(), 16);
+    assert_eq!(
+        queue.hash,
+        models::Queue::chain_hash(&[0; 32], &pusher_key(), 1, b"hello cpi")
+    );
+}
+
+#[tokio::test]
+async fn push_message_via_cpi_requires_allow_list() {
+    let (result, queue) = push_via_cpi(false, b"hello cpi").await;
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(ContractError::SenderNotAllowed as u32)
+        )
+    );
+    assert_eq!(queue.seq, 0);
+}
+
+#[tokio::test]
+async fn kv_put_and_delete_via_cpi() {
+    let (mut banks_client, payer, recent_blockhash) = start(true).await;
+    call(
+        &mut banks_client,
+        &payer,
+        recent_blockhash,
+        &[KV_PUT, 1, 2, 3],
+    )
+    .await
+    .unwrap();
+    let account = banks_client
+        .get_account(entry_key())
+        .await
+        .unwrap()
+        .expect("entry account");
+    let entry = models::KvEntry::try_from_slice(&account.data).unwrap();
+    assert_eq!(entry.key, KV_KEY);
+    assert_eq!(entry.value, vec![1, 2, 3]);
+    assert_eq!(entry.updated_by, pusher_key());
+
+    call(&mut banks_client, &payer, recent_blockhash, &[KV_DELETE])
+        .await
+        .unwrap();
+    assert!(banks_client
+        .get_account(entry_key())
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn kv_put_via_cpi_requires_allow_list() {
+    let (mut banks_client, payer, recent_blockhash) = start(false).await;
+    let result = call(&mut banks_client, &payer, recent_blockhash, &[KV_PUT, 1]).await;
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(ContractError::SenderNotAllowed as u32)
+        )
+    );
+}
@@ -0,0 +1,105 @@
+use {
+    borsh::BorshDeserialize,
+    contract::{entrypoint, instruction, instruction::HookMessage, models},
+    solana_program::{
+        account_info::AccountInfo, entrypoint::ProgramResult, instruction::AccountMeta,
+        program_error::ProgramError, pubkey::Pubkey,
+    },
+    solana_program_test::{processor, ProgramTest},
+    solana_sdk::{account::Account, signer::Signer, transaction::Transaction},
+};
+
+const HOOK_ID: Pubkey = Pubkey::new_from_array([9; 32]);
+const QUEUE_NAME: &str = "hook-queue";
+
+/// The hook program: fails unless it got the queue, a pusher that does not
+/// sign for it and the extra accounts with the privileges they were pushed
+/// with.
+fn hook_process_instruction(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let message = HookMessage::try_from_slice(instruction_data)?;
+    let [queue, pusher, readonly, writable, ..] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+    if queue.is_writable || pusher.is_signer || pusher.is_writable || *pusher.key != message.sender
+    {
+        return Err(ProgramError::InvalidArgument);
+    }
+    if readonly.is_writable || !writable.is_writable {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn hook_receives_the_pusher_as_non_signer() {
+    let program_id = entrypoint::ID;
+    let (queue_key, _bump_seed) = models::Queue::find_address(QUEUE_NAME, &program_id);
+    let mut test = ProgramTest::new(
+        "contract",
+        program_id,
+        processor!(entrypoint::process_instruction),
+    );
+    test.add_program("hook", HOOK_ID, processor!(hook_process_instruction));
+    let (readonly, writable) = (Pubkey::new_unique(), Pubkey::new_unique());
+    for key in [readonly, writable] {
+        test.add_account(
+            key,
+            Account {
+                lamports: 1_000_000,
+                owner: HOOK_ID,
+                ..Account::default()
+            },
+        );
+    }
+    let mut context = test.start_with_context().await;
+    let payer = context.payer.pubkey();
+    let queue = models::Queue {
+        creator: payer,
+        allow: vec![payer, Pubkey::default()],
+        data: vec![0; 16],
+        need_data_size: 16,
+        created_at: 0,
+        last_change: 0,
+        seq: 0,
+        hook: HOOK_ID,
+        hash: [0; 32],
+        content_type: models::ContentType::Text,
+    };
+    context.set_account(
+        &queue_key,
+        &Account {
+            lamports: 1_000_000_000,
+            data: queue.pack(),
+            owner: program_id,
+            ..Account::default()
+        }
+        .into(),
+    );
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::push_message_with_hook(
+            &program_id,
+            &payer,
+            &queue_key,
+            QUEUE_NAME,
+            b"hello hook".to_vec(),
+            &HOOK_ID,
+            vec![
+                AccountMeta::new_readonly(readonly, false),
+                AccountMeta::new(writable, false),
+            ],
+        )],
+        Some(&payer),
+        &[&context.payer],
+        context.last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+}
@@ -0,0 +1,210 @@
+use {
+    borsh::BorshDeserialize,
+    contract::{entrypoint, errors::ContractError, instruction, models},
+    solana_program::{
+        instruction::{Instruction, InstructionError},
+        pubkey::Pubkey,
+        system_program,
+    },
+    solana_program_test::{processor, BanksClient, BanksClientError, ProgramTest},
+    solana_sdk::{
+        account::Account,
+        hash::Hash,
+        signature::Keypair,
+        signer::Signer,
+        transaction::{Transaction, TransactionError},
+    },
+};
+
+const QUEUE_NAME: &str = "kv-queue";
+
+fn queue_key() -> Pubkey {
+    models::Queue::find_address(QUEUE_NAME, &entrypoint::ID).0
+}
+
+fn entry_key(key: &str) -> Pubkey {
+    models::KvEntry::find_address(&queue_key(), key, &entrypoint::ID).0
+}
+
+struct Test {
+    banks_client: BanksClient,
+    payer: Keypair,
+    recent_blockhash: Hash,
+    writer: Keypair,
+}
+
+impl Test {
+    /// Start ddmonitor with a queue whose allow list holds the funded
+    /// writer.
+    async fn start() -> Self {
+        let program_id = entrypoint::ID;
+        let writer = Keypair::new();
+        let queue = models::Queue {
+            creator: Pubkey::new_unique(),
+            allow: vec![writer.pubkey(), Pubkey::default()],
+            data: vec![0; 8],
+            need_data_size: 8,
+            created_at: 0,
+            last_change: 0,
+            seq: 0,
+            hook: Pubkey::default(),
+            hash: [0; 32],
+            content_type: models::ContentType::Raw,
+        };
+        let mut test = ProgramTest::new(
+            "contract",
+            program_id,
+            processor!(entrypoint::process_instruction),
+        );
+        test.add_account(
+            queue_key(),
+            Account {
+                lamports: 1_000_000_000,
+                data: queue.pack(),
+                owner: program_id,
+                ..Account::default()
+            },
+        );
+        test.add_account(
+            writer.pubkey(),
+            Account {
+                lamports: 1_000_000_000,
+                owner: system_program::ID,
+                ..Account::default()
+            },
+        );
+        let (banks_client, payer, recent_blockhash) = test.start().await;
+        Self {
+            banks_client,
+            payer,
+            recent_blockhash,
+            writer,
+        }
+    }
+
+    async fn send(
+        &mut self,
+        instruction: Instruction,
+        signer: &Keypair,
+    ) -> Result<(), BanksClientError> {
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&self.payer.pubkey()),
+            &[&self.payer, signer],
+            self.recent_blockhash,
+        );
+        self.banks_client.process_transaction(transaction).await
+    }
+
+    async fn put_as(
+        &mut self,
+        signer: &Keypair,
+        key: &str,
+        value: &[u8],
+    ) -> Result<(), BanksClientError> {
+        let instruction = instruction::kv_put(
+            &entrypoint::ID,
+            &signer.pubkey(),
+            &queue_key(),
+            QUEUE_NAME,
+            key,
+            value.to_vec(),
+        );
+        self.send(instruction, signer).await
+    }
+
+    async fn put(&mut self, key: &str, value: &[u8]) -> Result<(), BanksClientError> {
+        let writer = self.writer.insecure_clone();
+        self.put_as(&writer, key, value).await
+    }
+
+    async fn delete(&mut self, key: &str) -> Result<(), BanksClientError> {
+        let writer = self.writer.insecure_clone();
+        let instruction = instruction::kv_delete(
+            &entrypoint::ID,
+            &writer.pubkey(),
+            &queue_key(),
+            QUEUE_NAME,
+            key,
+        );
+        self.send(instruction, &writer).await
+    }
+
+    async fn get(&mut self, key: &str) -> Option<(Account, models::KvEntry)> {
+        let account = self
+            .banks_client
+            .get_account(entry_key(key))
+            .await
+            .unwrap()?;
+        let entry = models::KvEntry::try_from_slice(&account.data).unwrap();
+        Some((account, entry))
+    }
+}
+
+fn custom(error: ContractError) -> TransactionError {
+    TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+}
+
+#[tokio::test]
+async fn put_get_delete_round_trip() {
+    let mut test = Test::start().await;
+    test.put("color", b"red").await.unwrap();
+    let (account, entry) = test.get("color").await.expect("entry");
+    assert_eq!(account.owner, entrypoint::ID);
+    assert_eq!(entry.key, "color");
+    assert_eq!(entry.key_hash, models::KvEntry::key_hash("color"));
+    assert_eq!(entry.value, b"red");
+    assert_eq!(entry.updated_by, test.writer.pubkey());
+
+    // a longer value resizes the entry
+    test.put("color", b"dark blue").await.unwrap();
+    let (account, entry) = test.get("color").await.expect("entry");
+    assert_eq!(entry.value, b"dark blue");
+    assert_eq!(account.data.len(), borsh::to_vec(&entry).unwrap().len());
+
+    test.delete("color").await.unwrap();
+    assert!(test.get("color").await.is_none());
+    assert_eq!(
+        test.delete("size").await.unwrap_err().unwrap(),
+        custom(ContractError::EntryNotFound)
+    );
+}
+
+#[tokio::test]
+async fn entries_start_with_their_queue_for_scans() {
+    let mut test = Test::start().await;
+    test.put("a", b"1").await.unwrap();
+    test.put("b", b"2").await.unwrap();
+    // scans filter program accounts on the queue address at offset 0
+    for key in ["a", "b"] {
+        let (account, entry) = test.get(key).await.expect("entry");
+        assert_eq!(&account.data[..32], queue_key().as_ref());
+        assert_eq!(entry.queue, queue_key());
+    }
+}
+
+#[tokio::test]
+async fn put_rejects_invalid_keys_and_senders() {
+    let mut test = Test::start().await;
+    let long_key = "k".repeat(models::KvEntry::MAX_KEY_LEN + 1);
+    assert_eq!(
+        test.put(&long_key, b"v").await.unwrap_err().unwrap(),
+        custom(ContractError::InvalidKey)
+    );
+    assert_eq!(
+        test.put("", b"v").await.unwrap_err().unwrap(),
+        custom(ContractError::InvalidKey)
+    );
+    test.put(&"k".repeat(models::KvEntry::MAX_KEY_LEN), b"v")
+        .await
+        .unwrap();
+
+    let stranger = Keypair::new();
+    assert_eq!(
+        test.put_as(&stranger, "color", b"red")
+            .await
+            .unwrap_err()
+            .unwrap(),
+        custom(ContractError::SenderNotAllowed)
+    );
+}
@@ -0,0 +1,282 @@
+//! Helpers for other programs to call into ddmonitor. Build with the
+//! `no-entrypoint` feature when depending on this crate from a program.
+//!
+//! `signer_seeds` are the seeds of the caller's PDA when that PDA is the
+//! payer, pass `&[]` when the payer signed the transaction itself. A queue
+//! hook can not call back into ddmonitor, the runtime rejects the reentrancy.
+
+use {
+    crate::{
+        instruction,
+        models::{ContentType, Metadata},
+    },
+    solana_program::{
+        account_info::AccountInfo, entrypoint::ProgramResult, instruction::AccountMeta,
+        program::invoke_signed, pubkey::Pubkey,
+    },
+};
+
+#[allow(clippy::too_many_arguments)]
+pub fn register_queue<'a>(
+    program: &AccountInfo<'a>,
+    payer: &AccountInfo<'a>,
+    queue: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    name: &str,
+    data_size: usize,
+    allow_count: u8,
+    content_type: ContentType,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    invoke_signed(
+        &instruction::register_queue(
+            program.key,
+            payer.key,
+            queue.key,
+            name,
+            data_size,
+            allow_count,
+            content_type,
+        ),
+        &[
+            payer.clone(),
+            queue.clone(),
+            system_program.clone(),
+            program.clone(),
+        ],
+        signer_seeds,
+    )
+}
+
+pub fn push_message<'a>(
+    program: &AccountInfo<'a>,
+    payer: &AccountInfo<'a>,
+    queue: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    name: &str,
+    data: Vec<u8>,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    invoke_signed(
+        &instruction::push_message(program.key, payer.key, queue.key, name, data),
+        &[
+            payer.clone(),
+            queue.clone(),
+            system_program.clone(),
+            program.clone(),
+        ],
+        signer_seeds,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn push_message_with_hook<'a>(
+    program: &AccountInfo<'a>,
+    payer: &AccountInfo<'a>,
+    queue: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    hook_program: &AccountInfo<'a>,
+    remaining: &[AccountInfo<'a>],
+    name: &str,
+    data: Vec<u8>,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    let ix = instruction::push_message_with_hook(
+        program.key,
+        payer.key,
+        queue.key,
+        name,
+        data,
+        hook_program.key,
+        remaining
+            .iter()
+            .map(|account| {
+                if account.is_writable {
+                    AccountMeta::new(*account.key, account.is_signer)
+                } else {
+                    AccountMeta::new_readonly(*account.key, account.is_signer)
+                }
+            })
+            .collect(),
+    );
+    let mut account_infos = vec![
+        payer.clone(),
+        queue.clone(),
+        system_program.clone(),
+        hook_program.clone(),
+    ];
+    account_infos.extend(remaining.iter().cloned());
+    account_infos.push(program.clone());
+    invoke_signed(&ix, &account_infos, signer_seeds)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn user_pub_operation<'a>(
+    program: &AccountInfo<'a>,
+    payer: &AccountInfo<'a>,
+    queue: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    name: &str,
+    user_pub: &Pubkey,
+    allow: bool,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    invoke_signed(
+        &instruction::user_pub_operation(program.key, payer.key, queue.key, name, user_pub, allow),
+        &[
+            payer.clone(),
+            queue.clone(),
+            system_program.clone(),
+            program.clone(),
+        ],
+        signer_seeds,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn ack<'a>(
+    program: &AccountInfo<'a>,
+    consumer: &AccountInfo<'a>,
+    queue: &AccountInfo<'a>,
+    cursor: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    name: &str,
+    group: &str,
+    seq: u64,
+    hash: [u8; 32],
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    invoke_signed(
+        &instruction::ack(program.key, consumer.key, queue.key, name, group, seq, hash),
+        &[
+            consumer.clone(),
+            queue.clone(),
+            cursor.clone(),
+            system_program.clone(),
+            program.clone(),
+        ],
+        signer_seeds,
+    )
+}
+
+pub fn close_queue<'a>(
+    program: &AccountInfo<'a>,
+    payer: &AccountInfo<'a>,
+    queue: &AccountInfo<'a>,
+    name: &str,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    invoke_signed(
+        &instruction::close_queue(program.key, payer.key, queue.key, name),
+        &[payer.clone(), queue.clone(), program.clone()],
+        signer_seeds,
+    )
+}
+
+pub fn set_hook<'a>(
+    program: &AccountInfo<'a>,
+    payer: &AccountInfo<'a>,
+    queue: &AccountInfo<'a>,
+    name: &str,
+    hook: &Pubkey,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    invoke_signed(
+        &instruction::set_hook(program.key, payer.key, queue.key, name, hook),
+        &[payer.clone(), queue.clone(), program.clone()],
+        signer_seeds,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn set_metadata<'a>(
+    program: &AccountInfo<'a>,
+    payer: &AccountInfo<'a>,
+    queue: &AccountInfo<'a>,
+    metadata_account: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    name: &str,
+    metadata: &Metadata,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    invoke_signed(
+        &instruction::set_metadata(program.key, payer.key, queue.key, name, metadata),
+        &[
+            payer.clone(),
+            queue.clone(),
+            metadata_account.clone(),
+            system_program.clone(),
+            program.clone(),
+        ],
+        signer_seeds,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn kv_put<'a>(
+    program: &AccountInfo<'a>,
+    payer: &AccountInfo<'a>,
+    queue: &AccountInfo<'a>,
+    entry: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    name: &str,
+    key: &str,
+    value: Vec<u8>,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    invoke_signed(
+        &instruction::kv_put(program.key, payer.key, queue.key, name, key, value),
+        &[
+            payer.clone(),
+            queue.clone(),
+            entry.clone(),
+            system_program.clone(),
+            program.clone(),
+        ],
+        signer_seeds,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn kv_delete<'a>(
+    program: &AccountInfo<'a>,
+    payer: &AccountInfo<'a>,
+    queue: &AccountInfo<'a>,
+    entry: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    name: &str,
+    key: &str,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    invoke_signed(
+        &instruction::kv_delete(program.key, payer.key, queue.key, name, key),
+        &[
+            payer.clone(),
+            queue.clone(),
+            entry.clone(),
+            system_program.clone(),
+            program.clone(),
+        ],
+        signer_seeds,
+    )
+}
+
+pub fn migrate_queue<'a>(
+    program: &AccountInfo<'a>,
+    payer: &AccountInfo<'a>,
+    queue: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    name: &str,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    invoke_signed(
+        &instruction::migrate_queue(program.key, payer.key, queue.key, name),
+        &[
+            payer.clone(),
+            queue.clone(),
+            system_program.clone(),
+            program.clone(),
+        ],
+        signer_seeds,
+    )
+}
@@ -1,9 +1,8 @@
 use {
-    crate::{instruction::InstructionData, process},
+    crate::{instruction::InstructionData, models, process},
     borsh::BorshDeserialize,
     solana_program::{
-        account_info::AccountInfo, declare_id, entrypoint, entrypoint::ProgramResult, msg,
-        pubkey::Pubkey,
+        account_info::AccountInfo, declare_id, entrypoint::ProgramResult, msg, pubkey::Pubkey,
     },
 };
 
@@ -16,7 +15,8 @@ fn dump_accounts_info(accounts: &[AccountInfo]) {
 
 declare_id!("9pW59BsNCqtQC1xucwTXYS4Qe9qz5AgSy2jajE63odQb");
 
-entrypoint!(process_instruction);
+#[cfg(not(feature = "no-entrypoint"))]
+solana_program::entrypoint!(process_instruction);
 pub fn process_instruction(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
@@ -33,6 +33,7 @@ pub fn process_instruction(
                     name,
                     data_size,
                     allow_count,
+                    content_type,
                 } => {
                     msg!("RegisterQueue: {:?}", name);
                     return process::do_create_queue(
@@ -40,6 +41,7 @@ pub fn process_instruction(
                         &name,
                         data_size,
                         allow_count,
+                        content_type,
                         program_id,
                     );
                 }
@@ -55,6 +57,45 @@ pub fn process_instruction(
                         accounts, &name, &user_pub, allow, program_id,
                     );
                 }
+                InstructionData::Ack {
+                    name,
+                    group,
+                    seq,
+                    hash,
+                } => {
+                    return process::do_ack(accounts, &name, &group, seq, hash, program_id);
+                }
+                InstructionData::CloseQueue { name } => {
+                    return process::do_close_queue(accounts, &name, program_id);
+                }
+                InstructionData::MigrateQueue { name } => {
+                    return process::do_migrate_queue(accounts, &name, program_id);
+                }
+                InstructionData::SetHook { name, hook } => {
+                    return process::do_set_hook(accounts, &name, &hook, program_id);
+                }
+                InstructionData::KvPut { name, key, value } => {
+                    return process::do_kv_put(accounts, &name, &key, value, program_id);
+                }
+                InstructionData::KvDelete { name, key } => {
+                    return process::do_kv_delete(accounts, &name, &key, program_id);
+                }
+                InstructionData::SetMetadata {
+                    name,
+                    display_name,
+                    description,
+                    tags,
+                    uri,
+                } => {
+                    let metadata = models::Metadata {
+                        display_name,
+                        description,
+                        tags,
+                        uri,
+                        ..Default::default()
+                    };
+                    return process::do_set_metadata(accounts, &name, metadata, program_id);
+                }
             }
         }
         Err(err) => {
@@ -1 +1,48 @@
+use {solana_program::program_error::ProgramError, thiserror::Error};
 
+#[derive(Error, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ContractError {
+    #[error("sender is not in the queue allow list")]
+    SenderNotAllowed,
+    #[error("ack sequence is ahead of the queue sequence")]
+    AckAheadOfQueue,
+    #[error("ack sequence is behind the consumer cursor")]
+    AckBehindCursor,
+    #[error("hook program account does not match the queue hook")]
+    HookMismatch,
+    #[error("key value entry does not exist")]
+    EntryNotFound,
+    #[error("metadata field is too long")]
+    MetadataTooLong,
+    #[error("queue has the legacy layout , migrate it first")]
+    LegacyQueueLayout,
+    #[error("ack hash does not match the queue hash")]
+    AckHashMismatch,
+    #[error("key value key is empty or too long")]
+    InvalidKey,
+}
+
+impl From<ContractError> for ProgramError {
+    fn from(e: ContractError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
+
+impl ContractError {
+    /// The error behind `ProgramError::Custom(code)`, if it is one of ours.
+    pub fn from_code(code: u32) -> Option<Self> {
+        [
+            Self::SenderNotAllowed,
+            Self::AckAheadOfQueue,
+            Self::AckBehindCursor,
+            Self::HookMismatch,
+            Self::EntryNotFound,
+            Self::MetadataTooLong,
+            Self::LegacyQueueLayout,
+            Self::AckHashMismatch,
+            Self::InvalidKey,
+        ]
+        .into_iter()
+        .find(|e| *e as u32 == code)
+    }
+}
@@ -1,4 +1,12 @@
-use borsh::{self, BorshDeserialize, BorshSerialize};
+use {
+    crate::models,
+    borsh::{self, BorshDeserialize, BorshSerialize},
+    solana_program::{
+        instruction::{AccountMeta, Instruction},
+        pubkey::Pubkey,
+        system_program,
+    },
+};
 
 #[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
 pub enum InstructionData {
@@ -6,6 +14,7 @@ pub enum InstructionData {
         name: String,
         data_size: usize,
         allow_count: u8,
+        content_type: models::ContentType,
     },
     PushMessage {
         name: String,
@@ -16,6 +25,40 @@ pub enum InstructionData {
         user_pub: String,
         allow: bool,
     },
+    Ack {
+        name: String,
+        group: String,
+        seq: u64,
+        hash: [u8; 32],
+    },
+    SetHook {
+        name: String,
+        hook: Pubkey,
+    },
+    KvPut {
+        name: String,
+        key: String,
+        value: Vec<u8>,
+    },
+    KvDelete {
+        name: String,
+        key: String,
+    },
+    SetMetadata {
+        name: String,
+        display_name: String,
+        description: String,
+        tags: Vec<String>,
+        uri: String,
+    },
+    CloseQueue {
+        name: String,
+    },
+    /// Rewrite a legacy queue in the current layout, the creator pays the
+    /// extra rent.
+    MigrateQueue {
+        name: String,
+    },
 }
 
 impl InstructionData {
@@ -23,3 +66,262 @@ impl InstructionData {
         borsh::BorshSerialize::try_to_vec(self).unwrap()
     }
 }
+
+/// Encoded here rather than with `Instruction::new_with_borsh`, which takes
+/// borsh 1 types in newer solana-program releases.
+fn new_instruction(
+    program_id: Pubkey,
+    data: &InstructionData,
+    accounts: Vec<AccountMeta>,
+) -> Instruction {
+    Instruction::new_with_bytes(program_id, &data.to_bytes(), accounts)
+}
+
+/// Instruction data a hook program receives after every accepted push.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct HookMessage {
+    pub name: String,
+    pub seq: u64,
+    pub sender: Pubkey,
+    pub data: Vec<u8>,
+}
+
+pub fn register_queue(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    queue: &Pubkey,
+    name: &str,
+    data_size: usize,
+    allow_count: u8,
+    content_type: models::ContentType,
+) -> Instruction {
+    new_instruction(
+        *program_id,
+        &InstructionData::RegisterQueue {
+            name: name.to_string(),
+            data_size,
+            allow_count,
+            content_type,
+        },
+        vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(*queue, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+    )
+}
+
+pub fn push_message(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    queue: &Pubkey,
+    name: &str,
+    data: Vec<u8>,
+) -> Instruction {
+    new_instruction(
+        *program_id,
+        &InstructionData::PushMessage {
+            name: name.to_string(),
+            data,
+        },
+        vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(*queue, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+    )
+}
+
+/// Push to a queue with a hook, `remaining` are passed on to the hook program.
+pub fn push_message_with_hook(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    queue: &Pubkey,
+    name: &str,
+    data: Vec<u8>,
+    hook_program: &Pubkey,
+    remaining: Vec<AccountMeta>,
+) -> Instruction {
+    let mut instruction = push_message(program_id, payer, queue, name, data);
+    instruction
+        .accounts
+        .push(AccountMeta::new_readonly(*hook_program, false));
+    instruction.accounts.extend(remaining);
+    instruction
+}
+
+pub fn user_pub_operation(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    queue: &Pubkey,
+    name: &str,
+    user_pub: &Pubkey,
+    allow: bool,
+) -> Instruction {
+    new_instruction(
+        *program_id,
+        &InstructionData::UserPubOperation {
+            name: name.to_string(),
+            user_pub: user_pub.to_string(),
+            allow,
+        },
+        vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(*queue, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+    )
+}
+
+pub fn ack(
+    program_id: &Pubkey,
+    consumer: &Pubkey,
+    queue: &Pubkey,
+    name: &str,
+    group: &str,
+    seq: u64,
+    hash: [u8; 32],
+) -> Instruction {
+    let (cursor, _bump_seed) = models::Cursor::find_address(queue, group, consumer, program_id);
+    new_instruction(
+        *program_id,
+        &InstructionData::Ack {
+            name: name.to_string(),
+            group: group.to_string(),
+            seq,
+            hash,
+        },
+        vec![
+            AccountMeta::new(*consumer, true),
+            AccountMeta::new_readonly(*queue, false),
+            AccountMeta::new(cursor, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+    )
+}
+
+pub fn set_hook(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    queue: &Pubkey,
+    name: &str,
+    hook: &Pubkey,
+) -> Instruction {
+    new_instruction(
+        *program_id,
+        &InstructionData::SetHook {
+            name: name.to_string(),
+            hook: *hook,
+        },
+        vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(*queue, false),
+        ],
+    )
+}
+
+pub fn kv_put(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    queue: &Pubkey,
+    name: &str,
+    key: &str,
+    value: Vec<u8>,
+) -> Instruction {
+    let (entry, _bump_seed) = models::KvEntry::find_address(queue, key, program_id);
+    new_instruction(
+        *program_id,
+        &InstructionData::KvPut {
+            name: name.to_string(),
+            key: key.to_string(),
+            value,
+        },
+        vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(*queue, false),
+            AccountMeta::new(entry, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+    )
+}
+
+pub fn kv_delete(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    queue: &Pubkey,
+    name: &str,
+    key: &str,
+) -> Instruction {
+    let (entry, _bump_seed) = models::KvEntry::find_address(queue, key, program_id);
+    new_instruction(
+        *program_id,
+        &InstructionData::KvDelete {
+            name: name.to_string(),
+            key: key.to_string(),
+        },
+        vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(*queue, false),
+            AccountMeta::new(entry, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+    )
+}
+
+pub fn set_metadata(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    queue: &Pubkey,
+    name: &str,
+    metadata: &models::Metadata,
+) -> Instruction {
+    let (metadata_pub, _bump_seed) = models::Metadata::find_address(queue, program_id);
+    new_instruction(
+        *program_id,
+        &InstructionData::SetMetadata {
+            name: name.to_string(),
+            display_name: metadata.display_name.clone(),
+            description: metadata.description.clone(),
+            tags: metadata.tags.clone(),
+            uri: metadata.uri.clone(),
+        },
+        vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(*queue, false),
+            AccountMeta::new(metadata_pub, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+    )
+}
+
+pub fn close_queue(program_id: &Pubkey, payer: &Pubkey, queue: &Pubkey, name: &str) -> Instruction {
+    new_instruction(
+        *program_id,
+        &InstructionData::CloseQueue {
+            name: name.to_string(),
+        },
+        vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(*queue, false),
+        ],
+    )
+}
+
+pub fn migrate_queue(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    queue: &Pubkey,
+    name: &str,
+) -> Instruction {
+    new_instruction(
+        *program_id,
+        &InstructionData::MigrateQueue {
+            name: name.to_string(),
+        },
+        vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(*queue, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+    )
+}
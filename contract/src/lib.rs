@@ -1,3 +1,4 @@
+pub mod cpi;
 pub mod entrypoint;
 pub mod errors;
 pub mod instruction;
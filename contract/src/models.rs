@@ -1,8 +1,26 @@
 use {
     borsh::{to_vec, BorshDeserialize, BorshSerialize},
-    solana_program::{clock, pubkey::Pubkey, sysvar::Sysvar},
+    solana_program::{clock, hash::hashv, pubkey::Pubkey, sysvar::Sysvar},
+    std::io,
 };
 
+/// Leading bytes of every queue account in a versioned layout. Accounts
+/// without them hold the layout from before queues had sequences.
+pub const QUEUE_DISCRIMINATOR: [u8; 8] = *b"ddmqueue";
+/// Version of the `Queue` layout following the discriminator.
+pub const QUEUE_LAYOUT_VERSION: u8 = 1;
+
+/// Declared format of the queue data, set when the queue is registered.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ContentType {
+    #[default]
+    Raw,
+    Text,
+    Action,
+    Json,
+    Schema(u32),
+}
+
 #[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
 pub struct Queue {
     pub creator: Pubkey,
@@ -11,23 +29,106 @@ pub struct Queue {
     pub need_data_size: usize,
     pub created_at: i64,
     pub last_change: i64,
+    pub seq: u64,
+    pub hook: Pubkey,
+    pub hash: [u8; 32],
+    pub content_type: ContentType,
+}
+
+/// The queue layout from before sequences, hooks, hashes and content types,
+/// read so old queues can be migrated.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct LegacyQueue {
+    pub creator: Pubkey,
+    pub allow: Vec<Pubkey>,
+    pub data: Vec<u8>,
+    pub need_data_size: usize,
+    pub created_at: i64,
+    pub last_change: i64,
+}
+
+impl From<LegacyQueue> for Queue {
+    fn from(legacy: LegacyQueue) -> Self {
+        Self {
+            creator: legacy.creator,
+            allow: legacy.allow,
+            data: legacy.data,
+            need_data_size: legacy.need_data_size,
+            created_at: legacy.created_at,
+            last_change: legacy.last_change,
+            seq: 0,
+            hook: Pubkey::default(),
+            hash: [0; 32],
+            content_type: ContentType::Raw,
+        }
+    }
 }
 
 impl Queue {
-    pub fn new_queue(creator: &Pubkey, allow: &Vec<Pubkey>, data_size: usize) -> Self {
+    /// Bytes before the borsh encoded queue : discriminator and version.
+    pub const HEADER_LEN: usize = QUEUE_DISCRIMINATOR.len() + 1;
+
+    /// Decode a queue account of any known layout. The flag is set for the
+    /// legacy layout, such queues must be migrated before they are written.
+    pub fn unpack_versioned(data: &[u8]) -> io::Result<(Self, bool)> {
+        let Some(body) = data.strip_prefix(&QUEUE_DISCRIMINATOR) else {
+            return Ok((LegacyQueue::try_from_slice(data)?.into(), true));
+        };
+        match body.split_first() {
+            Some((&QUEUE_LAYOUT_VERSION, body)) => Ok((Self::try_from_slice(body)?, false)),
+            Some((version, _)) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported queue layout version {}", version),
+            )),
+            None => Err(io::ErrorKind::UnexpectedEof.into()),
+        }
+    }
+
+    pub fn unpack(data: &[u8]) -> io::Result<Self> {
+        Self::unpack_versioned(data).map(|(queue, _legacy)| queue)
+    }
+
+    /// Account data in the current layout.
+    pub fn pack(&self) -> Vec<u8> {
+        let mut data = QUEUE_DISCRIMINATOR.to_vec();
+        data.push(QUEUE_LAYOUT_VERSION);
+        data.extend(to_vec(self).unwrap());
+        data
+    }
+
+    pub fn find_address(name: &str, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[name.as_bytes()], program_id)
+    }
+
+    pub fn new_queue(
+        creator: &Pubkey,
+        allow: &[Pubkey],
+        data_size: usize,
+        content_type: ContentType,
+    ) -> Self {
         let clock = clock::Clock::get().unwrap();
         let data: Vec<u8> = vec![0; data_size];
         Self {
-            creator: creator.clone(),
-            allow: allow.clone(),
+            creator: *creator,
+            allow: allow.to_vec(),
             need_data_size: data_size,
             data,
             created_at: clock.unix_timestamp,
             last_change: clock.unix_timestamp,
+            seq: 0,
+            hook: Pubkey::default(),
+            hash: [0; 32],
+            content_type,
         }
     }
+    pub fn is_allowed(&self, sender_pub: &Pubkey) -> bool {
+        self.allow.contains(sender_pub)
+    }
+
     pub fn push_data(&mut self, sender_pub: Pubkey, data: Vec<u8>) -> bool {
-        if self.allow.contains(&sender_pub) {
+        if self.is_allowed(&sender_pub) {
+            self.seq += 1;
+            self.hash = Self::chain_hash(&self.hash, &sender_pub, self.seq, &data);
             self.data = data;
             self.pad_to_length(self.need_data_size, 0);
             self.last_change = clock::Clock::get().unwrap().unix_timestamp;
@@ -40,11 +141,11 @@ impl Queue {
         for i in 0..self.allow.len() {
             if allow {
                 if self.allow[i] == Pubkey::default() {
-                    self.allow[i] = user_pub.clone();
+                    self.allow[i] = *user_pub;
                     break;
                 }
             } else {
-                if self.allow[i] == user_pub.clone() {
+                if self.allow[i] == *user_pub {
                     self.allow[i] = Pubkey::default();
                     break;
                 }
@@ -53,6 +154,16 @@ impl Queue {
         self.last_change = clock::Clock::get().unwrap().unix_timestamp;
     }
 
+    /// Running hash of the push history: sha256(prev_hash + sender + seq + payload),
+    /// starting from all zeros for a new queue.
+    pub fn chain_hash(prev_hash: &[u8; 32], sender: &Pubkey, seq: u64, payload: &[u8]) -> [u8; 32] {
+        hashv(&[prev_hash, sender.as_ref(), &seq.to_le_bytes(), payload]).to_bytes()
+    }
+
+    pub fn has_hook(&self) -> bool {
+        self.hook != Pubkey::default()
+    }
+
     fn pad_to_length(&mut self, desired_length: usize, padding_value: u8) {
         let current_length = self.data.len();
         if current_length < desired_length {
@@ -61,7 +172,7 @@ impl Queue {
         }
     }
 
-    pub fn queue_size(data_size: usize, allow_count: u8) -> usize {
+    pub fn queue_size(data_size: usize, allow_count: u8, content_type: ContentType) -> usize {
         let allow: Vec<Pubkey> = vec![Pubkey::default(); allow_count as usize];
         let data = vec![0; data_size];
         let tmp_queue = Self {
@@ -71,7 +182,167 @@ impl Queue {
             data,
             created_at: 0,
             last_change: 0,
+            seq: 0,
+            hook: Pubkey::default(),
+            hash: [0; 32],
+            content_type,
         };
-        to_vec(&tmp_queue).unwrap().len()
+        tmp_queue.pack().len()
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct Cursor {
+    pub queue: Pubkey,
+    pub consumer: Pubkey,
+    pub group: String,
+    pub acked_seq: u64,
+    pub updated_at: i64,
+    /// Chain hash of the queue after the acked push, so a consumer resuming
+    /// from the cursor verifies the history after it without replaying all.
+    pub acked_hash: [u8; 32],
+}
+
+/// The cursor layout from before it held the acked hash.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct LegacyCursor {
+    pub queue: Pubkey,
+    pub consumer: Pubkey,
+    pub group: String,
+    pub acked_seq: u64,
+    pub updated_at: i64,
+}
+
+impl From<LegacyCursor> for Cursor {
+    fn from(legacy: LegacyCursor) -> Self {
+        Self {
+            queue: legacy.queue,
+            consumer: legacy.consumer,
+            group: legacy.group,
+            acked_seq: legacy.acked_seq,
+            updated_at: legacy.updated_at,
+            acked_hash: [0; 32],
+        }
+    }
+}
+
+impl Cursor {
+    pub const SEED: &'static [u8] = b"cursor";
+
+    pub fn find_address(
+        queue: &Pubkey,
+        group: &str,
+        consumer: &Pubkey,
+        program_id: &Pubkey,
+    ) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[
+                Self::SEED,
+                queue.as_ref(),
+                group.as_bytes(),
+                consumer.as_ref(),
+            ],
+            program_id,
+        )
+    }
+
+    pub fn new_cursor(queue: &Pubkey, consumer: &Pubkey, group: &str) -> Self {
+        Self {
+            queue: *queue,
+            consumer: *consumer,
+            group: group.to_string(),
+            acked_seq: 0,
+            updated_at: clock::Clock::get().unwrap().unix_timestamp,
+            acked_hash: [0; 32],
+        }
+    }
+
+    /// Decode a cursor account of any known layout. The flag is set for the
+    /// legacy layout, its acked hash is unknown.
+    pub fn unpack_versioned(data: &[u8]) -> io::Result<(Self, bool)> {
+        match Self::try_from_slice(data) {
+            Ok(cursor) => Ok((cursor, false)),
+            Err(_) => Ok((LegacyCursor::try_from_slice(data)?.into(), true)),
+        }
+    }
+
+    pub fn ack(&mut self, seq: u64, hash: [u8; 32]) {
+        self.acked_seq = seq;
+        self.acked_hash = hash;
+        self.updated_at = clock::Clock::get().unwrap().unix_timestamp;
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct KvEntry {
+    pub queue: Pubkey,
+    pub key_hash: [u8; 32],
+    pub key: String,
+    pub value: Vec<u8>,
+    pub updated_by: Pubkey,
+    pub updated_at: i64,
+}
+
+impl KvEntry {
+    pub const SEED: &'static [u8] = b"kv";
+    pub const MAX_KEY_LEN: usize = 64;
+
+    /// Keys are stored in the entry, so they must be short and not empty.
+    pub fn is_valid_key(key: &str) -> bool {
+        !key.is_empty() && key.len() <= Self::MAX_KEY_LEN
+    }
+
+    pub fn key_hash(key: &str) -> [u8; 32] {
+        hashv(&[key.as_bytes()]).to_bytes()
+    }
+
+    pub fn find_address(queue: &Pubkey, key: &str, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[Self::SEED, queue.as_ref(), &Self::key_hash(key)],
+            program_id,
+        )
+    }
+
+    pub fn new_entry(queue: &Pubkey, key: &str, value: Vec<u8>, updated_by: &Pubkey) -> Self {
+        Self {
+            queue: *queue,
+            key_hash: Self::key_hash(key),
+            key: key.to_string(),
+            value,
+            updated_by: *updated_by,
+            updated_at: clock::Clock::get().unwrap().unix_timestamp,
+        }
+    }
+}
+
+/// Human readable description of a queue, kept in a companion PDA.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Default)]
+pub struct Metadata {
+    pub queue: Pubkey,
+    pub display_name: String,
+    pub description: String,
+    pub tags: Vec<String>,
+    pub uri: String,
+    pub updated_at: i64,
+}
+
+impl Metadata {
+    pub const SEED: &'static [u8] = b"metadata";
+    pub const MAX_DISPLAY_NAME_LEN: usize = 64;
+    pub const MAX_DESCRIPTION_LEN: usize = 256;
+    pub const MAX_TAGS: usize = 8;
+    pub const MAX_TAG_LEN: usize = 32;
+    pub const MAX_URI_LEN: usize = 200;
+
+    pub fn find_address(queue: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[Self::SEED, queue.as_ref()], program_id)
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.display_name.len() <= Self::MAX_DISPLAY_NAME_LEN
+            && self.description.len() <= Self::MAX_DESCRIPTION_LEN
+            && self.tags.len() <= Self::MAX_TAGS
+            && self.tags.iter().all(|tag| tag.len() <= Self::MAX_TAG_LEN)
+            && self.uri.len() <= Self::MAX_URI_LEN
     }
 }
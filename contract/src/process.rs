@@ -1,13 +1,16 @@
 use {
-    crate::models,
-    borsh::{to_vec, BorshDeserialize},
+    crate::{errors::ContractError, instruction::HookMessage, models},
+    borsh::to_vec,
     solana_program::{
         account_info::{next_account_info, AccountInfo},
+        clock,
         entrypoint::ProgramResult,
+        instruction::{AccountMeta, Instruction},
         msg,
-        program::invoke_signed,
+        program::{invoke, invoke_signed},
+        program_error::ProgramError,
         pubkey::Pubkey,
-        system_instruction,
+        system_instruction, system_program,
         sysvar::{rent::Rent, Sysvar},
     },
     std::str::FromStr,
@@ -18,6 +21,7 @@ pub fn do_create_queue(
     seed_str: &str,
     data_size: usize,
     allow_count: u8,
+    content_type: models::ContentType,
     program_id: &Pubkey,
 ) -> ProgramResult {
     msg!("You will create one Queue account with name : {}", seed_str);
@@ -54,13 +58,13 @@ pub fn do_create_queue(
         let mut allow_keys = Vec::new();
 
         for _ in 0..allow_count {
-            allow_keys.push(system_account.key.clone());
+            allow_keys.push(*system_account.key);
         }
 
-        allow_keys[0] = payer.key.clone();
+        allow_keys[0] = *payer.key;
 
-        let data_queue = models::Queue::new_queue(&payer.key, &allow_keys, data_size);
-        let q_data = to_vec(&data_queue).unwrap();
+        let data_queue = models::Queue::new_queue(payer.key, &allow_keys, data_size, content_type);
+        let q_data = data_queue.pack();
         let pda_space: u64 = q_data.len() as u64;
 
         // 计算所需的租金
@@ -87,7 +91,7 @@ pub fn do_create_queue(
 pub fn do_push_message(
     accounts: &[AccountInfo],
     seed_str: &str,
-    message_data: &Vec<u8>,
+    message_data: &[u8],
     program_id: &Pubkey,
 ) -> ProgramResult {
     msg!(
@@ -121,16 +125,73 @@ pub fn do_push_message(
         return Err(solana_program::program_error::ProgramError::InvalidSeeds);
     }
 
-    let mut user_queue = models::Queue::try_from_slice(&queue_account.data.borrow())?;
-    user_queue.push_data(payer.key.clone(), message_data.clone());
+    let mut user_queue = writable_queue(queue_account)?;
+    if !user_queue.push_data(*payer.key, message_data.to_vec()) {
+        msg!("Sender {} is not allowed to push", payer.key);
+        return Err(ContractError::SenderNotAllowed.into());
+    }
     msg!("current data is : {:?}", user_queue.data);
-    let q_data = to_vec(&user_queue).unwrap();
+    let q_data = user_queue.pack();
 
     queue_account.data.borrow_mut().copy_from_slice(&q_data);
 
+    if user_queue.has_hook() {
+        let hook_program = next_account_info(account_info_iter)?;
+        if *hook_program.key != user_queue.hook || !hook_program.executable {
+            msg!("Hook program account does not match : {}", user_queue.hook);
+            return Err(ContractError::HookMismatch.into());
+        }
+        let remaining: Vec<AccountInfo> = account_info_iter.cloned().collect();
+        invoke_hook(
+            hook_program,
+            queue_account,
+            payer,
+            &remaining,
+            HookMessage {
+                name: seed_str.to_string(),
+                seq: user_queue.seq,
+                sender: *payer.key,
+                data: message_data.to_vec(),
+            },
+        )?;
+    }
+
     Ok(())
 }
 
+fn invoke_hook<'a>(
+    hook_program: &AccountInfo<'a>,
+    queue_account: &AccountInfo<'a>,
+    payer: &AccountInfo<'a>,
+    remaining: &[AccountInfo<'a>],
+    message: HookMessage,
+) -> ProgramResult {
+    msg!(
+        "invoke hook program : {} with seq : {}",
+        hook_program.key,
+        message.seq
+    );
+    // the pusher signed for ddmonitor, not for the hook
+    let mut account_metas = vec![
+        AccountMeta::new_readonly(*queue_account.key, false),
+        AccountMeta::new_readonly(*payer.key, false),
+    ];
+    let mut account_infos = vec![queue_account.clone(), payer.clone()];
+    for account in remaining {
+        account_metas.push(if account.is_writable {
+            AccountMeta::new(*account.key, account.is_signer)
+        } else {
+            AccountMeta::new_readonly(*account.key, account.is_signer)
+        });
+        account_infos.push(account.clone());
+    }
+    account_infos.push(hook_program.clone());
+    invoke(
+        &Instruction::new_with_bytes(*hook_program.key, &borsh::to_vec(&message)?, account_metas),
+        &account_infos,
+    )
+}
+
 pub fn do_operate_user_pub(
     accounts: &[AccountInfo],
     seed_str: &str,
@@ -169,7 +230,7 @@ pub fn do_operate_user_pub(
         return Err(solana_program::program_error::ProgramError::InvalidSeeds);
     }
 
-    let mut user_queue = models::Queue::try_from_slice(&queue_account.data.borrow())?;
+    let mut user_queue = writable_queue(queue_account)?;
     assert!(
         user_queue.creator == *payer.key,
         "only creator can operate allow pub"
@@ -177,6 +238,462 @@ pub fn do_operate_user_pub(
 
     let user_pub = Pubkey::from_str(user_pub).unwrap();
     user_queue.operate_push_pub(&user_pub, allow);
+    let q_data = user_queue.pack();
+    queue_account.data.borrow_mut().copy_from_slice(&q_data);
+
+    Ok(())
+}
+
+pub fn do_ack(
+    accounts: &[AccountInfo],
+    seed_str: &str,
+    group: &str,
+    seq: u64,
+    hash: [u8; 32],
+    program_id: &Pubkey,
+) -> ProgramResult {
+    msg!(
+        "You will ack seq {} of Queue account with name : {} , group : {}",
+        seq,
+        seed_str,
+        group
+    );
+    let account_info_iter = &mut accounts.iter();
+    let consumer = next_account_info(account_info_iter)?;
+    let queue_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+    let cursor_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+    let system_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+
+    let (pda, _bump_seed) = Pubkey::find_program_address(&[seed_str.as_bytes()], program_id);
+    let (cursor_pda, cursor_bump_seed) = Pubkey::find_program_address(
+        &[
+            models::Cursor::SEED,
+            queue_account.key.as_ref(),
+            group.as_bytes(),
+            consumer.key.as_ref(),
+        ],
+        program_id,
+    );
+
+    assert!(
+        consumer.is_signer
+            && consumer.is_writable
+            && cursor_account.is_writable
+            && queue_account.owner == program_id,
+        "users invalid!"
+    );
+
+    if pda != *queue_account.key || cursor_pda != *cursor_account.key {
+        msg!("Queue or cursor account does not have the correct pda");
+        return Err(solana_program::program_error::ProgramError::InvalidSeeds);
+    }
+
+    let queue = models::Queue::unpack(&queue_account.data.borrow())?;
+    if seq > queue.seq {
+        msg!("ack seq {} is ahead of queue seq {}", seq, queue.seq);
+        return Err(ContractError::AckAheadOfQueue.into());
+    }
+    // older hashes are the consumer's own record, only the latest is on chain
+    if seq == queue.seq && hash != queue.hash {
+        msg!("ack hash of seq {} does not match the queue hash", seq);
+        return Err(ContractError::AckHashMismatch.into());
+    }
+
+    let mut cursor = if cursor_account.data_len() == 0 {
+        msg!("cursor account is empty, create it...");
+        models::Cursor::new_cursor(queue_account.key, consumer.key, group)
+    } else {
+        models::Cursor::unpack_versioned(&cursor_account.data.borrow())?.0
+    };
+
+    if seq < cursor.acked_seq {
+        msg!("ack seq {} is behind cursor seq {}", seq, cursor.acked_seq);
+        return Err(ContractError::AckBehindCursor.into());
+    }
+
+    cursor.ack(seq, hash);
+    let c_data = to_vec(&cursor).unwrap();
+    // legacy cursors grow to hold the hash
+    write_sized_account(
+        consumer,
+        cursor_account,
+        system_account,
+        &c_data,
+        &[
+            models::Cursor::SEED,
+            queue_account.key.as_ref(),
+            group.as_bytes(),
+            consumer.key.as_ref(),
+            &[cursor_bump_seed],
+        ],
+        program_id,
+    )
+}
+
+pub fn do_set_hook(
+    accounts: &[AccountInfo],
+    seed_str: &str,
+    hook: &Pubkey,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    msg!(
+        "You will set hook {} to Queue account with name : {}",
+        hook,
+        seed_str
+    );
+    let account_info_iter = &mut accounts.iter();
+    let payer = next_account_info(account_info_iter)?;
+    let queue_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+
+    let (pda, _bump_seed) = Pubkey::find_program_address(&[seed_str.as_bytes()], program_id);
+
+    assert!(
+        payer.is_signer
+            && payer.is_writable
+            && queue_account.is_writable
+            && queue_account.owner == program_id,
+        "users invalid!"
+    );
+
+    if pda != *queue_account.key {
+        msg!("Queue account does not have the correct pda");
+        return Err(solana_program::program_error::ProgramError::InvalidSeeds);
+    }
+
+    let mut user_queue = writable_queue(queue_account)?;
+    assert!(
+        user_queue.creator == *payer.key,
+        "only creator can set the queue hook"
+    );
+
+    user_queue.hook = *hook;
+    let q_data = user_queue.pack();
+    queue_account.data.borrow_mut().copy_from_slice(&q_data);
+
+    Ok(())
+}
+
+pub fn do_kv_put(
+    accounts: &[AccountInfo],
+    seed_str: &str,
+    key: &str,
+    value: Vec<u8>,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    msg!(
+        "You will put key {} to Queue account with name : {}",
+        key,
+        seed_str
+    );
+    let account_info_iter = &mut accounts.iter();
+    let payer = next_account_info(account_info_iter)?;
+    let queue_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+    let entry_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+    let system_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+
+    let (pda, _bump_seed) = Pubkey::find_program_address(&[seed_str.as_bytes()], program_id);
+    let (entry_pda, entry_bump_seed) =
+        models::KvEntry::find_address(queue_account.key, key, program_id);
+
+    assert!(
+        payer.is_signer
+            && payer.is_writable
+            && entry_account.is_writable
+            && queue_account.owner == program_id,
+        "users invalid!"
+    );
+
+    if pda != *queue_account.key || entry_pda != *entry_account.key {
+        msg!("Queue or entry account does not have the correct pda");
+        return Err(solana_program::program_error::ProgramError::InvalidSeeds);
+    }
+
+    let queue = models::Queue::unpack(&queue_account.data.borrow())?;
+    if !queue.is_allowed(payer.key) {
+        msg!("Sender {} is not allowed to put", payer.key);
+        return Err(ContractError::SenderNotAllowed.into());
+    }
+
+    if !models::KvEntry::is_valid_key(key) {
+        msg!(
+            "key is empty or longer than {}",
+            models::KvEntry::MAX_KEY_LEN
+        );
+        return Err(ContractError::InvalidKey.into());
+    }
+
+    let entry = models::KvEntry::new_entry(queue_account.key, key, value, payer.key);
+    let e_data = to_vec(&entry).unwrap();
+    write_sized_account(
+        payer,
+        entry_account,
+        system_account,
+        &e_data,
+        &[
+            models::KvEntry::SEED,
+            queue_account.key.as_ref(),
+            &entry.key_hash,
+            &[entry_bump_seed],
+        ],
+        program_id,
+    )
+}
+
+pub fn do_kv_delete(
+    accounts: &[AccountInfo],
+    seed_str: &str,
+    key: &str,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    msg!(
+        "You will delete key {} from Queue account with name : {}",
+        key,
+        seed_str
+    );
+    let account_info_iter = &mut accounts.iter();
+    let payer = next_account_info(account_info_iter)?;
+    let queue_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+    let entry_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+
+    let (pda, _bump_seed) = Pubkey::find_program_address(&[seed_str.as_bytes()], program_id);
+    let (entry_pda, _entry_bump_seed) =
+        models::KvEntry::find_address(queue_account.key, key, program_id);
+
+    assert!(
+        payer.is_signer
+            && payer.is_writable
+            && entry_account.is_writable
+            && queue_account.owner == program_id,
+        "users invalid!"
+    );
+
+    if pda != *queue_account.key || entry_pda != *entry_account.key {
+        msg!("Queue or entry account does not have the correct pda");
+        return Err(solana_program::program_error::ProgramError::InvalidSeeds);
+    }
+
+    let queue = models::Queue::unpack(&queue_account.data.borrow())?;
+    if !queue.is_allowed(payer.key) {
+        msg!("Sender {} is not allowed to delete", payer.key);
+        return Err(ContractError::SenderNotAllowed.into());
+    }
+
+    if entry_account.data_len() == 0 || entry_account.owner != program_id {
+        msg!("entry {} does not exist", key);
+        return Err(ContractError::EntryNotFound.into());
+    }
+
+    // close the entry, the rent goes back to the payer
+    let lamports = entry_account.lamports();
+    **entry_account.try_borrow_mut_lamports()? = 0;
+    **payer.try_borrow_mut_lamports()? += lamports;
+    entry_account.realloc(0, false)?;
+    entry_account.assign(&system_program::ID);
+
+    Ok(())
+}
+
+pub fn do_close_queue(
+    accounts: &[AccountInfo],
+    seed_str: &str,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    msg!("You will close Queue account with name : {}", seed_str);
+    let account_info_iter = &mut accounts.iter();
+    let payer = next_account_info(account_info_iter)?;
+    let queue_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+
+    let (pda, _bump_seed) = Pubkey::find_program_address(&[seed_str.as_bytes()], program_id);
+
+    assert!(
+        payer.is_signer
+            && payer.is_writable
+            && queue_account.is_writable
+            && queue_account.owner == program_id,
+        "users invalid!"
+    );
+
+    if pda != *queue_account.key {
+        msg!("Queue account does not have the correct pda");
+        return Err(solana_program::program_error::ProgramError::InvalidSeeds);
+    }
+
+    let queue = models::Queue::unpack(&queue_account.data.borrow())?;
+    assert!(
+        queue.creator == *payer.key,
+        "only creator can close the queue"
+    );
+
+    // close the queue, the rent goes back to the creator
+    let lamports = queue_account.lamports();
+    **queue_account.try_borrow_mut_lamports()? = 0;
+    **payer.try_borrow_mut_lamports()? += lamports;
+    queue_account.realloc(0, false)?;
+    queue_account.assign(&system_program::ID);
+
+    Ok(())
+}
 
+pub fn do_migrate_queue(
+    accounts: &[AccountInfo],
+    seed_str: &str,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    msg!("You will migrate Queue account with name : {}", seed_str);
+    let account_info_iter = &mut accounts.iter();
+    let payer = next_account_info(account_info_iter)?;
+    let queue_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+    let system_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+
+    let (pda, bump_seed) = Pubkey::find_program_address(&[seed_str.as_bytes()], program_id);
+
+    assert!(
+        payer.is_signer
+            && payer.is_writable
+            && queue_account.is_writable
+            && queue_account.owner == program_id,
+        "users invalid!"
+    );
+
+    if pda != *queue_account.key {
+        msg!("Queue account does not have the correct pda");
+        return Err(solana_program::program_error::ProgramError::InvalidSeeds);
+    }
+
+    let (queue, legacy) = models::Queue::unpack_versioned(&queue_account.data.borrow())?;
+    if !legacy {
+        msg!("queue already has the current layout");
+        return Ok(());
+    }
+    assert!(
+        queue.creator == *payer.key,
+        "only creator can migrate the queue"
+    );
+
+    write_sized_account(
+        payer,
+        queue_account,
+        system_account,
+        &queue.pack(),
+        &[seed_str.as_bytes(), &[bump_seed]],
+        program_id,
+    )
+}
+
+pub fn do_set_metadata(
+    accounts: &[AccountInfo],
+    seed_str: &str,
+    mut metadata: models::Metadata,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    msg!(
+        "You will set metadata of Queue account with name : {}",
+        seed_str
+    );
+    let account_info_iter = &mut accounts.iter();
+    let payer = next_account_info(account_info_iter)?;
+    let queue_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+    let metadata_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+    let system_account: &AccountInfo<'_> = next_account_info(account_info_iter)?;
+
+    let (pda, _bump_seed) = Pubkey::find_program_address(&[seed_str.as_bytes()], program_id);
+    let (metadata_pda, metadata_bump_seed) =
+        models::Metadata::find_address(queue_account.key, program_id);
+
+    assert!(
+        payer.is_signer
+            && payer.is_writable
+            && metadata_account.is_writable
+            && queue_account.owner == program_id,
+        "users invalid!"
+    );
+
+    if pda != *queue_account.key || metadata_pda != *metadata_account.key {
+        msg!("Queue or metadata account does not have the correct pda");
+        return Err(solana_program::program_error::ProgramError::InvalidSeeds);
+    }
+
+    let queue = models::Queue::unpack(&queue_account.data.borrow())?;
+    assert!(
+        queue.creator == *payer.key,
+        "only creator can set the queue metadata"
+    );
+
+    if !metadata.is_valid() {
+        msg!("metadata is too long");
+        return Err(ContractError::MetadataTooLong.into());
+    }
+    metadata.queue = *queue_account.key;
+    metadata.updated_at = clock::Clock::get()?.unix_timestamp;
+    let m_data = to_vec(&metadata).unwrap();
+
+    write_sized_account(
+        payer,
+        metadata_account,
+        system_account,
+        &m_data,
+        &[
+            models::Metadata::SEED,
+            queue_account.key.as_ref(),
+            &[metadata_bump_seed],
+        ],
+        program_id,
+    )
+}
+
+/// The queue of `queue_account` for an instruction that writes it back,
+/// legacy queues have to be migrated first.
+fn writable_queue(queue_account: &AccountInfo) -> Result<models::Queue, ProgramError> {
+    let (queue, legacy) = models::Queue::unpack_versioned(&queue_account.data.borrow())?;
+    if legacy {
+        msg!("Queue account has the legacy layout, migrate it first");
+        return Err(ContractError::LegacyQueueLayout.into());
+    }
+    Ok(queue)
+}
+
+/// Create the PDA `account` holding `data`, or resize an existing one, so it
+/// stays exactly rent exempt. `seeds` include the bump seed.
+fn write_sized_account<'a>(
+    payer: &AccountInfo<'a>,
+    account: &AccountInfo<'a>,
+    system_account: &AccountInfo<'a>,
+    data: &[u8],
+    seeds: &[&[u8]],
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let rent_lamports = Rent::get()?.minimum_balance(data.len());
+    if account.data_len() == 0 {
+        msg!("account {} is empty, create it...", account.key);
+        invoke_signed(
+            &system_instruction::create_account(
+                payer.key,
+                account.key,
+                rent_lamports,
+                data.len() as u64,
+                program_id,
+            ),
+            &[payer.clone(), account.clone(), system_account.clone()],
+            &[seeds],
+        )?;
+    } else {
+        if account.lamports() < rent_lamports {
+            invoke(
+                &system_instruction::transfer(
+                    payer.key,
+                    account.key,
+                    rent_lamports - account.lamports(),
+                ),
+                &[payer.clone(), account.clone(), system_account.clone()],
+            )?;
+        }
+        account.realloc(data.len(), false)?;
+        if account.lamports() > rent_lamports {
+            let excess = account.lamports() - rent_lamports;
+            **account.try_borrow_mut_lamports()? -= excess;
+            **payer.try_borrow_mut_lamports()? += excess;
+        }
+    }
+    account.data.borrow_mut().copy_from_slice(data);
     Ok(())
 }
@@ -0,0 +1,81 @@
+use {
+    borsh::to_vec,
+    contract::models::{self, Cursor, LegacyCursor, LegacyQueue, Queue},
+    solana_program::pubkey::Pubkey,
+};
+
+fn queue() -> Queue {
+    Queue {
+        creator: Pubkey::new_unique(),
+        allow: vec![Pubkey::new_unique(), Pubkey::default()],
+        data: vec![1, 2, 3, 0],
+        need_data_size: 4,
+        created_at: 10,
+        last_change: 20,
+        seq: 3,
+        hook: Pubkey::new_unique(),
+        hash: [9; 32],
+        content_type: models::ContentType::Json,
+    }
+}
+
+#[test]
+fn pack_round_trips_the_current_layout() {
+    let queue = queue();
+    let packed = queue.pack();
+    assert_eq!(&packed[..8], &models::QUEUE_DISCRIMINATOR);
+    assert_eq!(packed[8], models::QUEUE_LAYOUT_VERSION);
+
+    let (unpacked, legacy) = Queue::unpack_versioned(&packed).unwrap();
+    assert!(!legacy);
+    assert_eq!(to_vec(&unpacked).unwrap(), to_vec(&queue).unwrap());
+}
+
+#[test]
+fn legacy_accounts_unpack_as_fresh_queues() {
+    let creator = Pubkey::new_unique();
+    let legacy = LegacyQueue {
+        creator,
+        allow: vec![creator],
+        data: vec![5; 8],
+        need_data_size: 8,
+        created_at: 1,
+        last_change: 2,
+    };
+    let (queue, legacy) = Queue::unpack_versioned(&to_vec(&legacy).unwrap()).unwrap();
+    assert!(legacy);
+    assert_eq!(queue.creator, creator);
+    assert_eq!(queue.data, vec![5; 8]);
+    assert_eq!((queue.seq, queue.hash), (0, [0; 32]));
+    assert_eq!(queue.content_type, models::ContentType::Raw);
+}
+
+#[test]
+fn unknown_layout_versions_are_rejected() {
+    let mut packed = queue().pack();
+    packed[8] = models::QUEUE_LAYOUT_VERSION + 1;
+    assert!(Queue::unpack(&packed).is_err());
+}
+
+#[test]
+fn cursors_unpack_with_and_without_the_acked_hash() {
+    let legacy = LegacyCursor {
+        queue: Pubkey::new_unique(),
+        consumer: Pubkey::new_unique(),
+        group: "workers".to_string(),
+        acked_seq: 4,
+        updated_at: 5,
+    };
+    let (cursor, is_legacy) = Cursor::unpack_versioned(&to_vec(&legacy).unwrap()).unwrap();
+    assert!(is_legacy);
+    assert_eq!((cursor.acked_seq, cursor.acked_hash), (4, [0; 32]));
+
+    let current = Cursor {
+        acked_hash: [3; 32],
+        ..cursor
+    };
+    let (cursor, is_legacy) = Cursor::unpack_versioned(&to_vec(&current).unwrap()).unwrap();
+    assert!(!is_legacy);
+    assert_eq!((cursor.acked_seq, cursor.acked_hash), (4, [3; 32]));
+    assert_eq!(cursor.group, "workers");
+}
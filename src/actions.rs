@@ -0,0 +1,303 @@
+use {
+    crate::{
+        config::Settings,
+        error::{DdMonitorError, Result},
+        exec,
+        handlers::{self, ActionInfo},
+        payload::{self, Payload},
+        runtime,
+        sdk::{self, QueueHandler, QueueUpdate},
+        sender::SendConfig,
+    },
+    log::{error, info, warn},
+    serde::Deserialize,
+    solana_client::rpc_client::RpcClient,
+    solana_program::pubkey::Pubkey,
+    solana_sdk::{signature::Keypair, signer::Signer},
+    std::collections::BTreeMap,
+};
+
+/// The queue a decoded action came from.
+#[derive(Debug, Clone, Copy)]
+pub struct ActionContext<'a> {
+    pub queue: &'a str,
+    pub address: &'a Pubkey,
+    pub update: &'a QueueUpdate,
+}
+
+impl ActionContext<'_> {
+    pub fn seq(&self) -> u64 {
+        self.update.seq()
+    }
+}
+
+/// A JSON result a handler wants pushed to another queue.
+#[derive(Debug, Clone)]
+pub struct ActionReply {
+    pub queue: String,
+    pub body: serde_json::Value,
+}
+
+/// What a handler did with one action.
+#[derive(Debug, Clone, Default)]
+pub struct ActionOutcome {
+    pub message: String,
+    pub reply: Option<ActionReply>,
+}
+
+impl ActionOutcome {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            reply: None,
+        }
+    }
+
+    pub fn with_reply(mut self, queue: impl Into<String>, body: serde_json::Value) -> Self {
+        self.reply = Some(ActionReply {
+            queue: queue.into(),
+            body,
+        });
+        self
+    }
+}
+
+/// Pushes handler replies, encoded for the content type of the reply queue.
+pub struct Replier<'a> {
+    pub connection: &'a RpcClient,
+    pub payer: &'a Keypair,
+    pub program_id: String,
+    pub send_config: SendConfig,
+}
+
+impl Replier<'_> {
+    pub fn send(&self, reply: &ActionReply) -> Result<()> {
+        let program_account = runtime::program_account(self.program_id.clone())?;
+        let queue_pub = sdk::pda_queue_account(&program_account, &reply.queue);
+        let queue = sdk::fetch_queue(self.connection, &queue_pub)?;
+        let data = payload::encode_text(&queue.content_type, &reply.body.to_string())?;
+        if data.len() > queue.need_data_size {
+            return Err(DdMonitorError::Config(format!(
+                "reply is {} bytes , queue {} holds {}",
+                data.len(),
+                reply.queue,
+                queue.need_data_size
+            )));
+        }
+        let payer = self.payer.pubkey();
+        sdk::send_instruction(
+            self.connection,
+            &payer,
+            &vec![self.payer],
+            sdk::create_instruction(
+                payer,
+                queue_pub,
+                self.program_id.clone(),
+                reply.queue.clone(),
+                data,
+            )?,
+            &self.send_config,
+        )?;
+        Ok(())
+    }
+}
+
+/// Behavior run for the actions pushed to a queue. Register one with
+/// `ActionRegistry::register`, or a factory with `register_factory` to
+/// build it from a `[handlers.<name>]` config table.
+pub trait ActionHandler: Send {
+    /// Action types handled when the config does not list them.
+    fn actions(&self) -> Vec<&'static str>;
+
+    fn handle(&mut self, action: &ActionInfo, context: &ActionContext) -> Result<ActionOutcome>;
+}
+
+/// Builds a handler from its `[handlers.<name>]` table.
+pub type HandlerFactory = fn(&toml::Value) -> Result<Box<dyn ActionHandler>>;
+
+/// Keys of a handler table the registry reads, the rest belongs to the
+/// handler.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct HandlerToggle {
+    enabled: Option<bool>,
+    actions: Option<Vec<String>>,
+}
+
+/// The handler that logs every action, what the server always did.
+pub struct LogHandler;
+
+impl ActionHandler for LogHandler {
+    fn actions(&self) -> Vec<&'static str> {
+        ActionInfo::ACTION_TYPES.to_vec()
+    }
+
+    fn handle(&mut self, action: &ActionInfo, context: &ActionContext) -> Result<ActionOutcome> {
+        info!("action of {} at seq {}", context.queue, context.seq());
+        action.do_action();
+        Ok(ActionOutcome::new("logged"))
+    }
+}
+
+fn log_factory(_settings: &toml::Value) -> Result<Box<dyn ActionHandler>> {
+    Ok(Box::new(LogHandler))
+}
+
+/// Handlers keyed by action type, with the factories that build them.
+pub struct ActionRegistry {
+    factories: BTreeMap<String, HandlerFactory>,
+    handlers: Vec<(String, Box<dyn ActionHandler>)>,
+    routes: BTreeMap<String, usize>,
+}
+
+impl Default for ActionRegistry {
+    fn default() -> Self {
+        let mut registry = Self {
+            factories: BTreeMap::new(),
+            handlers: Vec::new(),
+            routes: BTreeMap::new(),
+        };
+        registry.register_factory("log", log_factory);
+        registry.register_factory("exec", exec::exec_factory);
+        registry
+    }
+}
+
+impl ActionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Make handler `name` available to `[handlers.<name>]` tables.
+    pub fn register_factory(&mut self, name: &str, factory: HandlerFactory) {
+        self.factories.insert(name.to_string(), factory);
+    }
+
+    /// Route `actions`, or the handler's own action types when empty, to
+    /// `handler`. A later handler takes over the action types it shares.
+    pub fn register(&mut self, name: &str, handler: Box<dyn ActionHandler>, actions: &[String]) {
+        let actions: Vec<String> = match actions.is_empty() {
+            true => handler.actions().iter().map(|a| a.to_string()).collect(),
+            false => actions.to_vec(),
+        };
+        let index = self.handlers.len();
+        for action in &actions {
+            if !ActionInfo::ACTION_TYPES.contains(&action.as_str()) {
+                warn!("handler {} takes unknown action type : {}", name, action);
+            }
+            if let Some(previous) = self.routes.insert(action.clone(), index) {
+                warn!(
+                    "action {} moves from handler {} to {}",
+                    action, self.handlers[previous].0, name
+                );
+            }
+        }
+        info!("handler {} takes actions : {:?}", name, actions);
+        self.handlers.push((name.to_string(), handler));
+    }
+
+    /// Build the handlers enabled in `settings`. Without any handler table
+    /// the log handler takes every action.
+    pub fn load(&mut self, settings: &Settings) -> Result<()> {
+        if settings.handlers.is_empty() {
+            self.register("log", Box::new(LogHandler), &[]);
+            return Ok(());
+        }
+        for (name, table) in &settings.handlers {
+            let toggle: HandlerToggle = table.clone().try_into().map_err(|e| {
+                DdMonitorError::Config(format!("invalid handler settings {} : {}", name, e))
+            })?;
+            if toggle.enabled == Some(false) {
+                info!("handler {} is disabled", name);
+                continue;
+            }
+            let Some(factory) = self.factories.get(name) else {
+                return Err(DdMonitorError::Config(format!(
+                    "unknown handler : {} , available : {:?}",
+                    name,
+                    self.factories.keys().collect::<Vec<_>>()
+                )));
+            };
+            let handler = factory(table)?;
+            self.register(name, handler, &toggle.actions.unwrap_or_default());
+        }
+        Ok(())
+    }
+
+    /// Run the handler routed to the type of `action`.
+    pub fn dispatch(
+        &mut self,
+        action: &ActionInfo,
+        context: &ActionContext,
+    ) -> Option<Result<ActionOutcome>> {
+        let index = *self.routes.get(action.action_type())?;
+        let (name, handler) = &mut self.handlers[index];
+        info!("handler {} runs action : {}", name, action.action_type());
+        Some(handler.handle(action, context))
+    }
+
+    /// A `QueueHandler` feeding the actions pushed to `queue` at `address`
+    /// through the registry, other payloads are logged.
+    pub fn for_queue<'a>(self, queue: &str, address: Pubkey) -> QueueActions<'a> {
+        QueueActions {
+            queue: queue.to_string(),
+            address,
+            registry: self,
+            replier: None,
+        }
+    }
+}
+
+pub struct QueueActions<'a> {
+    queue: String,
+    address: Pubkey,
+    registry: ActionRegistry,
+    replier: Option<Replier<'a>>,
+}
+
+impl<'a> QueueActions<'a> {
+    /// Push the replies of handlers with `replier`, they are only logged
+    /// without one.
+    pub fn with_replier(mut self, replier: Replier<'a>) -> Self {
+        self.replier = Some(replier);
+        self
+    }
+
+    fn reply(&self, reply: &ActionReply) {
+        let Some(replier) = &self.replier else {
+            warn!(
+                "no replier , reply to {} dropped : {}",
+                reply.queue, reply.body
+            );
+            return;
+        };
+        match replier.send(reply) {
+            Ok(()) => info!("replied to : {}", reply.queue),
+            Err(e) => error!("reply to {} error : {}", reply.queue, e),
+        }
+    }
+}
+
+impl QueueHandler for QueueActions<'_> {
+    fn handle(&mut self, update: &QueueUpdate) {
+        let action = match payload::decode_queue(&update.queue) {
+            Ok(Payload::Action(action)) => action,
+            _ => return handlers::main(update),
+        };
+        let context = ActionContext {
+            queue: &self.queue,
+            address: &self.address,
+            update,
+        };
+        match self.registry.dispatch(&action, &context) {
+            Some(Ok(outcome)) => {
+                info!("action at seq {} : {}", context.seq(), outcome.message);
+                if let Some(reply) = &outcome.reply {
+                    self.reply(reply);
+                }
+            }
+            Some(Err(e)) => error!("action at seq {} error : {}", context.seq(), e),
+            None => warn!("no handler for action : {}", action.action_type()),
+        }
+    }
+}
@@ -1,14 +1,57 @@
-use {env_logger::Env, log::info, teloxide::prelude::*};
+use {
+    clap::Parser,
+    ddmonitor::{client::DdMonitorClient, config, payload, runtime},
+    env_logger::Env,
+    futures::StreamExt,
+    log::{error, info},
+    teloxide::prelude::*,
+};
 
 // TELOXIDE_TOKEN= cargo run --bin bot
 
+/// One telegram bot forwarding ddmonitor queue messages to a chat
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Name of the queue, defaults to the configured queue
+    #[arg(short, long)]
+    name: Option<String>,
+
+    #[command(flatten)]
+    common: config::CommonArgs,
+
+    /// Telegram chat to forward messages to
+    #[arg(long, default_value_t = 1712332550)]
+    chat_id: i64,
+}
+
 #[tokio::main]
-async fn main() {
+async fn main() -> ddmonitor::error::Result<()> {
     env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
+    runtime::init_app()?;
+    let args = Args::parse();
+    let settings = config::load(&args.common)?;
     info!("bot started");
     let bot = Bot::from_env();
-    bot.send_message(ChatId(1712332550), "Hello telegram world!")
-        .send()
-        .await
-        .unwrap();
+    let client = DdMonitorClient::new(
+        settings.network.clone(),
+        settings.program_account()?,
+        settings.keypair()?,
+    )
+    .with_send_config(settings.send);
+
+    let mut updates = client
+        .updates(&settings.queue_name(args.name.as_deref()))
+        .await?;
+    while let Some(update) = updates.next().await {
+        let text = match payload::decode_queue(&update.queue) {
+            Ok(payload) => format!("#{} {:?}", update.seq(), payload),
+            Err(e) => format!("#{} undecodable payload : {}", update.seq(), e),
+        };
+        if let Err(e) = bot.send_message(ChatId(args.chat_id), text).send().await {
+            error!("send telegram message error : {:?}", e);
+        }
+    }
+    info!("queue subscription closed");
+    Ok(())
 }
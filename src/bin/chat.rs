@@ -1,11 +1,9 @@
 use {
     clap::Parser,
-    contract::instruction::InstructionData,
-    ddmonitor::{ddmonitor_init, handlers, runtime, sdk},
+    contract::{instruction, models},
+    ddmonitor::{config, ddmonitor_init, error::DdMonitorError, handlers, metadata, sdk},
     env_logger::Env,
     log::{error, info},
-    solana_program::{instruction::AccountMeta, system_program},
-    solana_sdk::{instruction::Instruction, transaction::Transaction},
     std::thread,
 };
 
@@ -17,13 +15,8 @@ struct Args {
     #[arg(short, long, default_value_t = String::from("creator706"))]
     room: String,
 
-    /// Network to communicate with
-    #[arg(long, default_value_t = String::from("local"))]
-    network: String,
-
-    /// Solana program address
-    #[arg(short, long, default_value_t = String::from(runtime::DEFAULT_PROGRAM))]
-    program: String,
+    #[command(flatten)]
+    common: config::CommonArgs,
 
     /// Room member address to add to the room
     #[arg(short, long, default_value_t = String::from(""))]
@@ -34,122 +27,94 @@ struct Args {
     chat_start: bool,
 }
 
-const DATA_SIZE: usize = 64;
-const ALLOW_COUNT: u8 = 5;
-
 #[tokio::main]
-async fn main() -> std::io::Result<()> {
+async fn main() -> ddmonitor::error::Result<()> {
     env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
     ddmonitor_init!();
     let args: Args = Args::parse();
+    let settings = config::load(&args.common)?;
     info!("ddmonitor init checking...");
 
-    let (network, pair, pub_key, connection, program_account) =
-        sdk::ddmonitor_init(&args.network, &args.program)?;
+    let (network, pair, pub_key, connection, program_account) = sdk::ddmonitor_init(&settings)?;
 
     let room_account = sdk::pda_queue_account(&program_account, &args.room);
     info!("room account is : {}", &room_account);
-    let queue_avaliable = {
-        let room_info = connection.get_account(&room_account);
-        if room_info.is_err() {
-            false
-        } else {
-            let room_info = room_info.unwrap();
-            room_info.owner == args.program.parse().unwrap()
+    let queue_avaliable = match connection.get_account(&room_account) {
+        Ok(room_info) => {
+            room_info.owner == program_account
                 && room_info.lamports > 0
                 && !room_info.executable
-                && room_info.data.len() > 0
+                && !room_info.data.is_empty()
         }
+        Err(_) => false,
     };
     if !queue_avaliable {
         info!("room account is not avaliable, now create room account !");
 
-        let accounts = vec![
-            AccountMeta::new(pub_key.clone(), true),
-            AccountMeta::new(room_account.clone(), false),
-            AccountMeta::new_readonly(system_program::ID, false),
-        ];
-
-        // Create the instruction by serializing our instruction data via borsh
-        let instruction = Instruction::new_with_borsh(
-            runtime::program_account(args.program.clone()),
-            &InstructionData::RegisterQueue {
-                name: args.room.to_string(),
-                data_size: DATA_SIZE,
-                allow_count: ALLOW_COUNT,
-            },
-            accounts,
-        );
-
-        let blockhash = connection.get_latest_blockhash().unwrap();
-        let transaction =
-            Transaction::new_signed_with_payer(&[instruction], Some(&pub_key), &[&pair], blockhash);
-
         info!("create room request send ...");
-        match connection.send_and_confirm_transaction(&transaction) {
-            Ok(tx) => {
-                info!("create room account tx : {:?}", tx);
-            }
-            Err(err) => {
-                let _transaction_err = err.get_transaction_error().unwrap();
-                error!("create room account error : {:?}", _transaction_err);
-                error!("create room account error : {:?}", err);
-            }
-        }
+        let (data_size, allow_count) = settings.queue.size();
+        let result = sdk::send_instruction(
+            &connection,
+            &pub_key,
+            &vec![&pair],
+            instruction::register_queue(
+                &program_account,
+                &pub_key,
+                &room_account,
+                &args.room,
+                data_size,
+                allow_count,
+                models::ContentType::Action,
+            ),
+            &settings.send,
+        )?;
+        info!("create room account tx : {}", result.signature);
     }
 
-    if args.add_user != "" {
+    if !args.add_user.is_empty() {
         info!("add user :{} to the room {}", &args.add_user, &args.room);
 
-        let accounts = vec![
-            AccountMeta::new(pub_key.clone(), true),
-            AccountMeta::new(room_account.clone(), false),
-            AccountMeta::new_readonly(system_program::ID, false),
-        ];
-
-        // Create the instruction by serializing our instruction data via borsh
-        let instruction = Instruction::new_with_borsh(
-            runtime::program_account(args.program.clone()),
-            &InstructionData::UserPubOperation {
-                name: args.room.to_string(),
-                user_pub: args.add_user.parse().unwrap(),
-                allow: true,
-            },
-            accounts,
-        );
-
-        let blockhash = connection.get_latest_blockhash().unwrap();
-        let transaction =
-            Transaction::new_signed_with_payer(&[instruction], Some(&pub_key), &[&pair], blockhash);
-
         info!("add user to  room request send ...");
-        match connection.send_and_confirm_transaction(&transaction) {
-            Ok(tx) => {
-                info!("add user to  room  tx : {:?}", tx);
-            }
-            Err(err) => {
-                let _transaction_err = err.get_transaction_error().unwrap();
-                error!("add user to  room  error : {:?}", _transaction_err);
-                error!("add user to  room error : {:?}", err);
-            }
-        }
+        let result = sdk::send_instruction(
+            &connection,
+            &pub_key,
+            &vec![&pair],
+            instruction::user_pub_operation(
+                &program_account,
+                &pub_key,
+                &room_account,
+                &args.room,
+                &args.add_user.parse().map_err(DdMonitorError::config)?,
+                true,
+            ),
+            &settings.send,
+        )?;
+        info!("add user to  room  tx : {}", result.signature);
     }
 
     if args.chat_start {
         info!("chat start ...");
+        if let Some(room) = metadata::fetch_metadata(&connection, &program_account, &room_account)?
+        {
+            info!(
+                "room {} : {} {:?} {}",
+                room.display_name, room.description, room.tags, room.uri
+            );
+        }
 
         sdk::send_instruction(
             &connection,
             &pub_key,
             &vec![&pair],
             sdk::create_instruction(
-                pub_key.clone(),
-                room_account.clone(),
-                args.program.clone(),
+                pub_key,
+                room_account,
+                settings.program_id.clone(),
                 args.room.clone(),
-                handlers::ActionInfo::UserMessage(pub_key.clone(), "I'm in!".to_string()).into(),
-            ),
-        );
+                handlers::ActionInfo::UserMessage(pub_key, "I'm in!".to_string()).into(),
+            )?,
+            &settings.send,
+        )?;
 
         thread::spawn(move || {
             sdk::get_account_updates(&network, &room_account, handlers::chat_main)
@@ -158,22 +123,25 @@ async fn main() -> std::io::Result<()> {
         loop {
             info!("you will write these lines to this queue: -> {}", args.room);
             let mut line = String::new();
-            std::io::stdin().read_line(&mut line).unwrap();
+            std::io::stdin().read_line(&mut line)?;
             if line.trim() == "exit" {
                 break;
             } else {
-                sdk::send_instruction(
+                if let Err(e) = sdk::send_instruction(
                     &connection,
                     &pub_key,
                     &vec![&pair],
                     sdk::create_instruction(
-                        pub_key.clone(),
-                        room_account.clone(),
-                        args.program.clone(),
+                        pub_key,
+                        room_account,
+                        settings.program_id.clone(),
                         args.room.to_string(),
-                        handlers::ActionInfo::UserMessage(pub_key.clone(), line).into(),
-                    ),
-                );
+                        handlers::ActionInfo::UserMessage(pub_key, line).into(),
+                    )?,
+                    &settings.send,
+                ) {
+                    error!("message not sent : {:?}", e);
+                }
             }
         }
     }
@@ -0,0 +1,661 @@
+use {
+    clap::{Parser, Subcommand, ValueEnum},
+    contract::models,
+    ddmonitor::{
+        client::DdMonitorClient,
+        config::{self, Settings},
+        error::{DdMonitorError, Result},
+        funding,
+        handlers::ActionInfo,
+        history, inspect, keystore, payload, runtime, sdk,
+        sdk::QueueUpdate,
+        sender::SendResult,
+        wallet,
+    },
+    env_logger::Env,
+    serde_json::{json, Value},
+    solana_account_decoder::UiAccountEncoding,
+    solana_client::nonblocking::rpc_client::RpcClient,
+    solana_program::pubkey::Pubkey,
+    solana_rpc_client_api::{
+        config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+        filter::{Memcmp, RpcFilterType},
+    },
+    solana_sdk::{
+        bs58, commitment_config::CommitmentConfig, signer::keypair::Keypair, signer::Signer,
+    },
+    std::path::PathBuf,
+};
+
+/// Manage and watch ddmonitor queues
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+
+    /// Output format
+    #[arg(short, long, global = true, value_enum, default_value_t = Output::Human)]
+    output: Output,
+
+    #[command(flatten)]
+    common: config::CommonArgs,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum Output {
+    Human,
+    Json,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Create a queue, nothing is sent when it already exists
+    Create {
+        /// Name of the queue, defaults to the configured queue
+        #[arg(short, long)]
+        name: Option<String>,
+
+        /// Bytes of data the queue holds, defaults to the configured size
+        #[arg(long)]
+        data_size: Option<usize>,
+
+        /// Slots of the allow list, defaults to the configured count
+        #[arg(long)]
+        allow_count: Option<u8>,
+
+        /// Content type: raw, text, action, json or schema:<id>
+        #[arg(long)]
+        content_type: Option<String>,
+    },
+    /// Push one message, encoded for the queue content type
+    Push {
+        #[arg(short, long)]
+        name: Option<String>,
+
+        /// Message text, or base64 bytes with --base64
+        message: String,
+
+        /// Push the message as base64 encoded raw bytes
+        #[arg(long, default_value_t = false)]
+        base64: bool,
+
+        /// Extra accounts passed to the queue hook program, ADDRESS for read
+        /// only and ADDRESS:w for writable
+        #[arg(long, value_delimiter = ',')]
+        hook_accounts: Vec<String>,
+    },
+    /// Ask the servers of an action queue to run an allowlisted command
+    Exec {
+        #[arg(short, long)]
+        name: Option<String>,
+
+        /// Id of the command in the server `[handlers.exec]` allowlist
+        command_id: String,
+
+        /// Extra arguments, checked by the server against the allowlist
+        args: Vec<String>,
+    },
+    /// Print every new message of a queue until interrupted
+    Watch {
+        #[arg(short, long)]
+        name: Option<String>,
+
+        /// Also print the messages after this sequence pushed before watching
+        #[arg(long)]
+        from_seq: Option<u64>,
+    },
+    /// Allow an address to push to a queue
+    Allow {
+        #[arg(short, long)]
+        name: Option<String>,
+
+        user: Pubkey,
+    },
+    /// Remove an address from the allow list of a queue
+    Revoke {
+        #[arg(short, long)]
+        name: Option<String>,
+
+        user: Pubkey,
+    },
+    /// Show the state of a queue
+    Inspect {
+        #[arg(short, long)]
+        name: Option<String>,
+
+        /// How to render the payload: auto, text, hex or action
+        #[arg(long, default_value_t = String::from("auto"))]
+        payload: String,
+    },
+    /// List the queues of the program
+    List {
+        /// Only list queues created by this address
+        #[arg(long)]
+        creator: Option<Pubkey>,
+    },
+    /// Close a queue and return its rent to the creator
+    Close {
+        #[arg(short, long)]
+        name: Option<String>,
+    },
+    /// Rewrite a queue created before layouts were versioned, so it can be
+    /// pushed to again
+    Migrate {
+        #[arg(short, long)]
+        name: Option<String>,
+    },
+    /// Print the messages found in transaction history
+    Replay {
+        #[arg(short, long)]
+        name: Option<String>,
+
+        /// Only print messages with a sequence greater than this
+        #[arg(long, default_value_t = 0)]
+        from_seq: u64,
+    },
+    /// Show or manage the wallet
+    Wallet {
+        #[command(subcommand)]
+        action: Option<WalletCommand>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum WalletCommand {
+    /// Show the wallet address and balance
+    Show,
+    /// Airdrop what creating a queue and sending transactions needs
+    Fund,
+    /// Generate a new keypair file
+    Generate {
+        /// Where to write the keypair, defaults to the app wallet path
+        #[arg(long)]
+        out: Option<String>,
+
+        /// Write an encrypted keystore instead of a plaintext keypair
+        #[arg(long, default_value_t = false)]
+        encrypt: bool,
+    },
+    /// Replace the plaintext app wallet with an encrypted keystore
+    Encrypt,
+}
+
+fn print_human(value: &Value, indent: usize) {
+    let pad = " ".repeat(indent);
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                match value {
+                    Value::Object(_) => {
+                        println!("{}{} :", pad, key);
+                        print_human(value, indent + 2);
+                    }
+                    Value::Array(items) if items.iter().any(Value::is_object) => {
+                        println!("{}{} :", pad, key);
+                        for item in items {
+                            print_human(item, indent + 2);
+                            println!();
+                        }
+                    }
+                    _ => println!("{}{} : {}", pad, key, scalar(value)),
+                }
+            }
+        }
+        _ => println!("{}{}", pad, scalar(value)),
+    }
+}
+
+fn scalar(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Null => "-".to_string(),
+        Value::Array(items) => items.iter().map(scalar).collect::<Vec<_>>().join(", "),
+        _ => value.to_string(),
+    }
+}
+
+fn print(output: Output, value: Value) {
+    match output {
+        Output::Json => println!("{}", value),
+        Output::Human => print_human(&value, 0),
+    }
+}
+
+fn sent(result: &SendResult) -> Value {
+    json!({
+        "signature": result.signature.to_string(),
+        "slot": result.slot,
+        "attempts": result.attempts,
+    })
+}
+
+fn queue_json(address: &Pubkey, queue: &models::Queue) -> Value {
+    json!({
+        "address": address.to_string(),
+        "creator": queue.creator.to_string(),
+        "content_type": payload::content_type_name(&queue.content_type),
+        "data_size": queue.need_data_size,
+        "seq": queue.seq,
+        "allow": queue.allow.iter().map(Pubkey::to_string).collect::<Vec<_>>(),
+        "hook": queue.has_hook().then(|| queue.hook.to_string()),
+        "created_at": queue.created_at,
+        "last_change": queue.last_change,
+        "hash": bs58::encode(queue.hash).into_string(),
+    })
+}
+
+fn update_json(update: &QueueUpdate) -> Value {
+    json!({
+        "seq": update.seq(),
+        "slot": update.slot,
+        "backfilled": update.backfilled,
+        "last_change": update.queue.last_change,
+        "payload": match payload::decode_queue(&update.queue) {
+            Ok(payload) => payload.to_json(),
+            Err(e) => json!({ "error": e.to_string() }),
+        },
+    })
+}
+
+/// Run blocking RPC and pubsub work off the runtime threads.
+async fn blocking<T, F>(work: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+{
+    tokio::task::spawn_blocking(work)
+        .await
+        .map_err(|e| DdMonitorError::Io(std::io::Error::other(e)))?
+}
+
+fn wallet_path(settings: &Settings) -> Result<Option<(PathBuf, &'static str)>> {
+    wallet::keypair_path(&settings.wallet)
+}
+
+async fn run(cli: Cli, settings: Settings) -> Result<()> {
+    let output = cli.output;
+    let network = settings.network.clone();
+    let program_account = settings.program_account()?;
+    let queue_name = |name: &Option<String>| settings.queue_name(name.as_deref());
+
+    if let Command::Wallet { action } = &cli.command {
+        return wallet_command(
+            output,
+            &settings,
+            action.as_ref().unwrap_or(&WalletCommand::Show),
+        );
+    }
+
+    let queue_address =
+        |name: &Option<String>| sdk::pda_queue_account(&program_account, &queue_name(name));
+    // only the subcommands that sign load the wallet
+    let signer = || -> Result<DdMonitorClient> {
+        Ok(
+            DdMonitorClient::new(network.clone(), program_account, settings.keypair()?)
+                .with_send_config(settings.send),
+        )
+    };
+    match cli.command {
+        Command::Create {
+            name,
+            data_size,
+            allow_count,
+            content_type,
+        } => {
+            let client = signer()?;
+            let name = queue_name(&name);
+            let address = client.queue_address(&name);
+            if let Ok(queue) = client.fetch(&name).await {
+                let mut value = queue_json(&address, &queue);
+                value["created"] = json!(false);
+                print(output, value);
+                return Ok(());
+            }
+            let (default_size, default_allow) = settings.queue.size();
+            let data_size = data_size.unwrap_or(default_size);
+            let allow_count = allow_count.unwrap_or(default_allow);
+            let content_type = payload::parse_content_type(
+                content_type
+                    .as_deref()
+                    .or(settings.queue.content_type.as_deref())
+                    .unwrap_or("action"),
+            )?;
+            let (network, payer) = (network.clone(), client.payer());
+            let (funding, budget) = (settings.funding, settings.send.compute_budget);
+            blocking(move || {
+                funding::fund_wallet(
+                    &sdk::get_rpc_client(&network),
+                    &network,
+                    &payer,
+                    Some((data_size, allow_count)),
+                    &funding,
+                    &budget,
+                )
+            })
+            .await?;
+            let result = client
+                .create(&name, data_size, allow_count, content_type)
+                .await?;
+            let queue = client.fetch(&name).await?;
+            let mut value = queue_json(&address, &queue);
+            value["created"] = json!(true);
+            value["transaction"] = sent(&result);
+            print(output, value);
+        }
+        Command::Push {
+            name,
+            message,
+            base64,
+            hook_accounts,
+        } => {
+            let client = signer()?;
+            let name = queue_name(&name);
+            let queue = client.fetch(&name).await?;
+            let data = if base64 {
+                sdk::base64_decode(&message)?
+            } else {
+                payload::encode_text(&queue.content_type, &message)?
+            };
+            if data.len() > queue.need_data_size {
+                return Err(DdMonitorError::Config(format!(
+                    "message is {} bytes , queue {} holds {}",
+                    data.len(),
+                    name,
+                    queue.need_data_size
+                )));
+            }
+            let hook_accounts = hook_accounts
+                .iter()
+                .map(|account| sdk::parse_hook_account(account))
+                .collect::<Result<_>>()?;
+            let result = client
+                .push_with_accounts(&name, data, hook_accounts)
+                .await?;
+            print(
+                output,
+                json!({ "queue": name, "transaction": sent(&result) }),
+            );
+        }
+        Command::Exec {
+            name,
+            command_id,
+            args,
+        } => {
+            let client = signer()?;
+            let name = queue_name(&name);
+            let queue = client.fetch(&name).await?;
+            if queue.content_type != models::ContentType::Action {
+                return Err(DdMonitorError::Config(format!(
+                    "queue {} holds {} , exec needs an action queue",
+                    name,
+                    payload::content_type_name(&queue.content_type)
+                )));
+            }
+            let data: Vec<u8> = ActionInfo::Exec { command_id, args }.into();
+            if data.len() > queue.need_data_size {
+                return Err(DdMonitorError::Config(format!(
+                    "exec action is {} bytes , queue {} holds {}",
+                    data.len(),
+                    name,
+                    queue.need_data_size
+                )));
+            }
+            let result = client.push(&name, data).await?;
+            print(
+                output,
+                json!({ "queue": name, "transaction": sent(&result) }),
+            );
+        }
+        Command::Watch { name, from_seq } => {
+            let queue_pub = queue_address(&name);
+            let network = network.clone();
+            let handler = move |update: &QueueUpdate| print(output, update_json(update));
+            blocking(move || match from_seq {
+                Some(seq) => sdk::get_account_updates_after(&network, &queue_pub, seq, handler),
+                None => sdk::get_account_updates(&network, &queue_pub, handler),
+            })
+            .await?;
+        }
+        Command::Allow { name, user } => {
+            let result = signer()?.allow(&queue_name(&name), &user).await?;
+            print(
+                output,
+                json!({ "allowed": user.to_string(), "transaction": sent(&result) }),
+            );
+        }
+        Command::Revoke { name, user } => {
+            let result = signer()?.revoke(&queue_name(&name), &user).await?;
+            print(
+                output,
+                json!({ "revoked": user.to_string(), "transaction": sent(&result) }),
+            );
+        }
+        Command::Inspect { name, payload } => {
+            let network = network.clone();
+            let name = queue_name(&name);
+            let payload = payload.parse()?;
+            let view = blocking(move || {
+                inspect::inspect_queue(
+                    &sdk::get_rpc_client(&network),
+                    &program_account,
+                    &name,
+                    payload,
+                )
+            })
+            .await?;
+            let mut value = serde_json::to_value(&view).map_err(DdMonitorError::decode)?;
+            if output == Output::Human {
+                value["allow"] = view
+                    .allow
+                    .iter()
+                    .map(|slot| match slot.free {
+                        true => format!("{} (free)", slot.address),
+                        false => slot.address.clone(),
+                    })
+                    .collect();
+            }
+            print(output, value);
+        }
+        Command::List { creator } => {
+            // the creator leads legacy queues and follows the header otherwise
+            let filters: Vec<Option<Vec<RpcFilterType>>> = match creator {
+                Some(creator) => vec![
+                    Some(vec![
+                        RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                            0,
+                            models::QUEUE_DISCRIMINATOR.to_vec(),
+                        )),
+                        RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                            models::Queue::HEADER_LEN,
+                            creator.to_bytes().to_vec(),
+                        )),
+                    ]),
+                    Some(vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                        0,
+                        creator.to_bytes().to_vec(),
+                    ))]),
+                ],
+                None => vec![None],
+            };
+            let connection = RpcClient::new_with_commitment(
+                network.get_rpc_url(),
+                CommitmentConfig::confirmed(),
+            );
+            let mut accounts = Vec::new();
+            for filters in filters {
+                accounts.extend(
+                    connection
+                        .get_program_accounts_with_config(
+                            &program_account,
+                            RpcProgramAccountsConfig {
+                                filters,
+                                account_config: RpcAccountInfoConfig {
+                                    encoding: Some(UiAccountEncoding::Base64),
+                                    ..RpcAccountInfoConfig::default()
+                                },
+                                ..RpcProgramAccountsConfig::default()
+                            },
+                        )
+                        .await?,
+                );
+            }
+            let queues: Vec<Value> = accounts
+                .iter()
+                .filter_map(|(address, account)| {
+                    let queue = models::Queue::unpack(&account.data).ok()?;
+                    Some(queue_json(address, &queue))
+                })
+                .collect();
+            match output {
+                Output::Json => println!("{}", Value::Array(queues)),
+                Output::Human => {
+                    for queue in &queues {
+                        print_human(queue, 0);
+                        println!();
+                    }
+                    println!("{} queues", queues.len());
+                }
+            }
+        }
+        Command::Close { name } => {
+            let name = queue_name(&name);
+            let result = signer()?.close(&name).await?;
+            print(
+                output,
+                json!({ "closed": name, "transaction": sent(&result) }),
+            );
+        }
+        Command::Migrate { name } => {
+            let name = queue_name(&name);
+            let result = signer()?.migrate(&name).await?;
+            print(
+                output,
+                json!({ "migrated": name, "transaction": sent(&result) }),
+            );
+        }
+        Command::Replay { name, from_seq } => {
+            let queue_pub = queue_address(&name);
+            let network = network.clone();
+            let count = blocking(move || {
+                history::replay(
+                    &sdk::get_rpc_client(&network),
+                    &program_account,
+                    &queue_pub,
+                    from_seq,
+                    |update: &QueueUpdate| print(output, update_json(update)),
+                )
+            })
+            .await?;
+            if output == Output::Human {
+                println!("{} messages replayed", count);
+            }
+        }
+        Command::Wallet { .. } => unreachable!(),
+    }
+    Ok(())
+}
+
+fn wallet_command(output: Output, settings: &Settings, action: &WalletCommand) -> Result<()> {
+    let network = &settings.network;
+    match action {
+        WalletCommand::Show | WalletCommand::Fund => {
+            let keypair = settings.keypair()?;
+            let connection = sdk::get_rpc_client(network);
+            let balance = if let WalletCommand::Fund = action {
+                funding::fund_wallet(
+                    &connection,
+                    network,
+                    &keypair.pubkey(),
+                    Some(settings.queue.size()),
+                    &settings.funding,
+                    &settings.send.compute_budget,
+                )?
+            } else {
+                connection.get_balance(&keypair.pubkey())?
+            };
+            let path = wallet_path(settings)?;
+            print(
+                output,
+                json!({
+                    "address": keypair.pubkey().to_string(),
+                    "keypair": path.as_ref().map(|(path, _)| path.display().to_string()),
+                    "source": path.map(|(_, source)| source),
+                    "network": network.get_rpc_url(),
+                    "balance": balance,
+                    "sol": balance as f64 / runtime::LAMPORTS_PER_SOL as f64,
+                }),
+            );
+        }
+        WalletCommand::Generate { out, encrypt } => {
+            let path = out
+                .as_ref()
+                .map(PathBuf::from)
+                .unwrap_or_else(|| PathBuf::from(runtime::app_path(wallet::KEYPAIR_PATH)));
+            if path.exists() {
+                return Err(DdMonitorError::Config(format!(
+                    "keypair file {} already exists",
+                    path.display()
+                )));
+            }
+            let keypair = Keypair::new();
+            if *encrypt {
+                keystore::create(&keypair, &path)?;
+            } else {
+                wallet::write_keypair(&keypair, &path)?;
+            }
+            print(
+                output,
+                json!({
+                    "address": keypair.pubkey().to_string(),
+                    "keypair": path.display().to_string(),
+                    "encrypted": encrypt,
+                }),
+            );
+        }
+        WalletCommand::Encrypt => {
+            let Some((path, source)) = wallet_path(settings)? else {
+                return Err(DdMonitorError::NotFound("keypair".to_string()));
+            };
+            if source == "solana cli config" {
+                return Err(DdMonitorError::Config(format!(
+                    "{} belongs to the solana cli , pass --keypair to encrypt it",
+                    path.display()
+                )));
+            }
+            let text = std::fs::read_to_string(&path)?;
+            let keypair = wallet::read_keypair(&path)?;
+            let migrated = !keystore::Keystore::is_keystore(&text);
+            if migrated {
+                keystore::migrate(&keypair, &path)?;
+            }
+            print(
+                output,
+                json!({
+                    "address": keypair.pubkey().to_string(),
+                    "keypair": path.display().to_string(),
+                    "migrated": migrated,
+                }),
+            );
+        }
+    }
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() {
+    env_logger::Builder::from_env(Env::default().default_filter_or("warn")).init();
+    let cli = Cli::parse();
+    let output = cli.output;
+    let result = match runtime::init_app().and_then(|_| config::load(&cli.common)) {
+        Ok(settings) => run(cli, settings).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        match output {
+            Output::Json => println!("{}", json!({ "error": e.to_string() })),
+            Output::Human => eprintln!("error : {}", e),
+        }
+        std::process::exit(1);
+    }
+}
@@ -1,70 +1,107 @@
 use {
     clap::Parser,
-    ddmonitor::{ddmonitor_init, handlers, runtime, sdk},
+    contract::models,
+    ddmonitor::{
+        config, ddmonitor_init, funding, handlers, history, kv, payload, reply, runtime, sdk,
+    },
     env_logger::Env,
     log::{error, info},
-    solana_sdk::signer::Signer,
+    solana_sdk::{instruction::Instruction, signer::Signer},
+    std::time::Duration,
 };
 
 /// One operator to push message to ddmonitor queue
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// Name of the queue
-    #[arg(short, long, default_value_t = String::from("default"))]
-    name: String,
+    /// Name of the queue, defaults to the configured queue
+    #[arg(short, long)]
+    name: Option<String>,
 
     /// Message to push
     #[arg(short, long, default_value_t = String::from("hello world"))]
     message: String,
 
-    /// Network to communicate with
-    #[arg(long, default_value_t = String::from("local"))]
-    network: String,
+    #[command(flatten)]
+    common: config::CommonArgs,
 
-    /// Solana program address
-    #[arg(short, long, default_value_t = String::from(runtime::DEFAULT_PROGRAM))]
-    program: String,
+    /// Queue to wait for a response on, the message is sent as a request
+    #[arg(long, default_value_t = String::from(""))]
+    reply_to: String,
+
+    /// Seconds to wait for the response of a request
+    #[arg(long, default_value_t = 30)]
+    timeout: u64,
+
+    /// Extra accounts passed to the queue hook program, ADDRESS for read
+    /// only and ADDRESS:w for writable
+    #[arg(long, value_delimiter = ',')]
+    hook_accounts: Vec<String>,
+
+    /// Rebuild the queue hash chain from transaction history and verify it
+    #[arg(long, default_value_t = false)]
+    verify: bool,
+
+    /// Put one KEY=VALUE entry to the queue key value store
+    #[arg(long)]
+    put: Option<String>,
+
+    /// Print the value of one key of the queue key value store
+    #[arg(long)]
+    get: Option<String>,
+
+    /// Delete one key of the queue key value store
+    #[arg(long)]
+    delete: Option<String>,
+
+    /// List all entries of the queue key value store
+    #[arg(long, default_value_t = false)]
+    scan: bool,
 }
 
 #[tokio::main]
-async fn main() -> std::io::Result<()> {
+async fn main() -> ddmonitor::error::Result<()> {
     env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
     ddmonitor_init!();
     info!("ddmonitor operator start ...");
 
     let args = Args::parse();
-    runtime::init_app();
-    let network = sdk::Network::from_string(&args.network);
+    let settings = config::load(&args.common)?;
+    runtime::init_app()?;
+    let network = settings.network.clone();
     info!("network is : <{:?}> ", network);
-    let pair = sdk::init_solana_wallet()?;
+    let pair = settings.keypair()?;
     let pub_key = pair.pubkey();
     let connection = sdk::get_rpc_client(&network);
-    let program_account = runtime::program_account(args.program.clone());
+    let program_account = settings.program_account()?;
 
     info!("current wallet address : {}", pub_key);
     sdk::connection_available(&connection)?;
-    sdk::confirm_balance(&connection, &network, &pub_key, 5);
+    funding::fund_wallet(
+        &connection,
+        &network,
+        &pub_key,
+        None,
+        &settings.funding,
+        &settings.send.compute_budget,
+    )?;
 
-    if !sdk::program_available(&connection, &program_account) {
+    if !sdk::program_available(&connection, &program_account)? {
         error!("program account is not available , exit...");
         return Ok(());
     }
 
-    let queue_name = args.name.clone();
+    let queue_name = settings.queue_name(args.name.as_deref());
     let queue_pub = sdk::pda_queue_account(&program_account, &queue_name);
 
-    let queue_avaliable = {
-        let queue_info = connection.get_account(&queue_pub);
-        if queue_info.is_err() {
-            false
-        } else {
-            let queue_info = queue_info.unwrap();
-            queue_info.owner == args.program.parse().unwrap()
+    let queue_avaliable = match connection.get_account(&queue_pub) {
+        Ok(queue_info) => {
+            queue_info.owner == program_account
                 && queue_info.lamports > 0
                 && !queue_info.executable
-                && queue_info.data.len() > 0
+                && !queue_info.data.is_empty()
         }
+        Err(_) => false,
     };
 
     if !queue_avaliable {
@@ -72,51 +109,151 @@ async fn main() -> std::io::Result<()> {
         return Ok(());
     }
 
-    sdk::send_instruction(
-        &connection,
-        &pub_key,
-        &vec![&pair],
-        sdk::create_instruction(
-            pub_key.clone(),
-            queue_pub.clone(),
-            args.program.clone(),
-            args.name.clone(),
-            args.message.clone().into_bytes(),
-        ),
-    );
+    let queue = sdk::fetch_queue(&connection, &queue_pub)?;
+
+    if args.verify {
+        let current = sdk::fetch_queue_update(&connection, &queue_pub)?;
+        let records = history::fetch_recent_pushes(
+            &connection,
+            &program_account,
+            &queue_pub,
+            current.seq() as usize,
+            current.slot,
+        )?;
+        history::verify_chain(&records, &current)?;
+        info!(
+            "queue {} hash chain verified with {} pushes",
+            queue_name,
+            current.seq()
+        );
+        return Ok(());
+    }
+    let push_instruction = |data: Vec<u8>| -> ddmonitor::error::Result<Instruction> {
+        if queue.has_hook() {
+            sdk::create_hook_instruction(
+                pub_key,
+                queue_pub,
+                settings.program_id.clone(),
+                queue_name.clone(),
+                data,
+                queue.hook,
+                args.hook_accounts
+                    .iter()
+                    .map(|account| sdk::parse_hook_account(account))
+                    .collect::<ddmonitor::error::Result<_>>()?,
+            )
+        } else {
+            sdk::create_instruction(
+                pub_key,
+                queue_pub,
+                settings.program_id.clone(),
+                queue_name.clone(),
+                data,
+            )
+        }
+    };
+
+    if let Some(entry) = &args.put {
+        let Some((key, value)) = entry.split_once('=') else {
+            error!("put expects KEY=VALUE , got : {}", entry);
+            return Ok(());
+        };
+        kv::put(
+            &connection,
+            &pair,
+            &program_account,
+            &queue_name,
+            key,
+            value.as_bytes().to_vec(),
+            &settings.send,
+        )?;
+        return Ok(());
+    }
+    if let Some(key) = &args.get {
+        match kv::get(&connection, &program_account, &queue_pub, key)? {
+            Some(entry) => info!("{} = {}", key, String::from_utf8_lossy(&entry.value)),
+            None => info!("{} is not set", key),
+        }
+        return Ok(());
+    }
+    if let Some(key) = &args.delete {
+        kv::delete(
+            &connection,
+            &pair,
+            &program_account,
+            &queue_name,
+            key,
+            &settings.send,
+        )?;
+        return Ok(());
+    }
+    if args.scan {
+        for entry in kv::scan(&connection, &program_account, &queue_pub)? {
+            info!("{} = {}", entry.key, String::from_utf8_lossy(&entry.value));
+        }
+        return Ok(());
+    }
+
+    if !args.reply_to.is_empty() {
+        let requester = reply::Requester {
+            network: &network,
+            connection: &connection,
+            payer: &pair,
+            program_id: settings.program_id.clone(),
+            reply_to: args.reply_to.clone(),
+            send_config: settings.send,
+        };
+        let response = requester.call(
+            &queue_name,
+            handlers::ActionInfo::Raw(args.message.clone()).into(),
+            Duration::from_secs(args.timeout),
+        )?;
+        handlers::ActionInfo::from(response).do_action();
+        return Ok(());
+    }
 
     sdk::send_instruction(
         &connection,
         &pub_key,
         &vec![&pair],
-        sdk::create_instruction(
-            pub_key.clone(),
-            queue_pub.clone(),
-            args.program.clone(),
-            args.name.clone(),
-            handlers::ActionInfo::ActionSample(1, 2).into(),
-        ),
-    );
+        push_instruction(payload::encode_text(&queue.content_type, &args.message)?)?,
+        &settings.send,
+    )?;
+
+    if queue.content_type == models::ContentType::Action {
+        sdk::send_instruction(
+            &connection,
+            &pub_key,
+            &vec![&pair],
+            push_instruction(handlers::ActionInfo::ActionSample(1, 2).into())?,
+            &settings.send,
+        )?;
+    }
 
     loop {
-        info!("you will write these lines to this queue: -> {}", args.name);
+        info!(
+            "you will write these lines to this queue: -> {}",
+            queue_name
+        );
         let mut line = String::new();
-        std::io::stdin().read_line(&mut line).unwrap();
+        std::io::stdin().read_line(&mut line)?;
         if line.trim() == "exit" {
             break;
         } else {
-            sdk::send_instruction(
-                &connection,
-                &pub_key,
-                &vec![&pair],
-                sdk::create_instruction(
-                    pub_key.clone(),
-                    queue_pub.clone(),
-                    args.program.clone(),
-                    args.name.clone(),
-                    handlers::ActionInfo::Raw(line).into(),
-                ),
-            );
+            match payload::encode_text(&queue.content_type, line.trim_end()) {
+                Ok(data) => {
+                    if let Err(e) = sdk::send_instruction(
+                        &connection,
+                        &pub_key,
+                        &vec![&pair],
+                        push_instruction(data)?,
+                        &settings.send,
+                    ) {
+                        error!("message not sent : {:?}", e);
+                    }
+                }
+                Err(e) => error!("can not encode line : {:?}", e),
+            }
         }
     }
 
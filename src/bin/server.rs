@@ -1,45 +1,95 @@
 use {
     clap::Parser,
-    contract::instruction::InstructionData,
-    ddmonitor::{ddmonitor_init, handlers, runtime, sdk},
+    contract::instruction,
+    ddmonitor::{
+        actions, config, consumer, ddmonitor_init, funding, handlers, history, kv, metadata,
+        payload, reply, runtime, sdk, webhook,
+    },
     env_logger::Env,
     log::{error, info},
-    solana_program::{instruction::AccountMeta, system_program},
-    solana_sdk::{instruction::Instruction, signer::Signer, transaction::Transaction},
+    solana_program::pubkey::Pubkey,
+    solana_sdk::signer::Signer,
 };
 
 /// One server to watch ddmonitor queue and print message
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// Name of the queue
-    #[arg(short, long, default_value_t = String::from("default"))]
-    name: String,
+    /// Name of the queue, defaults to the configured queue
+    #[arg(short, long)]
+    name: Option<String>,
 
     #[arg(short, long, default_value_t = String::from("default"))]
     allow: String,
 
-    /// Network to communicate with
-    #[arg(long, default_value_t = String::from("local"))]
-    network: String,
+    #[command(flatten)]
+    common: config::CommonArgs,
 
-    /// Solana program address
-    #[arg(short, long, default_value_t = String::from(runtime::DEFAULT_PROGRAM))]
-    program: String,
-}
+    /// Consumer group to ack handled messages with, empty to only watch
+    #[arg(short, long, default_value_t = String::from(""))]
+    group: String,
+
+    /// Answer request envelopes pushed to the queue instead of only watching
+    #[arg(long, default_value_t = false)]
+    respond: bool,
+
+    /// Hook program invoked by the queue on every push
+    #[arg(long)]
+    hook: Option<Pubkey>,
+
+    /// Watch one key of the queue key value store instead of the queue
+    #[arg(long)]
+    watch_key: Option<String>,
+
+    /// Content type of a new queue: raw, text, action, json or schema:<id>,
+    /// defaults to the configured queue content type, then action
+    #[arg(long)]
+    content_type: Option<String>,
+
+    /// Display name shown for the queue, sets the queue metadata
+    #[arg(long)]
+    display_name: Option<String>,
+
+    /// Description of the queue, sets the queue metadata
+    #[arg(long)]
+    description: Option<String>,
+
+    /// Comma separated tags of the queue, sets the queue metadata
+    #[arg(long)]
+    tags: Option<String>,
+
+    /// Icon or homepage URI of the queue, sets the queue metadata
+    #[arg(long)]
+    uri: Option<String>,
 
-const DATA_SIZE: usize = 64;
-const ALLOW_COUNT: u8 = 3;
+    /// Replay the pushes found in transaction history through the handlers
+    /// and exit
+    #[arg(long, default_value_t = false)]
+    replay: bool,
+
+    /// Only replay pushes with a sequence greater than this
+    #[arg(long, default_value_t = 0)]
+    from_seq: u64,
+
+    /// POST every queue update to the webhooks instead of running handlers
+    #[arg(long, default_value_t = false)]
+    webhook: bool,
+
+    /// Webhook URL, repeat for several, replaces the configured URLs
+    #[arg(long)]
+    webhook_url: Vec<String>,
+}
 
 #[tokio::main]
-async fn main() -> std::io::Result<()> {
+async fn main() -> ddmonitor::error::Result<()> {
     env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
     ddmonitor_init!();
-    runtime::init_app();
+    runtime::init_app()?;
     let args = Args::parse();
-    let network = sdk::Network::from_string(&args.network);
+    let settings = config::load(&args.common)?;
+    let network = settings.network.clone();
     info!("network is : {:?}", network);
-    let pair = sdk::init_solana_wallet().unwrap();
+    let pair = settings.keypair()?;
     let pub_key = pair.pubkey();
 
     info!(
@@ -48,74 +98,209 @@ async fn main() -> std::io::Result<()> {
     );
     let connection = sdk::get_rpc_client(&network);
     sdk::connection_available(&connection)?;
-    sdk::confirm_balance(&connection, &network, &pub_key, 5);
-    let program_account = runtime::program_account(args.program.clone());
-    if !sdk::program_available(&connection, &program_account) {
+    let (data_size, allow_count) = settings.queue.size();
+    funding::fund_wallet(
+        &connection,
+        &network,
+        &pub_key,
+        Some((data_size, allow_count)),
+        &settings.funding,
+        &settings.send.compute_budget,
+    )?;
+    let program_account = settings.program_account()?;
+    if !sdk::program_available(&connection, &program_account)? {
         error!("program account is not available , exit...");
         return Ok(());
     }
 
     info!("now sol is ready , create one account for ddmonitor... ");
 
-    let queue_name = args.name;
+    let queue_name = settings.queue_name(args.name.as_deref());
     let queue_pub = sdk::pda_queue_account(&program_account, &queue_name);
     info!("queue account is : {:?}", queue_pub);
 
-    let queue_avaliable = {
-        let queue_info = connection.get_account(&queue_pub);
-        if queue_info.is_err() {
-            false
-        } else {
-            let queue_info = queue_info.unwrap();
-            queue_info.owner == args.program.parse().unwrap()
+    if args.webhook {
+        let mut config = settings.webhook.clone();
+        if !args.webhook_url.is_empty() {
+            config.urls = args.webhook_url.clone();
+        }
+        let forwarder = webhook::WebhookForwarder::new(config, program_account, &queue_name)?;
+        if args.replay {
+            let count = history::replay(
+                &connection,
+                &program_account,
+                &queue_pub,
+                args.from_seq,
+                forwarder,
+            )?;
+            info!("forwarded {} pushes of : {}", count, queue_name);
+            return Ok(());
+        }
+        if !args.group.is_empty() {
+            let consumer = consumer::Consumer::new(
+                &connection,
+                settings.program_id.clone(),
+                &queue_name,
+                &args.group,
+                &pub_key,
+                settings.send,
+            )?;
+            // acked once delivered or written to the dead letter file
+            let mut forwarder = forwarder.wait_for_delivery();
+            return consumer.run_checked(
+                &network,
+                &connection,
+                &pair,
+                |update: &sdk::QueueUpdate| forwarder.forward(update),
+            );
+        }
+        return sdk::get_account_updates(&network, &queue_pub, forwarder);
+    }
+
+    let mut registry = actions::ActionRegistry::new();
+    registry.load(&settings)?;
+    let queue_actions = registry
+        .for_queue(&queue_name, queue_pub)
+        .with_replier(actions::Replier {
+            connection: &connection,
+            payer: &pair,
+            program_id: settings.program_id.clone(),
+            send_config: settings.send,
+        });
+
+    if args.replay {
+        let count = history::replay(
+            &connection,
+            &program_account,
+            &queue_pub,
+            args.from_seq,
+            queue_actions,
+        )?;
+        info!("replayed {} pushes of : {}", count, queue_name);
+        return Ok(());
+    }
+
+    let queue_avaliable = match connection.get_account(&queue_pub) {
+        Ok(queue_info) => {
+            queue_info.owner == program_account
                 && queue_info.lamports > 0
                 && !queue_info.executable
-                && queue_info.data.len() > 0
+                && !queue_info.data.is_empty()
         }
+        Err(_) => false,
     };
 
     if !queue_avaliable {
-        let accounts = vec![
-            AccountMeta::new(pub_key.clone(), true),
-            AccountMeta::new(queue_pub.clone(), false),
-            AccountMeta::new_readonly(system_program::ID, false),
-        ];
-
-        // Create the instruction by serializing our instruction data via borsh
-        let instruction = Instruction::new_with_borsh(
-            runtime::program_account(args.program.clone()),
-            &InstructionData::RegisterQueue {
-                name: queue_name.to_string(),
-                data_size: DATA_SIZE,
-                allow_count: ALLOW_COUNT,
-            },
-            accounts,
-        );
-
-        let blockhash = connection.get_latest_blockhash().unwrap();
-        let transaction =
-            Transaction::new_signed_with_payer(&[instruction], Some(&pub_key), &[&pair], blockhash);
-
+        let content_type = payload::parse_content_type(
+            args.content_type
+                .as_deref()
+                .or(settings.queue.content_type.as_deref())
+                .unwrap_or("action"),
+        )?;
         info!("create queue request send ...");
-        match connection.send_and_confirm_transaction(&transaction) {
-            Ok(tx) => {
-                info!("create queue account tx : {:?}", tx);
-                if args.allow != "default" {
-                    error!("no allow account , exit...");
-                    return Ok(());
-                }
-            }
-            Err(err) => {
-                let _transaction_err = err.get_transaction_error().unwrap();
-                error!("create queue account error : {:?}", _transaction_err);
-                error!("create queue account error : {:?}", err);
-            }
+        let result = sdk::send_instruction(
+            &connection,
+            &pub_key,
+            &vec![&pair],
+            instruction::register_queue(
+                &program_account,
+                &pub_key,
+                &queue_pub,
+                &queue_name,
+                data_size,
+                allow_count,
+                content_type,
+            ),
+            &settings.send,
+        )?;
+        info!("create queue account tx : {}", result.signature);
+        if args.allow != "default" {
+            error!("no allow account , exit...");
+            return Ok(());
         }
     } else {
         info!("queue account is exist , skip create...");
     }
 
-    sdk::get_account_updates(&network, &queue_pub, handlers::main).unwrap();
+    if let Some(hook) = args.hook {
+        sdk::send_instruction(
+            &connection,
+            &pub_key,
+            &vec![&pair],
+            sdk::create_set_hook_instruction(
+                pub_key,
+                queue_pub,
+                settings.program_id.clone(),
+                queue_name.clone(),
+                hook,
+            )?,
+            &settings.send,
+        )?;
+    }
+
+    if args.display_name.is_some()
+        || args.description.is_some()
+        || args.tags.is_some()
+        || args.uri.is_some()
+    {
+        let current = metadata::fetch_metadata(&connection, &program_account, &queue_pub)?
+            .unwrap_or_default();
+        let update = contract::models::Metadata {
+            display_name: args.display_name.clone().unwrap_or(current.display_name),
+            description: args.description.clone().unwrap_or(current.description),
+            tags: args
+                .tags
+                .as_deref()
+                .map(metadata::parse_tags)
+                .unwrap_or(current.tags),
+            uri: args.uri.clone().unwrap_or(current.uri),
+            ..Default::default()
+        };
+        metadata::set(
+            &connection,
+            &pair,
+            &program_account,
+            &queue_name,
+            &update,
+            &settings.send,
+        )?;
+    }
+
+    if let Some(key) = &args.watch_key {
+        return kv::watch_key(
+            &network,
+            &program_account,
+            &queue_pub,
+            key,
+            handlers::kv_main,
+        );
+    }
+
+    if args.respond {
+        return reply::respond(
+            &network,
+            &connection,
+            &pair,
+            settings.program_id.clone(),
+            &queue_name,
+            &settings.send,
+            handlers::respond_action,
+        );
+    }
+
+    if !args.group.is_empty() {
+        let consumer = consumer::Consumer::new(
+            &connection,
+            settings.program_id.clone(),
+            &queue_name,
+            &args.group,
+            &pub_key,
+            settings.send,
+        )?;
+        return consumer.run(&network, &connection, &pair, queue_actions);
+    }
+
+    sdk::get_account_updates(&network, &queue_pub, queue_actions)?;
 
     Ok(())
 }
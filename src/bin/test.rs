@@ -6,9 +6,9 @@ enum MyEnum {
     ByteData(u8),
 }
 
-impl Into<Vec<u8>> for MyEnum {
-    fn into(self) -> Vec<u8> {
-        match self {
+impl From<MyEnum> for Vec<u8> {
+    fn from(val: MyEnum) -> Self {
+        match val {
             MyEnum::StringData(s) => s.as_bytes().to_vec(),
             MyEnum::ByteData(n) => vec![n],
         }
@@ -0,0 +1,223 @@
+use {
+    crate::{
+        error::{DdMonitorError, Result},
+        history,
+        sdk::{self, Network, QueueHandler, QueueUpdate},
+        sender::{self, SendConfig, SendResult},
+    },
+    contract::{instruction, models},
+    futures::{channel::mpsc, Stream},
+    log::info,
+    solana_client::nonblocking::rpc_client::RpcClient,
+    solana_program::{instruction::AccountMeta, pubkey::Pubkey},
+    solana_sdk::{
+        commitment_config::CommitmentConfig, instruction::Instruction, signer::keypair::Keypair,
+        signer::Signer,
+    },
+};
+
+/// Async ddmonitor client built on the nonblocking RPC and pubsub clients,
+/// for tokio applications that must not park a thread per subscription.
+pub struct DdMonitorClient {
+    network: Network,
+    connection: RpcClient,
+    payer: Keypair,
+    program_id: Pubkey,
+    send_config: SendConfig,
+}
+
+impl DdMonitorClient {
+    pub fn new(network: Network, program_id: Pubkey, payer: Keypair) -> Self {
+        let connection =
+            RpcClient::new_with_commitment(network.get_rpc_url(), CommitmentConfig::confirmed());
+        Self {
+            network,
+            connection,
+            payer,
+            program_id,
+            send_config: SendConfig::default(),
+        }
+    }
+
+    /// Send with `config` instead of the default send config.
+    pub fn with_send_config(mut self, config: SendConfig) -> Self {
+        self.send_config = config;
+        self
+    }
+
+    pub fn connection(&self) -> &RpcClient {
+        &self.connection
+    }
+
+    pub fn payer(&self) -> Pubkey {
+        self.payer.pubkey()
+    }
+
+    pub fn queue_address(&self, name: &str) -> Pubkey {
+        let (pda, _nonce) = models::Queue::find_address(name, &self.program_id);
+        pda
+    }
+
+    pub async fn fetch(&self, name: &str) -> Result<models::Queue> {
+        let account = self
+            .connection
+            .get_account(&self.queue_address(name))
+            .await?;
+        models::Queue::unpack(&account.data).map_err(DdMonitorError::decode)
+    }
+
+    pub async fn create(
+        &self,
+        name: &str,
+        data_size: usize,
+        allow_count: u8,
+        content_type: models::ContentType,
+    ) -> Result<SendResult> {
+        info!("create queue : {}", name);
+        self.send(instruction::register_queue(
+            &self.program_id,
+            &self.payer.pubkey(),
+            &self.queue_address(name),
+            name,
+            data_size,
+            allow_count,
+            content_type,
+        ))
+        .await
+    }
+
+    /// Push `data`, passing the hook program along when the queue has one.
+    pub async fn push(&self, name: &str, data: Vec<u8>) -> Result<SendResult> {
+        self.push_with_accounts(name, data, vec![]).await
+    }
+
+    /// Push to a queue, passing `hook_accounts` on to its hook program.
+    /// They are ignored when the queue has no hook.
+    pub async fn push_with_accounts(
+        &self,
+        name: &str,
+        data: Vec<u8>,
+        hook_accounts: Vec<AccountMeta>,
+    ) -> Result<SendResult> {
+        let queue = self.fetch(name).await?;
+        let queue_pub = self.queue_address(name);
+        let payer = self.payer.pubkey();
+        let instruction = if queue.has_hook() {
+            instruction::push_message_with_hook(
+                &self.program_id,
+                &payer,
+                &queue_pub,
+                name,
+                data,
+                &queue.hook,
+                hook_accounts,
+            )
+        } else {
+            instruction::push_message(&self.program_id, &payer, &queue_pub, name, data)
+        };
+        self.send(instruction).await
+    }
+
+    pub async fn allow(&self, name: &str, user: &Pubkey) -> Result<SendResult> {
+        self.user_pub_operation(name, user, true).await
+    }
+
+    pub async fn revoke(&self, name: &str, user: &Pubkey) -> Result<SendResult> {
+        self.user_pub_operation(name, user, false).await
+    }
+
+    /// Close the queue and return its rent to the creator.
+    pub async fn close(&self, name: &str) -> Result<SendResult> {
+        info!("close queue : {}", name);
+        self.send(instruction::close_queue(
+            &self.program_id,
+            &self.payer.pubkey(),
+            &self.queue_address(name),
+            name,
+        ))
+        .await
+    }
+
+    /// Rewrite a queue created before layouts were versioned in the current
+    /// layout, only the creator can and pays the extra rent.
+    pub async fn migrate(&self, name: &str) -> Result<SendResult> {
+        info!("migrate queue : {}", name);
+        self.send(instruction::migrate_queue(
+            &self.program_id,
+            &self.payer.pubkey(),
+            &self.queue_address(name),
+            name,
+        ))
+        .await
+    }
+
+    /// Decoded updates of queue `name`, every push after this returns is
+    /// delivered in order. The watch reconnects and backfills missed pushes
+    /// like `sdk::get_account_updates`, and stops once the stream is dropped.
+    pub async fn updates(
+        &self,
+        name: &str,
+    ) -> Result<impl Stream<Item = QueueUpdate> + Send + Unpin> {
+        let queue_pub = self.queue_address(name);
+        let current = sdk::nonblocking::fetch_queue_update(&self.connection, &queue_pub).await?;
+        let anchor = history::ChainAnchor {
+            seq: current.seq(),
+            hash: current.queue.hash,
+        };
+        let network = self.network.clone();
+        let (update_sender, update_receiver) = mpsc::unbounded();
+        tokio::spawn(async move {
+            let handler = StreamHandler(update_sender);
+            sdk::nonblocking::get_account_updates_from(&network, &queue_pub, anchor, handler).await;
+        });
+        Ok(update_receiver)
+    }
+
+    async fn user_pub_operation(
+        &self,
+        name: &str,
+        user: &Pubkey,
+        allow: bool,
+    ) -> Result<SendResult> {
+        info!("set allow of {} on {} to {}", user, name, allow);
+        self.send(instruction::user_pub_operation(
+            &self.program_id,
+            &self.payer.pubkey(),
+            &self.queue_address(name),
+            name,
+            user,
+            allow,
+        ))
+        .await
+    }
+
+    async fn send(&self, instruction: Instruction) -> Result<SendResult> {
+        let result = sender::nonblocking::send_transaction(
+            &self.connection,
+            &self.payer.pubkey(),
+            &[&self.payer],
+            &[instruction],
+            &self.program_id,
+            &self.send_config,
+        )
+        .await?;
+        info!(
+            "send message tx : {} at slot {}",
+            result.signature, result.slot
+        );
+        Ok(result)
+    }
+}
+
+/// Feeds an `updates` stream, closed once the stream is dropped.
+struct StreamHandler(mpsc::UnboundedSender<QueueUpdate>);
+
+impl QueueHandler for StreamHandler {
+    fn handle(&mut self, update: &QueueUpdate) {
+        let _ = self.0.unbounded_send(update.clone());
+    }
+
+    fn is_open(&self) -> bool {
+        !self.0.is_closed()
+    }
+}
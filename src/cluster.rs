@@ -0,0 +1,128 @@
+use {
+    crate::{
+        error::{DdMonitorError, Result},
+        sdk::Network,
+    },
+    log::info,
+    std::{env, fs, path::PathBuf},
+};
+
+/// Names the network, either a cluster name or an rpc url.
+pub const NETWORK_ENV: &str = "DDMONITOR_NETWORK";
+/// Rpc url, overrides `DDMONITOR_NETWORK`.
+pub const RPC_URL_ENV: &str = "DDMONITOR_RPC_URL";
+/// Websocket url, derived from the rpc url when missing.
+pub const WS_URL_ENV: &str = "DDMONITOR_WS_URL";
+/// Location of the solana cli config, default `~/.config/solana/cli/config.yml`.
+pub const SOLANA_CONFIG_ENV: &str = "DDMONITOR_SOLANA_CONFIG";
+
+/// The fields ddmonitor uses from the solana cli `config.yml`.
+#[derive(Debug, Clone, Default)]
+pub struct SolanaCliConfig {
+    pub json_rpc_url: Option<String>,
+    pub websocket_url: Option<String>,
+    pub keypair_path: Option<String>,
+    pub commitment: Option<String>,
+}
+
+impl SolanaCliConfig {
+    pub fn default_path() -> Option<PathBuf> {
+        if let Ok(path) = env::var(SOLANA_CONFIG_ENV) {
+            return Some(PathBuf::from(path));
+        }
+        let home = env::var_os("HOME")?;
+        Some(PathBuf::from(home).join(".config/solana/cli/config.yml"))
+    }
+
+    /// Read the config at the default path, `None` when there is no file.
+    pub fn load() -> Result<Option<Self>> {
+        let Some(path) = Self::default_path() else {
+            return Ok(None);
+        };
+        if !path.exists() {
+            return Ok(None);
+        }
+        let text = fs::read_to_string(&path)?;
+        info!("read solana cli config : {}", path.display());
+        Self::parse(&text).map(Some)
+    }
+
+    /// The cli writes a flat `key: value` map, nested keys are ignored.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut config = Self::default();
+        for line in text.lines() {
+            if line.starts_with(char::is_whitespace) || line.trim_start().starts_with('#') {
+                continue;
+            }
+            let line = line.trim();
+            if line.is_empty() || line == "---" {
+                continue;
+            }
+            let Some((key, value)) = line.split_once(':') else {
+                return Err(DdMonitorError::Config(format!(
+                    "invalid solana cli config line : {}",
+                    line
+                )));
+            };
+            let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+            let value = (!value.is_empty()).then(|| value.to_string());
+            match key.trim() {
+                "json_rpc_url" => config.json_rpc_url = value,
+                "websocket_url" => config.websocket_url = value,
+                "keypair_path" => config.keypair_path = value,
+                "commitment" => config.commitment = value,
+                _ => {}
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn network(&self) -> Result<Option<Network>> {
+        let Some(rpc) = &self.json_rpc_url else {
+            return Ok(None);
+        };
+        let network = match &self.websocket_url {
+            Some(ws) => Network::custom(rpc, ws)?,
+            None => rpc.parse()?,
+        };
+        Ok(Some(network))
+    }
+}
+
+/// The environment variable `name`, unset when empty.
+pub fn env_value(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|value| !value.trim().is_empty())
+}
+
+/// The network named by the environment, if any.
+pub fn env_network() -> Result<Option<Network>> {
+    if let Some(rpc) = env_value(RPC_URL_ENV) {
+        let network = match env_value(WS_URL_ENV) {
+            Some(ws) => Network::custom(&rpc, &ws)?,
+            None => rpc.parse()?,
+        };
+        return Ok(Some(network));
+    }
+    env_value(NETWORK_ENV).map(|name| name.parse()).transpose()
+}
+
+/// Pick the network from, in order : the `--network` flag, the environment,
+/// the solana cli config and finally the local validator.
+pub fn resolve_network(flag: Option<&str>) -> Result<Network> {
+    if let Some(name) = flag {
+        return name.parse();
+    }
+    if let Some(network) = env_network()? {
+        info!("network from environment");
+        return Ok(network);
+    }
+    if let Some(network) = SolanaCliConfig::load()?
+        .map(|config| config.network())
+        .transpose()?
+        .flatten()
+    {
+        info!("network from solana cli config");
+        return Ok(network);
+    }
+    Ok(Network::Local)
+}
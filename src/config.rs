@@ -0,0 +1,337 @@
+use {
+    crate::{
+        cluster::{self, env_value},
+        error::{DdMonitorError, Result},
+        funding::{FundingArgs, FundingConfig},
+        runtime,
+        sdk::Network,
+        sender::{SendArgs, SendConfig},
+        wallet::{self, WalletArgs},
+        webhook::{self, WebhookConfig},
+    },
+    clap::Args,
+    log::info,
+    serde::{de::DeserializeOwned, Deserialize, Serialize},
+    solana_program::pubkey::Pubkey,
+    solana_sdk::signer::keypair::Keypair,
+    std::{collections::BTreeMap, fmt::Display, fs, path::PathBuf, str::FromStr},
+};
+
+/// Config file to read instead of `config.toml` under `app_path`.
+pub const CONFIG_ENV: &str = "DDMONITOR_CONFIG";
+/// Profile to use when `--profile` is not given.
+pub const PROFILE_ENV: &str = "DDMONITOR_PROFILE";
+/// Program id, overrides the profile.
+pub const PROGRAM_ENV: &str = "DDMONITOR_PROGRAM";
+/// Queue name, overrides the `[queue]` table.
+pub const QUEUE_ENV: &str = "DDMONITOR_QUEUE";
+/// Send and funding settings are read from this prefix and the flag name,
+/// e.g. `DDMONITOR_COMPUTE_UNIT_PRICE`, over the `[send]` and `[funding]`
+/// tables.
+pub const SETTING_ENV_PREFIX: &str = "DDMONITOR_";
+
+pub const CONFIG_PATH: &str = "config.toml";
+pub const DEFAULT_PROFILE: &str = "default";
+pub const DEFAULT_QUEUE: &str = "default";
+pub const DEFAULT_DATA_SIZE: usize = 64;
+pub const DEFAULT_ALLOW_COUNT: u8 = 5;
+
+/// One cluster the binaries can talk to.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    /// Cluster name or rpc url, the profile name when it is a cluster name.
+    pub network: Option<String>,
+    /// Websocket url, derived from the rpc url when missing.
+    pub ws_url: Option<String>,
+    pub program: Option<String>,
+    pub keypair: Option<String>,
+}
+
+/// Defaults for queues created or watched by the binaries.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct QueueDefaults {
+    pub name: Option<String>,
+    pub data_size: Option<usize>,
+    pub allow_count: Option<u8>,
+    pub content_type: Option<String>,
+}
+
+impl QueueDefaults {
+    /// Data size and allow count of a new queue.
+    pub fn size(&self) -> (usize, u8) {
+        (
+            self.data_size.unwrap_or(DEFAULT_DATA_SIZE),
+            self.allow_count.unwrap_or(DEFAULT_ALLOW_COUNT),
+        )
+    }
+}
+
+/// The `config.toml` file :
+///
+/// ```toml
+/// profile = "dev"
+///
+/// [profiles.dev]
+/// program = "CkJ4NC4KCQfoXvyYj9Xxs4LkGDi34zNzE1e2EEeq1h9x"
+/// keypair = "~/.config/solana/id.json"
+///
+/// [profiles.private]
+/// network = "https://rpc.example.com/key"
+/// ws_url = "wss://rpc.example.com/key"
+///
+/// [queue]
+/// name = "alerts"
+/// data_size = 64
+///
+/// [handlers.log]
+/// actions = ["raw", "sample"]
+///
+/// [webhook]
+/// urls = ["https://hooks.example.com/ddmonitor"]
+///
+/// [send]
+/// commitment = "finalized"
+/// compute_unit_price = "auto"
+///
+/// [funding]
+/// fee_count = 20
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigFile {
+    pub profile: Option<String>,
+    pub profiles: BTreeMap<String, Profile>,
+    pub queue: QueueDefaults,
+    /// Settings of each handler, keyed by handler name.
+    pub handlers: BTreeMap<String, toml::Value>,
+    pub webhook: WebhookConfig,
+    pub send: SendArgs,
+    pub funding: FundingArgs,
+}
+
+impl ConfigFile {
+    pub fn parse(text: &str) -> Result<Self> {
+        toml::from_str(text).map_err(|e| DdMonitorError::Config(format!("invalid config : {}", e)))
+    }
+
+    /// Read `path`, a missing file is an empty config unless `required`.
+    pub fn read(path: &PathBuf, required: bool) -> Result<Self> {
+        if !path.exists() {
+            if required {
+                return Err(DdMonitorError::NotFound(format!(
+                    "config file {}",
+                    path.display()
+                )));
+            }
+            return Ok(Self::default());
+        }
+        info!("read config file : {}", path.display());
+        let text = fs::read_to_string(path)?;
+        Self::parse(&text)
+            .map_err(|e| DdMonitorError::Config(format!("{} in {}", e, path.display())))
+    }
+}
+
+/// Flags shared by every binary, they override the environment and the
+/// config file.
+#[derive(Args, Debug, Clone, Default)]
+pub struct CommonArgs {
+    /// Config file, defaults to `DDMONITOR_CONFIG` then config.toml under
+    /// the app path
+    #[arg(long, global = true)]
+    pub config: Option<String>,
+
+    /// Profile of the config file to use
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
+
+    /// Network to communicate with : local, dev, test, main-beta or an rpc
+    /// url, defaults to the environment, the profile, then the solana cli
+    /// config
+    #[arg(long, global = true)]
+    pub network: Option<String>,
+
+    /// Solana program address
+    #[arg(short, long, global = true)]
+    pub program: Option<String>,
+
+    #[command(flatten)]
+    pub wallet: WalletArgs,
+
+    #[command(flatten)]
+    pub send: SendArgs,
+
+    #[command(flatten)]
+    pub funding: FundingArgs,
+}
+
+/// Settings after layering flags over environment over config file.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub profile: String,
+    pub network: Network,
+    pub program_id: String,
+    pub wallet: WalletArgs,
+    /// How transactions are sent, compute budget included.
+    pub send: SendConfig,
+    /// How the wallet is funded before the binaries start.
+    pub funding: FundingConfig,
+    pub queue: QueueDefaults,
+    pub handlers: BTreeMap<String, toml::Value>,
+    pub webhook: WebhookConfig,
+}
+
+/// The network of profile `name` : its rpc and websocket urls, its network
+/// alone, else the profile name when that names a cluster.
+pub fn profile_network(name: &str, profile: &Profile) -> Result<Option<Network>> {
+    match (&profile.network, &profile.ws_url) {
+        (Some(rpc), Some(ws)) => Network::custom(rpc, ws).map(Some),
+        (Some(network), None) => network.parse().map(Some),
+        (None, _) => Ok(name.parse().ok()),
+    }
+}
+
+/// The environment value of the setting behind `flag`, parsed.
+fn setting_env<T>(flag: &str) -> Result<Option<T>>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    let name = format!("{}{}", SETTING_ENV_PREFIX, flag.to_uppercase());
+    env_value(&name)
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|e| DdMonitorError::Config(format!("invalid {} : {}", name, e)))
+        })
+        .transpose()
+}
+
+fn send_env() -> Result<SendArgs> {
+    Ok(SendArgs {
+        max_retries: setting_env("max_retries")?,
+        commitment: setting_env("commitment")?,
+        poll_interval_ms: setting_env("poll_interval_ms")?,
+        compute_unit_limit: setting_env("compute_unit_limit")?,
+        compute_unit_price: setting_env("compute_unit_price")?,
+    })
+}
+
+fn funding_env() -> Result<FundingArgs> {
+    Ok(FundingArgs {
+        fee_count: setting_env("fee_count")?,
+        airdrop_retries: setting_env("airdrop_retries")?,
+        airdrop_timeout_secs: setting_env("airdrop_timeout_secs")?,
+        max_airdrop_lamports: setting_env("max_airdrop_lamports")?,
+        airdrop_custom: setting_env("airdrop_custom")?,
+    })
+}
+
+/// Load the config and layer the environment and `args` over it.
+pub fn load(args: &CommonArgs) -> Result<Settings> {
+    let (path, required) = match args.config.clone().or_else(|| env_value(CONFIG_ENV)) {
+        Some(path) => (PathBuf::from(path), true),
+        None => (PathBuf::from(runtime::app_path(CONFIG_PATH)), false),
+    };
+    let file = ConfigFile::read(&path, required)?;
+
+    let explicit_profile = args.profile.clone().or_else(|| env_value(PROFILE_ENV));
+    let profile_name = explicit_profile
+        .clone()
+        .or(file.profile.clone())
+        .unwrap_or_else(|| DEFAULT_PROFILE.to_string());
+    let profile = match file.profiles.get(&profile_name) {
+        Some(profile) => profile.clone(),
+        None if explicit_profile.is_some() && profile_name.parse::<Network>().is_err() => {
+            return Err(DdMonitorError::NotFound(format!(
+                "profile {}",
+                profile_name
+            )));
+        }
+        None => Profile::default(),
+    };
+
+    let network = match &args.network {
+        Some(network) => network.parse()?,
+        None => match cluster::env_network()? {
+            Some(network) => network,
+            None => match profile_network(&profile_name, &profile)? {
+                Some(network) => network,
+                None => cluster::resolve_network(None)?,
+            },
+        },
+    };
+
+    let program_id = args
+        .program
+        .clone()
+        .or_else(|| env_value(PROGRAM_ENV))
+        .or(profile.program.clone())
+        .unwrap_or_else(|| runtime::DEFAULT_PROGRAM.to_string());
+
+    let mut wallet = args.wallet.clone();
+    if wallet.keypair.is_none() && env_value(wallet::KEYPAIR_ENV).is_none() {
+        wallet.keypair = profile.keypair.clone();
+    }
+
+    let mut queue = file.queue;
+    if let Some(name) = env_value(QUEUE_ENV) {
+        queue.name = Some(name);
+    }
+
+    let mut webhook = file.webhook;
+    if let Some(secret) = env_value(webhook::SECRET_ENV) {
+        webhook.secret = Some(secret);
+    }
+
+    let send = args.send.or(send_env()?).or(file.send).config();
+    let funding = args.funding.or(funding_env()?).or(file.funding).config();
+
+    info!(
+        "profile : {} , network : {:?} , program : {}",
+        profile_name, network, program_id
+    );
+    info!("send config : {:?} , funding : {:?}", send, funding);
+    Ok(Settings {
+        profile: profile_name,
+        network,
+        program_id,
+        wallet,
+        send,
+        funding,
+        queue,
+        handlers: file.handlers,
+        webhook,
+    })
+}
+
+impl Settings {
+    pub fn program_account(&self) -> Result<Pubkey> {
+        runtime::program_account(self.program_id.clone())
+    }
+
+    pub fn keypair(&self) -> Result<Keypair> {
+        wallet::load_keypair(&self.wallet)
+    }
+
+    /// The queue named by `flag`, else the configured default queue.
+    pub fn queue_name(&self, flag: Option<&str>) -> String {
+        flag.map(str::to_string)
+            .or(self.queue.name.clone())
+            .unwrap_or_else(|| DEFAULT_QUEUE.to_string())
+    }
+
+    /// The `[handlers.<name>]` table decoded as `T`, `None` when absent.
+    pub fn handler_settings<T: DeserializeOwned>(&self, name: &str) -> Result<Option<T>> {
+        self.handlers
+            .get(name)
+            .map(|value| {
+                value.clone().try_into().map_err(|e| {
+                    DdMonitorError::Config(format!("invalid handler settings {} : {}", name, e))
+                })
+            })
+            .transpose()
+    }
+}
@@ -0,0 +1,279 @@
+use {
+    crate::{
+        error::{DdMonitorError, Result},
+        history, runtime,
+        sdk::{self, Network, QueueHandler, QueueUpdate},
+        sender::{SendConfig, SendResult},
+    },
+    contract::{instruction, models},
+    log::{error, info, warn},
+    solana_client::rpc_client::RpcClient,
+    solana_program::pubkey::Pubkey,
+    solana_sdk::{instruction::Instruction, signer::keypair::Keypair, signer::Signer},
+    std::{thread, time::Duration},
+};
+
+/// Times a failed message is handed to the handler again before the
+/// consumer stops, waiting twice as long after each failure.
+const HANDLE_RETRIES: u32 = 3;
+const HANDLE_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+pub fn pda_cursor_account(
+    program_account: &Pubkey,
+    queue_pub: &Pubkey,
+    group: &str,
+    consumer_pub: &Pubkey,
+) -> Pubkey {
+    let (pda, _nonce) =
+        models::Cursor::find_address(queue_pub, group, consumer_pub, program_account);
+    pda
+}
+
+pub fn create_ack_instruction(
+    consumer_pub: Pubkey,
+    queue_pub: Pubkey,
+    program_id: String,
+    name: String,
+    group: String,
+    seq: u64,
+    hash: [u8; 32],
+) -> Result<Instruction> {
+    let program_account = runtime::program_account(program_id)?;
+    let cursor_pub = pda_cursor_account(&program_account, &queue_pub, &group, &consumer_pub);
+    info!(
+        "you will ack seq {} of : {} with group : {}, cursor account : {}",
+        seq, name, group, cursor_pub
+    );
+    Ok(instruction::ack(
+        &program_account,
+        &consumer_pub,
+        &queue_pub,
+        &name,
+        &group,
+        seq,
+        hash,
+    ))
+}
+
+/// The cursor account, flagged when it has the legacy layout without the
+/// acked hash.
+pub fn fetch_cursor(
+    connection: &RpcClient,
+    cursor_pub: &Pubkey,
+) -> Result<Option<(models::Cursor, bool)>> {
+    let account = connection
+        .get_account_with_commitment(cursor_pub, connection.commitment())?
+        .value;
+    match account {
+        Some(account) => Ok(Some(
+            models::Cursor::unpack_versioned(&account.data).map_err(DdMonitorError::decode)?,
+        )),
+        None => Ok(None),
+    }
+}
+
+/// One consumer of a queue inside a consumer group, it keeps the last
+/// acknowledged sequence on chain so it can resume after a restart.
+pub struct Consumer {
+    pub name: String,
+    pub group: String,
+    pub program_id: String,
+    pub queue_pub: Pubkey,
+    pub cursor_pub: Pubkey,
+    pub acked_seq: u64,
+    /// Chain hash after the acked push, unknown for a legacy cursor.
+    pub acked_hash: Option<[u8; 32]>,
+    pub send_config: SendConfig,
+}
+
+impl Consumer {
+    pub fn new(
+        connection: &RpcClient,
+        program_id: String,
+        name: &str,
+        group: &str,
+        consumer_pub: &Pubkey,
+        send_config: SendConfig,
+    ) -> Result<Self> {
+        let program_account = runtime::program_account(program_id.clone())?;
+        let queue_pub = sdk::pda_queue_account(&program_account, name);
+        let cursor_pub = pda_cursor_account(&program_account, &queue_pub, group, consumer_pub);
+        let (acked_seq, acked_hash) = match fetch_cursor(connection, &cursor_pub)? {
+            Some((cursor, false)) => (cursor.acked_seq, Some(cursor.acked_hash)),
+            Some((cursor, true)) => (cursor.acked_seq, None),
+            None => (0, Some(history::ChainAnchor::GENESIS.hash)),
+        };
+        info!(
+            "consumer of {} in group {} resume from seq : {}",
+            name, group, acked_seq
+        );
+        Ok(Self {
+            name: name.to_string(),
+            group: group.to_string(),
+            program_id,
+            queue_pub,
+            cursor_pub,
+            acked_seq,
+            acked_hash,
+            send_config,
+        })
+    }
+
+    /// Number of messages pushed to the queue that this consumer has not acked.
+    pub fn lag(&self, queue: &models::Queue) -> u64 {
+        queue.seq.saturating_sub(self.acked_seq)
+    }
+
+    /// Move the cursor to `seq`, `hash` is the queue chain hash after it.
+    pub fn ack(
+        &mut self,
+        connection: &RpcClient,
+        payer: &Keypair,
+        seq: u64,
+        hash: [u8; 32],
+    ) -> Result<SendResult> {
+        let result = sdk::send_instruction(
+            connection,
+            &payer.pubkey(),
+            &vec![payer],
+            create_ack_instruction(
+                payer.pubkey(),
+                self.queue_pub,
+                self.program_id.clone(),
+                self.name.clone(),
+                self.group.clone(),
+                seq,
+                hash,
+            )?,
+            &self.send_config,
+        )?;
+        self.acked_seq = seq;
+        self.acked_hash = Some(hash);
+        Ok(result)
+    }
+
+    /// Hand `update` to `handle` when it is newer than the cursor, then ack
+    /// its sequence. The watcher delivers every sequence in order, pushes the
+    /// account no longer holds are rebuilt from history, so each message is
+    /// acked only once `handle` returned Ok. A crash in between delivers the
+    /// message again. A failing `handle` is retried with backoff, once the
+    /// retries are spent its error is returned and the message stays unacked.
+    pub fn deliver(
+        &mut self,
+        connection: &RpcClient,
+        payer: &Keypair,
+        update: &QueueUpdate,
+        mut handle: impl FnMut(&QueueUpdate) -> Result<()>,
+    ) -> Result<()> {
+        let seq = update.seq();
+        if seq <= self.acked_seq {
+            return Ok(());
+        }
+        if seq > self.acked_seq + 1 {
+            warn!(
+                "deliver seq {} while acked seq is {} , an ack failed",
+                seq, self.acked_seq
+            );
+        }
+        let mut delay = HANDLE_RETRY_DELAY;
+        let mut retries = 0;
+        while let Err(e) = handle(update) {
+            if retries == HANDLE_RETRIES {
+                error!("handle {} error , not acked : {:?}", seq, e);
+                return Err(e);
+            }
+            warn!("handle {} error , retry in {:?} : {:?}", seq, delay, e);
+            thread::sleep(delay);
+            delay *= 2;
+            retries += 1;
+        }
+        if let Err(e) = self.ack(connection, payer, seq, update.queue.hash) {
+            error!("ack {} error , it will be delivered again : {:?}", seq, e);
+        }
+        Ok(())
+    }
+
+    /// Deliver every message after the cursor, the ones pushed while the
+    /// consumer was away first, then the new ones as they come.
+    pub fn run(
+        self,
+        network: &Network,
+        connection: &RpcClient,
+        payer: &Keypair,
+        mut handler: impl QueueHandler,
+    ) -> Result<()> {
+        self.run_checked(network, connection, payer, |update: &QueueUpdate| {
+            handler.handle(update);
+            Ok(())
+        })
+    }
+
+    /// Like `run`, but a message `handle` fails on is not acked. The run
+    /// stops with the error once its retries are spent, so no later message
+    /// is acked past it.
+    pub fn run_checked(
+        mut self,
+        network: &Network,
+        connection: &RpcClient,
+        payer: &Keypair,
+        handle: impl FnMut(&QueueUpdate) -> Result<()>,
+    ) -> Result<()> {
+        let update = sdk::fetch_queue_update(connection, &self.queue_pub)?;
+        info!(
+            "queue seq : {} , acked seq : {} , lag : {}",
+            update.seq(),
+            self.acked_seq,
+            self.lag(&update.queue)
+        );
+
+        let queue_pub = self.queue_pub;
+        let acked_seq = self.acked_seq;
+        let acked_hash = self.acked_hash;
+        let mut failure = None;
+        let checked = Checked {
+            consumer: &mut self,
+            connection,
+            payer,
+            handle,
+            failure: &mut failure,
+        };
+        match acked_hash {
+            Some(hash) => {
+                let anchor = history::ChainAnchor {
+                    seq: acked_seq,
+                    hash,
+                };
+                sdk::get_account_updates_from(network, &queue_pub, anchor, checked)?
+            }
+            None => sdk::get_account_updates_after(network, &queue_pub, acked_seq, checked)?,
+        }
+        failure.map_or(Ok(()), Err)
+    }
+}
+
+/// Delivers through the consumer and closes the watch once a message failed.
+struct Checked<'a, F> {
+    consumer: &'a mut Consumer,
+    connection: &'a RpcClient,
+    payer: &'a Keypair,
+    handle: F,
+    failure: &'a mut Option<DdMonitorError>,
+}
+
+impl<F: FnMut(&QueueUpdate) -> Result<()>> QueueHandler for Checked<'_, F> {
+    fn handle(&mut self, update: &QueueUpdate) {
+        if self.failure.is_some() {
+            return;
+        }
+        if let Err(e) = self
+            .consumer
+            .deliver(self.connection, self.payer, update, &mut self.handle)
+        {
+            *self.failure = Some(e);
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        self.failure.is_none()
+    }
+}
@@ -0,0 +1,102 @@
+use {
+    contract::errors::ContractError,
+    solana_client::{client_error::ClientError, pubsub_client::PubsubClientError},
+    solana_sdk::{instruction::InstructionError, pubkey::Pubkey, transaction::TransactionError},
+    std::fmt::Display,
+    thiserror::Error,
+};
+
+/// Every failure the ddmonitor SDK reports.
+#[derive(Error, Debug)]
+pub enum DdMonitorError {
+    #[error("rpc error : {0}")]
+    Rpc(Box<ClientError>),
+    #[error("pubsub error : {0}")]
+    Pubsub(Box<PubsubClientError>),
+    #[error("decode error : {0}")]
+    Decode(String),
+    /// The cluster executed the transaction and it failed, `contract` is
+    /// set when a ddmonitor instruction returned one of its own errors.
+    #[error("program error : {}", program_message(.error, .contract))]
+    Program {
+        error: TransactionError,
+        contract: Option<ContractError>,
+    },
+    #[error("config error : {0}")]
+    Config(String),
+    #[error("{0} not found")]
+    NotFound(String),
+    #[error("timed out : {0}")]
+    Timeout(String),
+    #[error("insufficient funds : {address} holds {balance} lamports but needs {required}")]
+    InsufficientFunds {
+        address: Pubkey,
+        balance: u64,
+        required: u64,
+    },
+    #[error("io error : {0}")]
+    Io(#[from] std::io::Error),
+}
+
+pub type Result<T> = std::result::Result<T, DdMonitorError>;
+
+impl DdMonitorError {
+    pub fn decode(e: impl Display) -> Self {
+        Self::Decode(e.to_string())
+    }
+
+    pub fn config(e: impl Display) -> Self {
+        Self::Config(e.to_string())
+    }
+}
+
+fn program_message(error: &TransactionError, contract: &Option<ContractError>) -> String {
+    match contract {
+        Some(contract) => contract.to_string(),
+        None => error.to_string(),
+    }
+}
+
+impl DdMonitorError {
+    /// Read the custom code of a failed instruction as a `ContractError`
+    /// when that instruction called `program_id`, other programs use the
+    /// same codes for their own errors. `programs` are the programs of the
+    /// transaction instructions, in order.
+    pub fn for_program(self, programs: &[Pubkey], program_id: &Pubkey) -> Self {
+        match self {
+            Self::Program {
+                error: TransactionError::InstructionError(index, InstructionError::Custom(code)),
+                ..
+            } if programs.get(index as usize) == Some(program_id) => Self::Program {
+                error: TransactionError::InstructionError(index, InstructionError::Custom(code)),
+                contract: ContractError::from_code(code),
+            },
+            error => error,
+        }
+    }
+}
+
+/// A generic program error, see `DdMonitorError::for_program`.
+impl From<TransactionError> for DdMonitorError {
+    fn from(error: TransactionError) -> Self {
+        Self::Program {
+            error,
+            contract: None,
+        }
+    }
+}
+
+impl From<ClientError> for DdMonitorError {
+    fn from(error: ClientError) -> Self {
+        match error.get_transaction_error() {
+            Some(transaction_error) => transaction_error.into(),
+            None => Self::Rpc(Box::new(error)),
+        }
+    }
+}
+
+impl From<PubsubClientError> for DdMonitorError {
+    fn from(error: PubsubClientError) -> Self {
+        Self::Pubsub(Box::new(error))
+    }
+}
@@ -0,0 +1,526 @@
+use {
+    crate::{
+        actions::{ActionContext, ActionHandler, ActionOutcome},
+        error::{DdMonitorError, Result},
+        handlers::ActionInfo,
+        runtime,
+    },
+    log::{info, warn},
+    serde::{Deserialize, Serialize},
+    solana_program::pubkey::Pubkey,
+    std::{
+        collections::BTreeMap,
+        fs,
+        io::Read,
+        path::{Path, PathBuf},
+        process::{Child, Command, ExitStatus, Stdio},
+        str::FromStr,
+        thread::{self, JoinHandle},
+        time::{Duration, Instant},
+    },
+};
+
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_MAX_OUTPUT_BYTES: usize = 256;
+const DEFAULT_PATH: &str = "/usr/bin:/bin";
+const DEFAULT_STATE_PATH: &str = "exec_state.json";
+/// Longest extra argument an action may pass.
+const MAX_ARG_LEN: usize = 256;
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// One allowlisted command. Actions only pick it by id and may append
+/// checked arguments, nothing runs through a shell.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ExecCommand {
+    /// Absolute path of the program.
+    pub program: PathBuf,
+    /// Arguments always passed first.
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Extra arguments an action may append, none by default.
+    #[serde(default)]
+    pub max_args: usize,
+    /// Exact values extra arguments must be one of. Without a list they
+    /// must be plain words that do not start with `-` or `/` and do not
+    /// hold `..`.
+    pub allowed_args: Option<Vec<String>>,
+    pub timeout_secs: Option<u64>,
+    pub cwd: Option<PathBuf>,
+    /// The whole environment of the command, `PATH` defaults to
+    /// `/usr/bin:/bin`.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+}
+
+/// Resource limits applied to every command, unix only.
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ExecLimits {
+    /// Address space, in megabytes.
+    pub memory_mb: Option<u64>,
+    /// CPU time, in seconds.
+    pub cpu_secs: Option<u64>,
+    /// Largest file the command may write, in megabytes.
+    pub file_size_mb: Option<u64>,
+}
+
+/// The `[handlers.exec]` table.
+///
+/// ```toml
+/// [handlers.exec]
+/// reply_queue = "box-1-results"
+/// allowed_senders = ["CkJ4NC4KCQfoXvyYj9Xxs4LkGDi34zNzE1e2EEeq1h9x"]
+/// limits = { memory_mb = 256, cpu_secs = 10 }
+///
+/// [handlers.exec.commands.disk]
+/// program = "/bin/df"
+/// args = ["-h"]
+/// max_args = 1
+/// allowed_args = ["/", "/var"]
+/// ```
+#[derive(Deserialize, Debug, Clone)]
+pub struct ExecSettings {
+    #[serde(default)]
+    pub commands: BTreeMap<String, ExecCommand>,
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    /// Output kept of each of stdout and stderr.
+    #[serde(default = "default_max_output_bytes")]
+    pub max_output_bytes: usize,
+    #[serde(default)]
+    pub limits: ExecLimits,
+    /// Queue the result of every run is pushed to.
+    pub reply_queue: Option<String>,
+    /// Run commands rebuilt from transaction history, such as the ones
+    /// pushed while the server was disconnected or replayed.
+    #[serde(default)]
+    pub run_backfilled: bool,
+    /// Addresses whose pushes may run commands, any allowed pusher of the
+    /// queue when missing. Pushes of an unknown sender do not run.
+    pub allowed_senders: Option<Vec<String>>,
+    /// File the last run sequence of every queue is kept in, so a restarted
+    /// server does not run a command twice. Defaults to `exec_state.json`
+    /// in the app directory.
+    pub state_file: Option<String>,
+}
+
+/// The last run from one queue address.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExecState {
+    /// Creation time of the queue, a queue closed and created again at the
+    /// same address starts over from its first push.
+    pub created_at: i64,
+    pub seq: u64,
+}
+
+/// A state file entry, keyed by queue address. Files written before
+/// addresses were kept hold a bare sequence keyed by queue name.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(untagged)]
+enum SavedState {
+    Run(ExecState),
+    Legacy(u64),
+}
+
+fn default_timeout_secs() -> u64 {
+    DEFAULT_TIMEOUT_SECS
+}
+
+fn default_max_output_bytes() -> usize {
+    DEFAULT_MAX_OUTPUT_BYTES
+}
+
+/// How one command run ended.
+#[derive(Serialize, Debug, Clone)]
+pub struct ExecResult {
+    pub queue: String,
+    pub seq: u64,
+    pub command_id: String,
+    pub exit_code: Option<i32>,
+    /// Signal that ended the command, unix only.
+    pub signal: Option<i32>,
+    pub timed_out: bool,
+    pub duration_ms: u64,
+    pub stdout: String,
+    pub stderr: String,
+    /// Output was cut at `max_output_bytes`.
+    pub truncated: bool,
+}
+
+impl ExecResult {
+    pub fn success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+}
+
+/// Runs the `Exec` actions whose command id is in the allowlist.
+pub struct ExecHandler {
+    settings: ExecSettings,
+    allowed_senders: Option<Vec<Pubkey>>,
+    state_path: PathBuf,
+    /// Last run of each queue, saved to `state_path`.
+    state: BTreeMap<String, SavedState>,
+}
+
+impl ExecHandler {
+    pub fn new(settings: ExecSettings) -> Result<Self> {
+        for (id, command) in &settings.commands {
+            if !command.program.is_absolute() {
+                return Err(DdMonitorError::Config(format!(
+                    "exec command {} program must be an absolute path : {}",
+                    id,
+                    command.program.display()
+                )));
+            }
+        }
+        let allowed_senders = settings
+            .allowed_senders
+            .as_ref()
+            .map(|senders| {
+                senders
+                    .iter()
+                    .map(|sender| {
+                        Pubkey::from_str(sender).map_err(|e| {
+                            DdMonitorError::Config(format!(
+                                "invalid exec allowed sender {} : {}",
+                                sender, e
+                            ))
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()
+            })
+            .transpose()?;
+        info!(
+            "exec commands : {:?}",
+            settings.commands.keys().collect::<Vec<_>>()
+        );
+        let state_path = PathBuf::from(
+            settings
+                .state_file
+                .clone()
+                .unwrap_or_else(|| runtime::app_path(DEFAULT_STATE_PATH)),
+        );
+        let state = load_state(&state_path)?;
+        Ok(Self {
+            settings,
+            allowed_senders,
+            state_path,
+            state,
+        })
+    }
+
+    /// The last run from the queue at `address`, kept across restarts.
+    pub fn last_run(&self, address: &Pubkey) -> Option<ExecState> {
+        match self.state.get(&address.to_string()) {
+            Some(SavedState::Run(state)) => Some(*state),
+            _ => None,
+        }
+    }
+
+    /// The last sequence run from the queue of `context`. A legacy entry of
+    /// the queue name is taken as this queue.
+    fn last_seq(&self, context: &ActionContext) -> Option<u64> {
+        let queue = &context.update.queue;
+        match self.last_run(context.address) {
+            Some(state) => (state.created_at == queue.created_at).then_some(state.seq),
+            None => match self.state.get(context.queue) {
+                Some(SavedState::Legacy(seq)) => Some(*seq),
+                _ => None,
+            },
+        }
+    }
+
+    /// Whether the sender of `context` may run commands.
+    fn sender_allowed(&self, context: &ActionContext) -> bool {
+        match (&self.allowed_senders, &context.update.sender) {
+            (None, _) => true,
+            (Some(allowed), Some(sender)) => allowed.contains(sender),
+            (Some(_), None) => false,
+        }
+    }
+
+    /// The full argument list of `command_id`, or why it may not run.
+    pub fn command_line(
+        &self,
+        command_id: &str,
+        args: &[String],
+    ) -> Result<(PathBuf, Vec<String>)> {
+        let command = self
+            .settings
+            .commands
+            .get(command_id)
+            .ok_or_else(|| DdMonitorError::NotFound(format!("exec command {}", command_id)))?;
+        if args.len() > command.max_args {
+            return Err(DdMonitorError::Config(format!(
+                "exec command {} takes at most {} arguments , got {}",
+                command_id,
+                command.max_args,
+                args.len()
+            )));
+        }
+        for arg in args {
+            let allowed = match &command.allowed_args {
+                Some(allowed) => allowed.contains(arg),
+                None => is_plain_arg(arg),
+            };
+            if !allowed {
+                return Err(DdMonitorError::Config(format!(
+                    "exec command {} rejects argument : {:?}",
+                    command_id, arg
+                )));
+            }
+        }
+        let mut line = command.args.clone();
+        line.extend_from_slice(args);
+        Ok((command.program.clone(), line))
+    }
+
+    /// Run `command_id` and wait for it, killing it at its timeout.
+    pub fn run(&self, command_id: &str, args: &[String]) -> Result<ExecResult> {
+        let (program, line) = self.command_line(command_id, args)?;
+        let command = &self.settings.commands[command_id];
+        let timeout =
+            Duration::from_secs(command.timeout_secs.unwrap_or(self.settings.timeout_secs));
+        info!("exec {} : {} {:?}", command_id, program.display(), line);
+
+        let mut process = Command::new(&program);
+        process
+            .args(&line)
+            .env_clear()
+            .env("PATH", DEFAULT_PATH)
+            .envs(&command.env)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        if let Some(cwd) = &command.cwd {
+            process.current_dir(cwd);
+        }
+        limit(&mut process, self.settings.limits);
+
+        let start = Instant::now();
+        let mut child = process.spawn()?;
+        let max_output = self.settings.max_output_bytes;
+        let stdout = capture(child.stdout.take(), max_output);
+        let stderr = capture(child.stderr.take(), max_output);
+        let (status, timed_out) = wait_timeout(&mut child, timeout)?;
+        let duration = start.elapsed();
+        let (stdout, stdout_cut) = stdout.join().unwrap_or_default();
+        let (stderr, stderr_cut) = stderr.join().unwrap_or_default();
+
+        Ok(ExecResult {
+            queue: String::new(),
+            seq: 0,
+            command_id: command_id.to_string(),
+            exit_code: status.code(),
+            signal: signal(&status),
+            timed_out,
+            duration_ms: duration.as_millis() as u64,
+            stdout: String::from_utf8_lossy(&stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&stderr).into_owned(),
+            truncated: stdout_cut || stderr_cut,
+        })
+    }
+}
+
+impl ActionHandler for ExecHandler {
+    fn actions(&self) -> Vec<&'static str> {
+        vec!["exec"]
+    }
+
+    fn handle(&mut self, action: &ActionInfo, context: &ActionContext) -> Result<ActionOutcome> {
+        let ActionInfo::Exec { command_id, args } = action else {
+            return Err(DdMonitorError::Config(format!(
+                "exec handler can not run action : {}",
+                action.action_type()
+            )));
+        };
+        let seq = context.seq();
+        if self.last_seq(context).is_some_and(|last| seq <= last) {
+            return Ok(ActionOutcome::new(format!("seq {} already ran", seq)));
+        }
+        if context.update.backfilled && !self.settings.run_backfilled {
+            warn!("skip backfilled exec {} at seq {}", command_id, seq);
+            return Ok(ActionOutcome::new("skipped backfilled command"));
+        }
+        if !self.sender_allowed(context) {
+            return Err(DdMonitorError::Config(format!(
+                "exec {} at seq {} , sender {} is not allowed",
+                command_id,
+                seq,
+                context
+                    .update
+                    .sender
+                    .map_or_else(|| "unknown".to_string(), |sender| sender.to_string())
+            )));
+        }
+        // saved before running, a crash mid run does not run it again
+        if let Some(SavedState::Legacy(_)) = self.state.get(context.queue) {
+            self.state.remove(context.queue);
+        }
+        self.state.insert(
+            context.address.to_string(),
+            SavedState::Run(ExecState {
+                created_at: context.update.queue.created_at,
+                seq,
+            }),
+        );
+        save_state(&self.state_path, &self.state)?;
+
+        let mut result = self.run(command_id, args)?;
+        result.queue = context.queue.to_string();
+        result.seq = seq;
+        let outcome = ActionOutcome::new(match (result.timed_out, result.exit_code) {
+            (true, _) => format!("{} timed out after {} ms", command_id, result.duration_ms),
+            (false, Some(code)) => format!("{} exited with {}", command_id, code),
+            (false, None) => format!("{} killed by signal {:?}", command_id, result.signal),
+        });
+        Ok(match &self.settings.reply_queue {
+            Some(queue) => {
+                let body = serde_json::to_value(&result).map_err(DdMonitorError::decode)?;
+                outcome.with_reply(queue.clone(), body)
+            }
+            None => outcome,
+        })
+    }
+}
+
+pub fn exec_factory(settings: &toml::Value) -> Result<Box<dyn ActionHandler>> {
+    let settings: ExecSettings = settings
+        .clone()
+        .try_into()
+        .map_err(|e| DdMonitorError::Config(format!("invalid handler settings exec : {}", e)))?;
+    Ok(Box::new(ExecHandler::new(settings)?))
+}
+
+/// A word without shell or option meaning, such as a name, number or
+/// relative path. Absolute paths and `..` must be listed in `allowed_args`.
+fn is_plain_arg(arg: &str) -> bool {
+    !arg.is_empty()
+        && arg.len() <= MAX_ARG_LEN
+        && !arg.starts_with('-')
+        && !arg.starts_with('/')
+        && !arg.contains("..")
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "._-:/=@,+".contains(c))
+}
+
+fn load_state(path: &Path) -> Result<BTreeMap<String, SavedState>> {
+    match fs::read_to_string(path) {
+        Ok(text) => serde_json::from_str(&text).map_err(|e| {
+            DdMonitorError::Config(format!("invalid exec state {} : {}", path.display(), e))
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Replace the state file through a temp file, a crash keeps the old one.
+fn save_state(path: &Path, state: &BTreeMap<String, SavedState>) -> Result<()> {
+    let text = serde_json::to_string_pretty(state).map_err(DdMonitorError::decode)?;
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, text)?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// Read `reader` to the end, keeping its first `limit` bytes.
+fn capture(
+    reader: Option<impl Read + Send + 'static>,
+    limit: usize,
+) -> JoinHandle<(Vec<u8>, bool)> {
+    thread::spawn(move || {
+        let (mut kept, mut truncated) = (Vec::new(), false);
+        let Some(mut reader) = reader else {
+            return (kept, truncated);
+        };
+        let mut buffer = [0u8; 4096];
+        while let Ok(read @ 1..) = reader.read(&mut buffer) {
+            let room = limit.saturating_sub(kept.len());
+            kept.extend_from_slice(&buffer[..read.min(room)]);
+            truncated |= read > room;
+        }
+        (kept, truncated)
+    })
+}
+
+fn wait_timeout(child: &mut Child, timeout: Duration) -> Result<(ExitStatus, bool)> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok((status, false));
+        }
+        if Instant::now() >= deadline {
+            kill(child);
+            return Ok((child.wait()?, true));
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Start the command in its own process group under `limits`.
+#[cfg(unix)]
+fn limit(process: &mut Command, limits: ExecLimits) {
+    use std::os::unix::process::CommandExt;
+
+    const MB: u64 = 1024 * 1024;
+    let limits = [
+        (libc::RLIMIT_AS, limits.memory_mb.map(|mb| mb * MB)),
+        (libc::RLIMIT_CPU, limits.cpu_secs),
+        (libc::RLIMIT_FSIZE, limits.file_size_mb.map(|mb| mb * MB)),
+        (libc::RLIMIT_CORE, Some(0)),
+    ];
+    // only async signal safe calls between fork and exec
+    unsafe {
+        process.pre_exec(move || {
+            if libc::setsid() < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            for (resource, value) in limits {
+                let Some(value) = value else { continue };
+                let rlimit = libc::rlimit {
+                    rlim_cur: value as libc::rlim_t,
+                    rlim_max: value as libc::rlim_t,
+                };
+                if libc::setrlimit(resource, &rlimit) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(unix))]
+fn limit(_process: &mut Command, limits: ExecLimits) {
+    if limits.memory_mb.is_some() || limits.cpu_secs.is_some() || limits.file_size_mb.is_some() {
+        warn!("exec resource limits are only applied on unix");
+    }
+}
+
+/// Kill the command and whatever it started.
+#[cfg(unix)]
+fn kill(child: &mut Child) {
+    // the command leads its own process group, see `limit`
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+    let _ = child.kill();
+}
+
+#[cfg(not(unix))]
+fn kill(child: &mut Child) {
+    let _ = child.kill();
+}
+
+#[cfg(unix)]
+fn signal(status: &ExitStatus) -> Option<i32> {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal()
+}
+
+#[cfg(not(unix))]
+fn signal(_status: &ExitStatus) -> Option<i32> {
+    None
+}
@@ -0,0 +1,323 @@
+use {
+    crate::error::{DdMonitorError, Result},
+    log::{info, warn},
+    serde::{de, Deserialize, Deserializer, Serialize, Serializer},
+    solana_client::rpc_client::RpcClient,
+    solana_program::pubkey::Pubkey,
+    solana_rpc_client_api::{
+        config::RpcSimulateTransactionConfig, response::RpcSimulateTransactionResult,
+    },
+    solana_sdk::{
+        commitment_config::CommitmentConfig, compute_budget::ComputeBudgetInstruction,
+        instruction::Instruction, signer::keypair::Keypair, transaction::Transaction,
+    },
+    std::str::FromStr,
+};
+
+/// Upper bound of compute units a transaction can request.
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+
+/// Extra room on top of the simulated compute units.
+const SIMULATION_MARGIN_PERCENT: u64 = 10;
+
+/// Percentile of recent prioritization fees used by `auto` prices.
+const PRICE_PERCENTILE: usize = 75;
+
+/// A compute budget value, either fixed or worked out before sending.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Setting<T> {
+    Fixed(T),
+    Auto,
+}
+
+/// A number or `"auto"` in the config file.
+impl<T: Serialize> Serialize for Setting<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match self {
+            Setting::Fixed(value) => value.serialize(serializer),
+            Setting::Auto => serializer.serialize_str("auto"),
+        }
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Setting<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Value<T> {
+            Fixed(T),
+            Text(String),
+        }
+        match Value::deserialize(deserializer)? {
+            Value::Fixed(value) => Ok(Setting::Fixed(value)),
+            Value::Text(text) if text == "auto" => Ok(Setting::Auto),
+            Value::Text(text) => Err(de::Error::custom(format!(
+                "expected a number or auto, got : {}",
+                text
+            ))),
+        }
+    }
+}
+
+impl<T: FromStr> FromStr for Setting<T> {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value {
+            "auto" => Ok(Setting::Auto),
+            _ => value
+                .parse()
+                .map(Setting::Fixed)
+                .map_err(|_| format!("expected a number or auto, got : {}", value)),
+        }
+    }
+}
+
+/// Compute budget added in front of every transaction the SDK sends.
+/// Nothing is added for unset values, which keeps transactions bare.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ComputeBudget {
+    pub compute_unit_limit: Option<Setting<u32>>,
+    pub compute_unit_price: Option<Setting<u64>>,
+}
+
+/// Price paid by `PRICE_PERCENTILE` of the recent slots, zero when the
+/// cluster reports no fees.
+fn pick_price(mut fees: Vec<u64>) -> u64 {
+    if fees.is_empty() {
+        return 0;
+    }
+    fees.sort_unstable();
+    fees[(fees.len() - 1) * PRICE_PERCENTILE / 100]
+}
+
+fn writable_accounts(instructions: &[Instruction]) -> Vec<Pubkey> {
+    instructions
+        .iter()
+        .flat_map(|instruction| instruction.accounts.iter())
+        .filter(|meta| meta.is_writable)
+        .map(|meta| meta.pubkey)
+        .collect()
+}
+
+/// The program each instruction of `transaction` calls, in order.
+pub fn transaction_programs(transaction: &Transaction) -> Vec<Pubkey> {
+    transaction
+        .message
+        .instructions
+        .iter()
+        .map(|instruction| *instruction.program_id(&transaction.message.account_keys))
+        .collect()
+}
+
+/// `instructions` after the maximum limit and the other `budget`
+/// instructions, unsigned, for simulation. It lays out its instructions as
+/// the transaction sent with the simulated limit, so instruction errors
+/// point at the same index in both.
+pub fn simulation_transaction(
+    payer: &Pubkey,
+    budget: &[Instruction],
+    instructions: &[Instruction],
+) -> Transaction {
+    let mut simulated = vec![ComputeBudgetInstruction::set_compute_unit_limit(
+        MAX_COMPUTE_UNIT_LIMIT,
+    )];
+    simulated.extend_from_slice(budget);
+    simulated.extend_from_slice(instructions);
+    Transaction::new_with_payer(&simulated, Some(payer))
+}
+
+fn simulation_config(commitment: CommitmentConfig) -> RpcSimulateTransactionConfig {
+    RpcSimulateTransactionConfig {
+        sig_verify: false,
+        replace_recent_blockhash: true,
+        commitment: Some(commitment),
+        ..RpcSimulateTransactionConfig::default()
+    }
+}
+
+/// The consumed compute units plus a margin. A failed simulation is read
+/// like a failed send, `programs` being those of the simulated transaction.
+pub fn simulated_limit(
+    result: RpcSimulateTransactionResult,
+    programs: &[Pubkey],
+    program_id: &Pubkey,
+) -> Result<u32> {
+    if let Some(err) = result.err {
+        warn!("simulate transaction logs : {:?}", result.logs);
+        return Err(DdMonitorError::from(err).for_program(programs, program_id));
+    }
+    let consumed = result.units_consumed.ok_or_else(|| {
+        DdMonitorError::decode("simulation did not report consumed compute units")
+    })?;
+    let limit = consumed + consumed * SIMULATION_MARGIN_PERCENT / 100;
+    Ok(limit.min(MAX_COMPUTE_UNIT_LIMIT as u64) as u32)
+}
+
+fn limit_instruction(limit: u32) -> Instruction {
+    info!("compute unit limit : {}", limit);
+    ComputeBudgetInstruction::set_compute_unit_limit(limit)
+}
+
+fn price_instruction(price: u64) -> Instruction {
+    info!("compute unit price : {}", price);
+    ComputeBudgetInstruction::set_compute_unit_price(price)
+}
+
+pub fn estimate_compute_unit_price(connection: &RpcClient, accounts: &[Pubkey]) -> Result<u64> {
+    let fees = connection.get_recent_prioritization_fees(accounts)?;
+    Ok(pick_price(
+        fees.into_iter().map(|fee| fee.prioritization_fee).collect(),
+    ))
+}
+
+/// Simulate `transaction`, built by `simulation_transaction`, and return
+/// the consumed compute units plus a margin. Custom errors of `program_id`
+/// instructions are read as `ContractError`s.
+pub fn simulate_compute_unit_limit(
+    connection: &RpcClient,
+    transaction: &Transaction,
+    program_id: &Pubkey,
+) -> Result<u32> {
+    let result = connection.simulate_transaction_with_config(
+        transaction,
+        simulation_config(connection.commitment()),
+    )?;
+    simulated_limit(result.value, &transaction_programs(transaction), program_id)
+}
+
+/// Compute budget instructions for `instructions` under `budget`, the limit
+/// first. The price is settled before an auto limit is simulated, so the
+/// simulation runs the same instructions as the transaction sent.
+pub fn budget_instructions(
+    connection: &RpcClient,
+    payer: &Pubkey,
+    instructions: &[Instruction],
+    budget: &ComputeBudget,
+    program_id: &Pubkey,
+) -> Result<Vec<Instruction>> {
+    let mut budget_instructions = vec![];
+    match budget.compute_unit_price {
+        Some(Setting::Fixed(price)) => budget_instructions.push(price_instruction(price)),
+        Some(Setting::Auto) => {
+            match estimate_compute_unit_price(connection, &writable_accounts(instructions)) {
+                Ok(price) => budget_instructions.push(price_instruction(price)),
+                Err(e) => warn!("{} , send without priority fee", e),
+            }
+        }
+        None => {}
+    }
+    match budget.compute_unit_limit {
+        Some(Setting::Fixed(limit)) => budget_instructions.insert(0, limit_instruction(limit)),
+        Some(Setting::Auto) => {
+            let simulated = simulation_transaction(payer, &budget_instructions, instructions);
+            let limit = simulate_compute_unit_limit(connection, &simulated, program_id)?;
+            budget_instructions.insert(0, limit_instruction(limit));
+        }
+        None => {}
+    }
+    Ok(budget_instructions)
+}
+
+/// Sign `instructions` with a fresh blockhash, prefixed by the instructions
+/// of `budget`. `program_id` is the program whose errors a failed limit
+/// simulation is read with.
+pub fn build_transaction(
+    connection: &RpcClient,
+    payer: &Pubkey,
+    signers: &[&Keypair],
+    instructions: &[Instruction],
+    budget: &ComputeBudget,
+    program_id: &Pubkey,
+) -> Result<Transaction> {
+    let mut all = budget_instructions(connection, payer, instructions, budget, program_id)?;
+    all.extend_from_slice(instructions);
+    let blockhash = connection.get_latest_blockhash()?;
+    Ok(Transaction::new_signed_with_payer(
+        &all,
+        Some(payer),
+        signers,
+        blockhash,
+    ))
+}
+
+/// The same helpers over the nonblocking RPC client.
+pub mod nonblocking {
+    use {super::*, solana_client::nonblocking::rpc_client::RpcClient};
+
+    pub async fn estimate_compute_unit_price(
+        connection: &RpcClient,
+        accounts: &[Pubkey],
+    ) -> Result<u64> {
+        let fees = connection.get_recent_prioritization_fees(accounts).await?;
+        Ok(pick_price(
+            fees.into_iter().map(|fee| fee.prioritization_fee).collect(),
+        ))
+    }
+
+    pub async fn simulate_compute_unit_limit(
+        connection: &RpcClient,
+        transaction: &Transaction,
+        program_id: &Pubkey,
+    ) -> Result<u32> {
+        let result = connection
+            .simulate_transaction_with_config(
+                transaction,
+                simulation_config(connection.commitment()),
+            )
+            .await?;
+        simulated_limit(result.value, &transaction_programs(transaction), program_id)
+    }
+
+    pub async fn budget_instructions(
+        connection: &RpcClient,
+        payer: &Pubkey,
+        instructions: &[Instruction],
+        budget: &ComputeBudget,
+        program_id: &Pubkey,
+    ) -> Result<Vec<Instruction>> {
+        let mut budget_instructions = vec![];
+        match budget.compute_unit_price {
+            Some(Setting::Fixed(price)) => budget_instructions.push(price_instruction(price)),
+            Some(Setting::Auto) => {
+                match estimate_compute_unit_price(connection, &writable_accounts(instructions))
+                    .await
+                {
+                    Ok(price) => budget_instructions.push(price_instruction(price)),
+                    Err(e) => warn!("{} , send without priority fee", e),
+                }
+            }
+            None => {}
+        }
+        match budget.compute_unit_limit {
+            Some(Setting::Fixed(limit)) => budget_instructions.insert(0, limit_instruction(limit)),
+            Some(Setting::Auto) => {
+                let simulated = simulation_transaction(payer, &budget_instructions, instructions);
+                let limit = simulate_compute_unit_limit(connection, &simulated, program_id).await?;
+                budget_instructions.insert(0, limit_instruction(limit));
+            }
+            None => {}
+        }
+        Ok(budget_instructions)
+    }
+
+    pub async fn build_transaction(
+        connection: &RpcClient,
+        payer: &Pubkey,
+        signers: &[&Keypair],
+        instructions: &[Instruction],
+        budget: &ComputeBudget,
+        program_id: &Pubkey,
+    ) -> Result<Transaction> {
+        let mut all =
+            budget_instructions(connection, payer, instructions, budget, program_id).await?;
+        all.extend_from_slice(instructions);
+        let blockhash = connection.get_latest_blockhash().await?;
+        Ok(Transaction::new_signed_with_payer(
+            &all,
+            Some(payer),
+            signers,
+            blockhash,
+        ))
+    }
+}
@@ -0,0 +1,247 @@
+use {
+    crate::{
+        error::{DdMonitorError, Result},
+        fees::{ComputeBudget, Setting},
+        runtime,
+        sdk::Network,
+    },
+    clap::Args,
+    contract::models,
+    log::{info, warn},
+    serde::{Deserialize, Serialize},
+    solana_client::rpc_client::RpcClient,
+    solana_program::pubkey::Pubkey,
+    solana_sdk::message::Message,
+    std::{
+        thread,
+        time::{Duration, Instant},
+    },
+};
+
+/// Lamports per signature when the cluster can not be asked.
+const DEFAULT_SIGNATURE_FEE: u64 = 5_000;
+/// Compute unit limit the runtime applies without a budget instruction.
+const DEFAULT_COMPUTE_UNIT_LIMIT: u64 = 200_000;
+
+/// How a wallet is checked and funded before the binaries start.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FundingConfig {
+    pub fee_count: u64,
+    pub airdrop_retries: usize,
+    pub airdrop_timeout_secs: u64,
+    pub max_airdrop_lamports: u64,
+    pub airdrop_custom: bool,
+}
+
+impl FundingConfig {
+    /// Whether the shortfall may be airdropped on `network`.
+    pub fn airdrop_allowed(&self, network: &Network) -> bool {
+        match network {
+            Network::Custom { .. } => self.airdrop_custom,
+            _ => network.airdrop_enable(),
+        }
+    }
+}
+
+impl Default for FundingConfig {
+    fn default() -> Self {
+        DEFAULT_FUNDING_CONFIG
+    }
+}
+
+const DEFAULT_FUNDING_CONFIG: FundingConfig = FundingConfig {
+    fee_count: 10,
+    airdrop_retries: 3,
+    airdrop_timeout_secs: 30,
+    max_airdrop_lamports: 2 * runtime::LAMPORTS_PER_SOL,
+    airdrop_custom: false,
+};
+
+/// Funding settings as flags or as the `[funding]` table of the config
+/// file, an unset value falls through to the next layer and then to the
+/// default.
+#[derive(Args, Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct FundingArgs {
+    /// Transaction fees the wallet must be able to pay on top of rent
+    /// [default: 10]
+    #[arg(long, global = true)]
+    pub fee_count: Option<u64>,
+
+    /// Airdrop requests made before giving up, on clusters that allow them
+    /// [default: 3]
+    #[arg(long, global = true)]
+    pub airdrop_retries: Option<usize>,
+
+    /// Seconds to wait for one airdrop to show up in the balance
+    /// [default: 30]
+    #[arg(long, global = true)]
+    pub airdrop_timeout_secs: Option<u64>,
+
+    /// Largest airdrop requested at once, in lamports [default: 2 SOL]
+    #[arg(long, global = true)]
+    pub max_airdrop_lamports: Option<u64>,
+
+    /// Request airdrops from a custom rpc url too, e.g. a private test
+    /// validator [default: false]
+    #[arg(long, global = true)]
+    pub airdrop_custom: Option<bool>,
+}
+
+impl FundingArgs {
+    /// The values of `self`, unset ones taken from `fallback`.
+    pub fn or(self, fallback: Self) -> Self {
+        Self {
+            fee_count: self.fee_count.or(fallback.fee_count),
+            airdrop_retries: self.airdrop_retries.or(fallback.airdrop_retries),
+            airdrop_timeout_secs: self.airdrop_timeout_secs.or(fallback.airdrop_timeout_secs),
+            max_airdrop_lamports: self.max_airdrop_lamports.or(fallback.max_airdrop_lamports),
+            airdrop_custom: self.airdrop_custom.or(fallback.airdrop_custom),
+        }
+    }
+
+    /// The config with defaults for the unset values.
+    pub fn config(self) -> FundingConfig {
+        let default = DEFAULT_FUNDING_CONFIG;
+        FundingConfig {
+            fee_count: self.fee_count.unwrap_or(default.fee_count),
+            airdrop_retries: self.airdrop_retries.unwrap_or(default.airdrop_retries),
+            airdrop_timeout_secs: self
+                .airdrop_timeout_secs
+                .unwrap_or(default.airdrop_timeout_secs),
+            max_airdrop_lamports: self
+                .max_airdrop_lamports
+                .unwrap_or(default.max_airdrop_lamports),
+            airdrop_custom: self.airdrop_custom.unwrap_or(default.airdrop_custom),
+        }
+    }
+}
+
+/// Fee of one single signature transaction, with the priority fee of a
+/// fixed compute unit price of `budget`.
+pub fn estimate_transaction_fee(
+    connection: &RpcClient,
+    payer: &Pubkey,
+    budget: &ComputeBudget,
+) -> u64 {
+    let fee_for_message = || -> Result<u64> {
+        let blockhash = connection.get_latest_blockhash()?;
+        let message = Message::new_with_blockhash(&[], Some(payer), &blockhash);
+        Ok(connection.get_fee_for_message(&message)?)
+    };
+    let base_fee = fee_for_message().unwrap_or_else(|e| {
+        warn!("get fee for message error , assume default : {}", e);
+        DEFAULT_SIGNATURE_FEE
+    });
+    let priority_fee = match budget.compute_unit_price {
+        Some(Setting::Fixed(price)) => {
+            let limit = match budget.compute_unit_limit {
+                Some(Setting::Fixed(limit)) => limit as u64,
+                _ => DEFAULT_COMPUTE_UNIT_LIMIT,
+            };
+            (price.saturating_mul(limit)).div_ceil(1_000_000)
+        }
+        _ => 0,
+    };
+    base_fee + priority_fee
+}
+
+/// Lamports the wallet needs : rent for a queue of `queue` (data size and
+/// allow count) when one is created, plus `fee_count` transaction fees.
+pub fn estimate_required_lamports(
+    connection: &RpcClient,
+    payer: &Pubkey,
+    queue: Option<(usize, u8)>,
+    config: &FundingConfig,
+    budget: &ComputeBudget,
+) -> Result<u64> {
+    let rent = match queue {
+        Some((data_size, allow_count)) => connection.get_minimum_balance_for_rent_exemption(
+            // the schema variant is the largest content type
+            models::Queue::queue_size(data_size, allow_count, models::ContentType::Schema(0)),
+        )?,
+        None => 0,
+    };
+    let fees = estimate_transaction_fee(connection, payer, budget) * config.fee_count;
+    info!("required lamports : rent {} + fees {}", rent, fees);
+    Ok(rent + fees)
+}
+
+fn wait_for_balance(
+    connection: &RpcClient,
+    pub_key: &Pubkey,
+    target: u64,
+    timeout: Duration,
+) -> Result<u64> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let balance = connection.get_balance(pub_key)?;
+        if balance >= target || Instant::now() >= deadline {
+            return Ok(balance);
+        }
+        thread::sleep(Duration::from_secs(1));
+    }
+}
+
+/// Make sure `pub_key` holds `required` lamports, airdropping the shortfall
+/// on clusters that allow it. Fails instead of waiting forever.
+pub fn ensure_funded(
+    connection: &RpcClient,
+    network: &Network,
+    pub_key: &Pubkey,
+    required: u64,
+    config: &FundingConfig,
+) -> Result<u64> {
+    let mut balance = connection.get_balance(pub_key)?;
+    info!("current balance is : {} , required : {}", balance, required);
+    if balance >= required {
+        return Ok(balance);
+    }
+    if !config.airdrop_allowed(network) {
+        return Err(DdMonitorError::InsufficientFunds {
+            address: *pub_key,
+            balance,
+            required,
+        });
+    }
+    for attempt in 1..=config.airdrop_retries {
+        let amount = (required - balance).min(config.max_airdrop_lamports);
+        info!(
+            "airdrop {} lamports , attempt {} of {}",
+            amount, attempt, config.airdrop_retries
+        );
+        if let Err(e) = connection.request_airdrop(pub_key, amount) {
+            warn!("airdrop request error : {}", DdMonitorError::from(e));
+            thread::sleep(Duration::from_secs(1));
+            continue;
+        }
+        balance = wait_for_balance(
+            connection,
+            pub_key,
+            balance + amount,
+            Duration::from_secs(config.airdrop_timeout_secs),
+        )?;
+        info!("current balance is : {}", balance);
+        if balance >= required {
+            return Ok(balance);
+        }
+    }
+    Err(DdMonitorError::InsufficientFunds {
+        address: *pub_key,
+        balance,
+        required,
+    })
+}
+
+/// Estimate what the wallet needs and fund it, see `ensure_funded`.
+pub fn fund_wallet(
+    connection: &RpcClient,
+    network: &Network,
+    pub_key: &Pubkey,
+    queue: Option<(usize, u8)>,
+    config: &FundingConfig,
+    budget: &ComputeBudget,
+) -> Result<u64> {
+    let required = estimate_required_lamports(connection, pub_key, queue, config, budget)?;
+    ensure_funded(connection, network, pub_key, required, config)
+}
@@ -1,55 +1,104 @@
 use {
-    crate::sdk,
+    crate::{
+        payload::{self, Payload},
+        sdk::QueueUpdate,
+    },
     borsh::{BorshDeserialize, BorshSerialize},
     contract::models,
     log::{error, info},
     solana_program::pubkey::Pubkey,
 };
 
-pub fn chat_main(b64data: String) {
-    let buf = sdk::base64_decode(&b64data);
-    if !buf.is_err() {
-        let data = buf.unwrap();
-        let queue = models::Queue::try_from_slice(&data);
-        if !queue.is_err() {
-            let queue = queue.unwrap();
-            let utf8_message = String::from_utf8(queue.data.clone());
-            if !utf8_message.is_err() {
-                info!("message : {}", utf8_message.unwrap());
-            }
-            ActionInfo::from(queue.data).do_action();
-        }
+pub fn chat_main(update: &QueueUpdate) {
+    handle_payload(&update.queue);
+}
+
+pub fn main(update: &QueueUpdate) {
+    info!("queue update at slot : {} ", update.slot);
+    handle_queue(&update.queue);
+}
+
+pub fn handle_queue(queue: &models::Queue) {
+    info!("queue seq : {} ", queue.seq);
+    info!("queue last change : {:?} ", queue.last_change);
+    info!("queue data : {:?} ", queue.data);
+    handle_payload(queue);
+}
+
+fn handle_payload(queue: &models::Queue) {
+    match payload::decode_queue(queue) {
+        Ok(Payload::Action(action)) => action.do_action(),
+        Ok(Payload::Text(message)) => info!("message : {}", message),
+        Ok(Payload::Json(value)) => info!("json : {}", value),
+        Ok(Payload::Raw(data)) => info!("raw data : {:?}", data),
+        Ok(Payload::Custom(id, data)) => info!("schema {} data : {:?}", id, data),
+        Err(e) => error!(
+            "decode {} payload error : {:?}",
+            payload::content_type_name(&queue.content_type),
+            e
+        ),
     }
 }
 
-pub fn main(b64data: String) {
-    let buf = sdk::base64_decode(&b64data);
-    if !buf.is_err() {
-        let data = buf.unwrap();
-        info!("data len : {} ", data.len());
-        let queue = models::Queue::try_from_slice(&data);
-        if !queue.is_err() {
-            let queue = queue.unwrap();
-            info!("queue last change : {:?} ", queue.last_change);
-            info!("queue data : {:?} ", queue.data);
-            let utf8_message = String::from_utf8(queue.data.clone());
-            if !utf8_message.is_err() {
-                info!("message : {}", utf8_message.unwrap());
-            }
-            ActionInfo::from(queue.data).do_action();
-        }
+pub fn kv_main(key: &str, entry: Option<models::KvEntry>) {
+    match entry {
+        Some(entry) => info!(
+            "key {} changed by {} : {}",
+            key,
+            entry.updated_by,
+            String::from_utf8_lossy(&entry.value)
+        ),
+        None => info!("key {} deleted", key),
     }
 }
 
+/// Responder handler for request/reply queues, runs the requested action
+/// and answers with a raw action describing what was done.
+pub fn respond_action(body: &[u8]) -> Vec<u8> {
+    let Some(action) = ActionInfo::decode(body) else {
+        return ActionInfo::Raw("invalid request".to_string()).into();
+    };
+    action.do_action();
+    ActionInfo::Raw(format!("done : {:?}", action)).into()
+}
+
 #[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
 pub enum ActionInfo {
     Raw(String),
     ActionSample(u8, u8),
     UserMessage(Pubkey, String),
     None,
+    /// Run command `command_id` of the server allowlist with extra `args`.
+    Exec {
+        command_id: String,
+        args: Vec<String>,
+    },
 }
 
 impl ActionInfo {
+    /// Names the action registry routes on, one per variant.
+    pub const ACTION_TYPES: [&'static str; 5] = ["raw", "sample", "user_message", "none", "exec"];
+
+    pub fn action_type(&self) -> &'static str {
+        match self {
+            ActionInfo::Raw(_) => "raw",
+            ActionInfo::ActionSample(_, _) => "sample",
+            ActionInfo::UserMessage(_, _) => "user_message",
+            ActionInfo::None => "none",
+            ActionInfo::Exec { .. } => "exec",
+        }
+    }
+
+    /// Decode a length prefixed action, `None` when the payload is not one.
+    pub fn decode(payload: &[u8]) -> Option<Self> {
+        if payload.len() < 4 {
+            return None;
+        }
+        let len = u32::try_from_slice(&payload[0..4]).ok()? as usize;
+        let data = payload.get(4..4 + len)?;
+        ActionInfo::try_from_slice(data).ok()
+    }
+
     pub fn do_action(&self) {
         info!("you will do action : {:?}", &self);
         match &self {
@@ -60,19 +109,22 @@ impl ActionInfo {
                 info!("this is raw action ! msg =  {} ", msg);
             }
             ActionInfo::UserMessage(user, msg) => {
-                info!("🔥 user:{}  🔥 send msg:  {}", user.to_string(), msg);
+                info!("🔥 user:{}  🔥 send msg:  {}", user, msg);
             }
             ActionInfo::None => {
                 error!("invalid action");
             }
+            ActionInfo::Exec { command_id, args } => {
+                info!("exec action ! command = {} , args = {:?}", command_id, args);
+            }
         }
     }
 }
 
-impl Into<Vec<u8>> for ActionInfo {
-    fn into(self) -> Vec<u8> {
-        info!("wrapper action : {:?}", self);
-        let mut v = self.try_to_vec().unwrap();
+impl From<ActionInfo> for Vec<u8> {
+    fn from(val: ActionInfo) -> Self {
+        info!("wrapper action : {:?}", val);
+        let mut v = val.try_to_vec().unwrap();
         let mut x = borsh::BorshSerialize::try_to_vec(&(v.len() as u32)).unwrap();
         x.append(&mut v);
         x
@@ -81,18 +133,15 @@ impl Into<Vec<u8>> for ActionInfo {
 
 impl From<Vec<u8>> for ActionInfo {
     fn from(payload: Vec<u8>) -> Self {
-        let mut buf = payload.clone();
-        let len = u32::try_from_slice(&buf[0..4]).unwrap() as usize;
-        buf.drain(0..4);
-        if buf.len() < len {
-            error!("expect buffer too long : {}", len);
-            ActionInfo::None
-        } else {
-            let data = buf[0..len].to_vec();
-            info!("unwrap data : {:?}", data);
-            let action = ActionInfo::try_from_slice(&data).unwrap();
-            info!("unwrap action : {:?}", action);
-            action
+        match ActionInfo::decode(&payload) {
+            Some(action) => {
+                info!("unwrap action : {:?}", action);
+                action
+            }
+            None => {
+                error!("invalid action payload : {:?}", payload);
+                ActionInfo::None
+            }
         }
     }
 }
@@ -0,0 +1,385 @@
+use {
+    crate::{
+        error::{DdMonitorError, Result},
+        inspect, payload,
+        sdk::{self, QueueHandler, QueueUpdate},
+    },
+    borsh::BorshDeserialize,
+    contract::{instruction::InstructionData, models},
+    log::{info, warn},
+    solana_client::rpc_client::{GetConfirmedSignaturesForAddress2Config, RpcClient},
+    solana_program::pubkey::Pubkey,
+    solana_rpc_client_api::{
+        config::RpcTransactionConfig, response::RpcConfirmedTransactionStatusWithSignature,
+    },
+    solana_sdk::{bs58, commitment_config::CommitmentConfig, signature::Signature},
+    solana_transaction_status::{
+        option_serializer::OptionSerializer, EncodedConfirmedTransactionWithStatusMeta,
+        UiInstruction, UiTransactionEncoding,
+    },
+    std::str::FromStr,
+};
+
+/// One accepted `PushMessage` found in the transaction history of a queue.
+#[derive(Debug, Clone)]
+pub struct PushRecord {
+    pub signature: Signature,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub sender: Pubkey,
+    pub data: Vec<u8>,
+}
+
+/// Walk the signatures of `queue_pub` and collect every push to it, oldest
+/// first. Failed transactions are skipped, pushes made through CPI are
+/// picked up from the inner instructions.
+pub fn fetch_push_history(
+    connection: &RpcClient,
+    program_account: &Pubkey,
+    queue_pub: &Pubkey,
+) -> Result<Vec<PushRecord>> {
+    fetch_recent_pushes(connection, program_account, queue_pub, usize::MAX, u64::MAX)
+}
+
+/// The latest `count` pushes to `queue_pub` landed at or before `max_slot`,
+/// oldest first. Signatures are paged back only until that many pushes were
+/// found, fewer are returned when the history ends first. A transaction
+/// with several pushes may add a few older ones.
+pub fn fetch_recent_pushes(
+    connection: &RpcClient,
+    program_account: &Pubkey,
+    queue_pub: &Pubkey,
+    count: usize,
+    max_slot: u64,
+) -> Result<Vec<PushRecord>> {
+    let mut records = Vec::new();
+    let mut before = None;
+    let mut transactions = 0;
+    while records.len() < count {
+        let page = connection.get_signatures_for_address_with_config(
+            queue_pub,
+            signatures_config(before, connection.commitment()),
+        )?;
+        let Some(last) = page.last() else {
+            break;
+        };
+        before = Some(Signature::from_str(&last.signature).map_err(DdMonitorError::decode)?);
+        for signature in landed_signatures(&page, max_slot)? {
+            let transaction = fetch_transaction(connection, &signature)?;
+            transactions += 1;
+            let pushes = decode_pushes(signature, &transaction, program_account, queue_pub);
+            records.extend(pushes.into_iter().rev());
+            if records.len() >= count {
+                break;
+            }
+        }
+    }
+    found_pushes(records, transactions, queue_pub)
+}
+
+fn signatures_config(
+    before: Option<Signature>,
+    commitment: CommitmentConfig,
+) -> GetConfirmedSignaturesForAddress2Config {
+    GetConfirmedSignaturesForAddress2Config {
+        before,
+        until: None,
+        limit: None,
+        commitment: Some(commitment),
+    }
+}
+
+/// Signatures of the transactions in `page` that succeeded by `max_slot`.
+fn landed_signatures(
+    page: &[RpcConfirmedTransactionStatusWithSignature],
+    max_slot: u64,
+) -> Result<Vec<Signature>> {
+    page.iter()
+        .filter(|status| status.err.is_none() && status.slot <= max_slot)
+        .map(|status| Signature::from_str(&status.signature).map_err(DdMonitorError::decode))
+        .collect()
+}
+
+/// `records` were collected newest first, hand them out oldest first.
+fn found_pushes(
+    mut records: Vec<PushRecord>,
+    transactions: usize,
+    queue_pub: &Pubkey,
+) -> Result<Vec<PushRecord>> {
+    info!(
+        "found {} pushes in {} transactions for queue : {}",
+        records.len(),
+        transactions,
+        queue_pub
+    );
+    records.reverse();
+    Ok(records)
+}
+
+fn transaction_config(commitment: CommitmentConfig) -> RpcTransactionConfig {
+    RpcTransactionConfig {
+        encoding: Some(UiTransactionEncoding::Base64),
+        commitment: Some(commitment),
+        max_supported_transaction_version: Some(0),
+    }
+}
+
+fn fetch_transaction(
+    connection: &RpcClient,
+    signature: &Signature,
+) -> Result<EncodedConfirmedTransactionWithStatusMeta> {
+    Ok(connection
+        .get_transaction_with_config(signature, transaction_config(connection.commitment()))?)
+}
+
+/// The allowed address whose push moved the queue from `prev_hash` to its
+/// current hash. The account holds the data padded, so every length down to
+/// the unpadded data is tried.
+pub fn chain_sender(prev_hash: &[u8; 32], queue: &models::Queue) -> Option<Pubkey> {
+    let min_len = payload::trim_padding(&queue.data).len();
+    queue
+        .allow
+        .iter()
+        .filter(|address| !inspect::is_free_slot(address))
+        .find(|sender| {
+            (min_len..=queue.data.len()).any(|len| {
+                models::Queue::chain_hash(prev_hash, sender, queue.seq, &queue.data[..len])
+                    == queue.hash
+            })
+        })
+        .copied()
+}
+
+/// Pushes to `queue_pub` in one transaction, in execution order.
+pub fn decode_pushes(
+    signature: Signature,
+    transaction: &EncodedConfirmedTransactionWithStatusMeta,
+    program_account: &Pubkey,
+    queue_pub: &Pubkey,
+) -> Vec<PushRecord> {
+    let Some(versioned) = transaction.transaction.transaction.decode() else {
+        warn!("can not decode transaction : {}", signature);
+        return vec![];
+    };
+    let meta = transaction.transaction.meta.as_ref();
+    if meta.map(|meta| meta.err.is_some()).unwrap_or(false) {
+        return vec![];
+    }
+
+    let mut account_keys = versioned.message.static_account_keys().to_vec();
+    if let Some(OptionSerializer::Some(loaded)) = meta.map(|meta| &meta.loaded_addresses) {
+        for address in loaded.writable.iter().chain(loaded.readonly.iter()) {
+            if let Ok(address) = Pubkey::from_str(address) {
+                account_keys.push(address);
+            }
+        }
+    }
+    let inner: Vec<_> = match meta.map(|meta| &meta.inner_instructions) {
+        Some(OptionSerializer::Some(inner)) => inner.clone(),
+        _ => vec![],
+    };
+
+    // (program index, account indexes, data) in execution order
+    let mut instructions: Vec<(u8, Vec<u8>, Vec<u8>)> = Vec::new();
+    for (index, instruction) in versioned.message.instructions().iter().enumerate() {
+        instructions.push((
+            instruction.program_id_index,
+            instruction.accounts.clone(),
+            instruction.data.clone(),
+        ));
+        for inner_instructions in inner.iter().filter(|inner| inner.index as usize == index) {
+            for inner_instruction in &inner_instructions.instructions {
+                if let UiInstruction::Compiled(compiled) = inner_instruction {
+                    if let Ok(data) = bs58::decode(&compiled.data).into_vec() {
+                        instructions.push((
+                            compiled.program_id_index,
+                            compiled.accounts.clone(),
+                            data,
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    let key = |index: &u8| account_keys.get(*index as usize).copied();
+    instructions
+        .into_iter()
+        .filter(|(program_index, _, _)| key(program_index) == Some(*program_account))
+        .filter_map(|(_, accounts, data)| {
+            let sender = key(accounts.first()?)?;
+            if key(accounts.get(1)?)? != *queue_pub {
+                return None;
+            }
+            match InstructionData::try_from_slice(&data).ok()? {
+                InstructionData::PushMessage { data, .. } => Some(PushRecord {
+                    signature,
+                    slot: transaction.slot,
+                    block_time: transaction.block_time,
+                    sender,
+                    data,
+                }),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// A point of the hash chain later pushes are rebuilt from : a sequence and
+/// the hash the queue held at it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainAnchor {
+    pub seq: u64,
+    pub hash: [u8; 32],
+}
+
+impl ChainAnchor {
+    /// A queue before its first push.
+    pub const GENESIS: Self = Self {
+        seq: 0,
+        hash: [0; 32],
+    };
+}
+
+/// The queue state after each push from `anchor` up to `current`. `records`
+/// must end with the push that produced `current`, they are numbered
+/// backwards from its sequence and pushes landed after its slot are ignored.
+/// Fails unless the history reaches back to the anchor and the rebuilt hash
+/// chain ends in the hash `current` holds.
+pub fn queue_states(
+    records: &[PushRecord],
+    current: &QueueUpdate,
+    anchor: ChainAnchor,
+) -> Result<Vec<QueueUpdate>> {
+    let queue = &current.queue;
+    let records: Vec<&PushRecord> = records
+        .iter()
+        .filter(|record| record.slot <= current.slot)
+        .collect();
+    let count = queue.seq.checked_sub(anchor.seq).ok_or_else(|| {
+        DdMonitorError::Decode(format!(
+            "anchor seq {} is ahead of queue seq {}",
+            anchor.seq, queue.seq
+        ))
+    })? as usize;
+    if records.len() < count {
+        return Err(DdMonitorError::NotFound(format!(
+            "pushes {} to {} in the queue history , it holds {}",
+            anchor.seq + 1,
+            queue.seq,
+            records.len()
+        )));
+    }
+
+    let mut hash = anchor.hash;
+    let updates = records[records.len() - count..]
+        .iter()
+        .zip(anchor.seq + 1..)
+        .map(|(record, seq)| {
+            hash = models::Queue::chain_hash(&hash, &record.sender, seq, &record.data);
+            let mut data = record.data.clone();
+            data.resize(queue.need_data_size.max(data.len()), 0);
+            QueueUpdate {
+                slot: record.slot,
+                queue: models::Queue {
+                    data,
+                    seq,
+                    hash,
+                    last_change: record.block_time.unwrap_or(queue.last_change),
+                    ..queue.clone()
+                },
+                backfilled: true,
+                sender: Some(record.sender),
+            }
+        })
+        .collect();
+    if hash != queue.hash {
+        return Err(DdMonitorError::Decode(format!(
+            "rebuilt hash {} does not match queue hash {}",
+            bs58::encode(hash).into_string(),
+            bs58::encode(queue.hash).into_string()
+        )));
+    }
+    Ok(updates)
+}
+
+/// Feed every push after `from_seq` through `handler`, oldest first, as the
+/// live watcher would have delivered it. The history is verified from the
+/// first push on, nothing is replayed when it is incomplete. Returns how
+/// many were replayed.
+pub fn replay(
+    connection: &RpcClient,
+    program_account: &Pubkey,
+    queue_pub: &Pubkey,
+    from_seq: u64,
+    mut handler: impl QueueHandler,
+) -> Result<usize> {
+    let current = sdk::fetch_queue_update(connection, queue_pub)?;
+    let records = fetch_recent_pushes(
+        connection,
+        program_account,
+        queue_pub,
+        current.seq() as usize,
+        current.slot,
+    )?;
+    let updates: Vec<QueueUpdate> = queue_states(&records, &current, ChainAnchor::GENESIS)?
+        .into_iter()
+        .filter(|update| update.seq() > from_seq)
+        .collect();
+    for update in &updates {
+        handler.handle(update);
+    }
+    Ok(updates.len())
+}
+
+/// Rebuild the hash chain from the first push and check it ends in the hash
+/// and sequence the queue holds on chain. Any dropped, extra or reordered
+/// push breaks the match.
+pub fn verify_chain(records: &[PushRecord], current: &QueueUpdate) -> Result<[u8; 32]> {
+    queue_states(records, current, ChainAnchor::GENESIS)?;
+    Ok(current.queue.hash)
+}
+
+/// The same history lookups over the nonblocking RPC client.
+pub mod nonblocking {
+    use {super::*, solana_client::nonblocking::rpc_client::RpcClient};
+
+    pub async fn fetch_recent_pushes(
+        connection: &RpcClient,
+        program_account: &Pubkey,
+        queue_pub: &Pubkey,
+        count: usize,
+        max_slot: u64,
+    ) -> Result<Vec<PushRecord>> {
+        let mut records = Vec::new();
+        let mut before = None;
+        let mut transactions = 0;
+        while records.len() < count {
+            let page = connection
+                .get_signatures_for_address_with_config(
+                    queue_pub,
+                    signatures_config(before, connection.commitment()),
+                )
+                .await?;
+            let Some(last) = page.last() else {
+                break;
+            };
+            before = Some(Signature::from_str(&last.signature).map_err(DdMonitorError::decode)?);
+            for signature in landed_signatures(&page, max_slot)? {
+                let transaction = connection
+                    .get_transaction_with_config(
+                        &signature,
+                        transaction_config(connection.commitment()),
+                    )
+                    .await?;
+                transactions += 1;
+                let pushes = decode_pushes(signature, &transaction, program_account, queue_pub);
+                records.extend(pushes.into_iter().rev());
+                if records.len() >= count {
+                    break;
+                }
+            }
+        }
+        found_pushes(records, transactions, queue_pub)
+    }
+}
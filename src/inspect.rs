@@ -0,0 +1,194 @@
+use {
+    crate::{
+        error::{DdMonitorError, Result},
+        handlers::ActionInfo,
+        metadata, payload, sdk,
+    },
+    chrono::{DateTime, Utc},
+    contract::models::{self, ContentType},
+    serde::Serialize,
+    solana_client::rpc_client::RpcClient,
+    solana_program::{pubkey::Pubkey, system_program},
+    solana_sdk::bs58,
+    std::fmt::Write,
+};
+
+/// One slot of the allow list, unused slots hold the default or system key.
+#[derive(Serialize, Debug, Clone)]
+pub struct AllowSlot {
+    pub index: usize,
+    pub address: String,
+    pub free: bool,
+}
+
+/// The queue data rendered every way it can be read.
+#[derive(Serialize, Debug, Clone)]
+pub struct PayloadView {
+    /// The rendering picked for the queue content type.
+    pub rendered: String,
+    pub hex: String,
+    /// The data without padding, when it is UTF-8.
+    pub text: Option<String>,
+    pub action: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct MetadataView {
+    pub display_name: String,
+    pub description: String,
+    pub tags: Vec<String>,
+    pub uri: String,
+    pub updated_at: String,
+}
+
+/// Everything the queue account holds, decoded for people and scripts.
+#[derive(Serialize, Debug, Clone)]
+pub struct QueueView {
+    pub name: String,
+    pub address: String,
+    pub creator: String,
+    pub content_type: String,
+    pub seq: u64,
+    pub data_size: usize,
+    pub allow: Vec<AllowSlot>,
+    pub free_slots: usize,
+    pub hook: Option<String>,
+    pub hash: String,
+    pub created_at: String,
+    pub last_change: String,
+    pub account_size: usize,
+    pub lamports: u64,
+    pub rent_exempt_minimum: u64,
+    pub payload: PayloadView,
+    pub metadata: Option<MetadataView>,
+}
+
+/// How `PayloadView::rendered` is produced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Render {
+    /// Action for action queues, text for text and json, hex otherwise.
+    #[default]
+    Auto,
+    Text,
+    Hex,
+    Action,
+}
+
+impl std::str::FromStr for Render {
+    type Err = DdMonitorError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "auto" => Ok(Self::Auto),
+            "text" => Ok(Self::Text),
+            "hex" => Ok(Self::Hex),
+            "action" => Ok(Self::Action),
+            _ => Err(DdMonitorError::Config(format!(
+                "unknown payload rendering : {} , expected auto, text, hex or action",
+                value
+            ))),
+        }
+    }
+}
+
+pub fn is_free_slot(address: &Pubkey) -> bool {
+    *address == Pubkey::default() || *address == system_program::ID
+}
+
+/// Lowercase hex of `data`.
+pub fn to_hex(data: &[u8]) -> String {
+    data.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{:02x}", byte);
+        hex
+    })
+}
+
+fn format_time(timestamp: i64) -> String {
+    DateTime::<Utc>::from_timestamp(timestamp, 0)
+        .map(|time| time.to_rfc3339())
+        .unwrap_or_else(|| timestamp.to_string())
+}
+
+pub fn render_payload(content_type: &ContentType, data: &[u8], render: Render) -> PayloadView {
+    let hex = to_hex(data);
+    let text = String::from_utf8(payload::trim_padding(data).to_vec()).ok();
+    let action = ActionInfo::decode(data).map(|action| format!("{:?}", action));
+    let render = match (render, content_type) {
+        (Render::Auto, ContentType::Action) => Render::Action,
+        (Render::Auto, ContentType::Text | ContentType::Json) => Render::Text,
+        (Render::Auto, _) => Render::Hex,
+        (render, _) => render,
+    };
+    let rendered = match render {
+        Render::Text => text.clone(),
+        Render::Action => action.clone(),
+        _ => None,
+    }
+    .unwrap_or_else(|| hex.clone());
+    PayloadView {
+        rendered,
+        hex,
+        text,
+        action,
+    }
+}
+
+/// Fetch queue `name` and decode it, with its rent balance and metadata.
+pub fn inspect_queue(
+    connection: &RpcClient,
+    program_account: &Pubkey,
+    name: &str,
+    render: Render,
+) -> Result<QueueView> {
+    let address = sdk::pda_queue_account(program_account, name);
+    let account = connection
+        .get_account_with_commitment(&address, connection.commitment())?
+        .value
+        .ok_or_else(|| DdMonitorError::NotFound(format!("queue {} ({})", name, address)))?;
+    if account.owner != *program_account {
+        return Err(DdMonitorError::Decode(format!(
+            "{} is owned by {} , not the program",
+            address, account.owner
+        )));
+    }
+    let queue = models::Queue::unpack(&account.data).map_err(DdMonitorError::decode)?;
+    let rent_exempt_minimum =
+        connection.get_minimum_balance_for_rent_exemption(account.data.len())?;
+    let metadata = metadata::fetch_metadata(connection, program_account, &address)?;
+
+    let allow: Vec<AllowSlot> = queue
+        .allow
+        .iter()
+        .enumerate()
+        .map(|(index, address)| AllowSlot {
+            index,
+            address: address.to_string(),
+            free: is_free_slot(address),
+        })
+        .collect();
+    Ok(QueueView {
+        name: name.to_string(),
+        address: address.to_string(),
+        creator: queue.creator.to_string(),
+        content_type: payload::content_type_name(&queue.content_type),
+        seq: queue.seq,
+        data_size: queue.need_data_size,
+        free_slots: allow.iter().filter(|slot| slot.free).count(),
+        allow,
+        hook: queue.has_hook().then(|| queue.hook.to_string()),
+        hash: bs58::encode(queue.hash).into_string(),
+        created_at: format_time(queue.created_at),
+        last_change: format_time(queue.last_change),
+        account_size: account.data.len(),
+        lamports: account.lamports,
+        rent_exempt_minimum,
+        payload: render_payload(&queue.content_type, &queue.data, render),
+        metadata: metadata.map(|meta| MetadataView {
+            display_name: meta.display_name,
+            description: meta.description,
+            tags: meta.tags,
+            uri: meta.uri,
+            updated_at: format_time(meta.updated_at),
+        }),
+    })
+}
@@ -0,0 +1,194 @@
+use {
+    crate::{
+        error::{DdMonitorError, Result},
+        runtime, sdk,
+    },
+    log::info,
+    ring::{
+        aead::{self, Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM},
+        pbkdf2,
+        rand::{SecureRandom, SystemRandom},
+    },
+    serde::{Deserialize, Serialize},
+    solana_sdk::signer::{keypair::Keypair, Signer},
+    std::{
+        env, fs,
+        io::{self, IsTerminal},
+        num::NonZeroU32,
+        path::Path,
+    },
+};
+
+/// Password used to unlock or create a keystore without a prompt.
+pub const PASSWORD_ENV: &str = "DDMONITOR_KEYSTORE_PASSWORD";
+/// File holding the keystore password, for daemons and secret mounts.
+pub const PASSWORD_FILE_ENV: &str = "DDMONITOR_KEYSTORE_PASSWORD_FILE";
+
+const VERSION: u8 = 1;
+const KDF: &str = "pbkdf2-hmac-sha256";
+const CIPHER: &str = "aes-256-gcm";
+const ITERATIONS: u32 = 600_000;
+const SALT_LEN: usize = 16;
+
+/// A keypair encrypted with a key derived from a password.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Keystore {
+    pub version: u8,
+    pub pubkey: String,
+    pub kdf: String,
+    pub iterations: u32,
+    pub salt: String,
+    pub cipher: String,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+fn derive_key(password: &str, salt: &[u8], iterations: u32) -> Result<LessSafeKey> {
+    let iterations = NonZeroU32::new(iterations)
+        .ok_or_else(|| DdMonitorError::config("keystore iterations must not be zero"))?;
+    let mut key = [0; 32];
+    pbkdf2::derive(
+        pbkdf2::PBKDF2_HMAC_SHA256,
+        iterations,
+        salt,
+        password.as_bytes(),
+        &mut key,
+    );
+    let key = UnboundKey::new(&AES_256_GCM, &key)
+        .map_err(|_| DdMonitorError::config("can not build keystore key"))?;
+    Ok(LessSafeKey::new(key))
+}
+
+fn random_bytes<const N: usize>() -> Result<[u8; N]> {
+    let mut bytes = [0; N];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| DdMonitorError::config("system random is unavailable"))?;
+    Ok(bytes)
+}
+
+impl Keystore {
+    pub fn encrypt(keypair: &Keypair, password: &str) -> Result<Self> {
+        let salt: [u8; SALT_LEN] = random_bytes()?;
+        let nonce: [u8; aead::NONCE_LEN] = random_bytes()?;
+        let pubkey = keypair.pubkey().to_string();
+        let mut data = keypair.to_bytes().to_vec();
+        derive_key(password, &salt, ITERATIONS)?
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(pubkey.as_bytes()),
+                &mut data,
+            )
+            .map_err(|_| DdMonitorError::config("keystore encryption failed"))?;
+        Ok(Self {
+            version: VERSION,
+            pubkey,
+            kdf: KDF.to_string(),
+            iterations: ITERATIONS,
+            salt: sdk::base64_encode(&salt),
+            cipher: CIPHER.to_string(),
+            nonce: sdk::base64_encode(&nonce),
+            ciphertext: sdk::base64_encode(&data),
+        })
+    }
+
+    pub fn decrypt(&self, password: &str) -> Result<Keypair> {
+        if self.version != VERSION || self.kdf != KDF || self.cipher != CIPHER {
+            return Err(DdMonitorError::Config(format!(
+                "unsupported keystore : version {} , {} , {}",
+                self.version, self.kdf, self.cipher
+            )));
+        }
+        let salt = sdk::base64_decode(&self.salt)?;
+        let nonce: [u8; aead::NONCE_LEN] = sdk::base64_decode(&self.nonce)?
+            .try_into()
+            .map_err(|_| DdMonitorError::config("invalid keystore nonce"))?;
+        let mut data = sdk::base64_decode(&self.ciphertext)?;
+        let secret = derive_key(password, &salt, self.iterations)?
+            .open_in_place(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(self.pubkey.as_bytes()),
+                &mut data,
+            )
+            .map_err(|_| DdMonitorError::config("wrong keystore password"))?;
+        let keypair = Keypair::from_bytes(secret).map_err(DdMonitorError::config)?;
+        data.fill(0);
+        if keypair.pubkey().to_string() != self.pubkey {
+            return Err(DdMonitorError::config("keystore pubkey does not match"));
+        }
+        Ok(keypair)
+    }
+
+    /// Whether `text` looks like a keystore rather than a plaintext keypair.
+    pub fn is_keystore(text: &str) -> bool {
+        serde_json::from_str::<Self>(text).is_ok()
+    }
+
+    pub fn read(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)?;
+        serde_json::from_str(&text).map_err(|e| {
+            DdMonitorError::Config(format!("invalid keystore {} : {}", path.display(), e))
+        })
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let text = serde_json::to_string_pretty(self).map_err(DdMonitorError::config)?;
+        runtime::write_private(path, text.as_bytes())
+    }
+}
+
+/// The keystore password from `DDMONITOR_KEYSTORE_PASSWORD`, the file named
+/// by `DDMONITOR_KEYSTORE_PASSWORD_FILE`, or a prompt when stdin is a
+/// terminal. Daemons must use one of the variables.
+pub fn password(confirm: bool) -> Result<String> {
+    let password = if let Ok(password) = env::var(PASSWORD_ENV) {
+        password
+    } else if let Ok(path) = env::var(PASSWORD_FILE_ENV) {
+        let password = fs::read_to_string(&path)?;
+        password.trim_end_matches(['\r', '\n']).to_string()
+    } else if io::stdin().is_terminal() {
+        let password = rpassword::prompt_password("keystore password: ")?;
+        if confirm && rpassword::prompt_password("confirm password: ")? != password {
+            return Err(DdMonitorError::config("passwords do not match"));
+        }
+        password
+    } else {
+        return Err(DdMonitorError::Config(format!(
+            "keystore is locked , set {} or {}",
+            PASSWORD_ENV, PASSWORD_FILE_ENV
+        )));
+    };
+    if password.is_empty() {
+        return Err(DdMonitorError::config(
+            "keystore password must not be empty",
+        ));
+    }
+    Ok(password)
+}
+
+/// Encrypt `keypair` into a new keystore at `path`.
+pub fn create(keypair: &Keypair, path: &Path) -> Result<()> {
+    Keystore::encrypt(keypair, &password(true)?)?.write(path)?;
+    info!(
+        "keystore for {} written to : {}",
+        keypair.pubkey(),
+        path.display()
+    );
+    Ok(())
+}
+
+pub fn unlock(path: &Path) -> Result<Keypair> {
+    let keypair = Keystore::read(path)?.decrypt(&password(false)?)?;
+    info!("keystore unlocked : {}", path.display());
+    Ok(keypair)
+}
+
+/// Replace the plaintext keypair at `path` with a keystore of the same key.
+pub fn migrate(keypair: &Keypair, path: &Path) -> Result<()> {
+    create(keypair, path)?;
+    info!(
+        "plaintext keypair migrated to keystore : {}",
+        path.display()
+    );
+    Ok(())
+}
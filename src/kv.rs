@@ -0,0 +1,247 @@
+use {
+    crate::{
+        error::{DdMonitorError, Result},
+        sdk,
+        sdk::Network,
+        sender::{SendConfig, SendResult},
+    },
+    borsh::BorshDeserialize,
+    contract::{instruction, models},
+    log::{error, info},
+    solana_account_decoder::{UiAccount, UiAccountData, UiAccountEncoding},
+    solana_client::{pubsub_client::PubsubClient, rpc_client::RpcClient},
+    solana_program::pubkey::Pubkey,
+    solana_rpc_client_api::{
+        config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+        filter::{Memcmp, RpcFilterType},
+        response::Response as RpcResponse,
+    },
+    solana_sdk::{commitment_config::CommitmentConfig, signer::keypair::Keypair, signer::Signer},
+};
+
+pub fn pda_kv_account(program_account: &Pubkey, queue_pub: &Pubkey, key: &str) -> Pubkey {
+    let (pda, _nonce) = models::KvEntry::find_address(queue_pub, key, program_account);
+    pda
+}
+
+/// Fails unless `key` can be stored, the program rejects it otherwise.
+pub fn check_key(key: &str) -> Result<()> {
+    if !models::KvEntry::is_valid_key(key) {
+        return Err(DdMonitorError::config(format!(
+            "key must be 1 to {} bytes long",
+            models::KvEntry::MAX_KEY_LEN
+        )));
+    }
+    Ok(())
+}
+
+/// Decode an entry account, `None` for closed accounts or data that is not
+/// an entry for `key`.
+pub fn decode_entry(data: &[u8], key: Option<&str>) -> Option<models::KvEntry> {
+    let entry = models::KvEntry::try_from_slice(data).ok()?;
+    let expected = models::KvEntry::key_hash(key.unwrap_or(&entry.key));
+    if entry.key_hash != expected {
+        return None;
+    }
+    Some(entry)
+}
+
+pub fn get(
+    connection: &RpcClient,
+    program_account: &Pubkey,
+    queue_pub: &Pubkey,
+    key: &str,
+) -> Result<Option<models::KvEntry>> {
+    let entry_pub = pda_kv_account(program_account, queue_pub, key);
+    let account = connection
+        .get_account_with_commitment(&entry_pub, connection.commitment())?
+        .value;
+    Ok(account.and_then(|account| decode_entry(&account.data, Some(key))))
+}
+
+pub fn put(
+    connection: &RpcClient,
+    payer: &Keypair,
+    program_account: &Pubkey,
+    name: &str,
+    key: &str,
+    value: Vec<u8>,
+    config: &SendConfig,
+) -> Result<SendResult> {
+    check_key(key)?;
+    let queue_pub = sdk::pda_queue_account(program_account, name);
+    info!(
+        "you will put {} bytes to key {} of : {}",
+        value.len(),
+        key,
+        name
+    );
+    sdk::send_instruction(
+        connection,
+        &payer.pubkey(),
+        &vec![payer],
+        instruction::kv_put(
+            program_account,
+            &payer.pubkey(),
+            &queue_pub,
+            name,
+            key,
+            value,
+        ),
+        config,
+    )
+}
+
+pub fn delete(
+    connection: &RpcClient,
+    payer: &Keypair,
+    program_account: &Pubkey,
+    name: &str,
+    key: &str,
+    config: &SendConfig,
+) -> Result<SendResult> {
+    check_key(key)?;
+    let queue_pub = sdk::pda_queue_account(program_account, name);
+    info!("you will delete key {} of : {}", key, name);
+    sdk::send_instruction(
+        connection,
+        &payer.pubkey(),
+        &vec![payer],
+        instruction::kv_delete(program_account, &payer.pubkey(), &queue_pub, name, key),
+        config,
+    )
+}
+
+/// All entries stored under a queue, sorted by key.
+pub fn scan(
+    connection: &RpcClient,
+    program_account: &Pubkey,
+    queue_pub: &Pubkey,
+) -> Result<Vec<models::KvEntry>> {
+    let accounts = connection.get_program_accounts_with_config(
+        program_account,
+        RpcProgramAccountsConfig {
+            filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                0,
+                queue_pub.as_ref(),
+            ))]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                data_slice: None,
+                commitment: Some(connection.commitment()),
+                min_context_slot: None,
+            },
+            with_context: None,
+        },
+    )?;
+    let mut entries: Vec<models::KvEntry> = accounts
+        .into_iter()
+        .filter_map(|(_pubkey, account)| decode_entry(&account.data, None))
+        .collect();
+    entries.sort_by(|a, b| a.key.cmp(&b.key));
+    Ok(entries)
+}
+
+/// Receives the entry of a watched key, `None` once it is deleted. Closures
+/// taking the key and the entry are handlers.
+pub trait KeyHandler {
+    fn handle(&mut self, key: &str, entry: Option<models::KvEntry>);
+
+    /// Whether the watch goes on, a closed handler ends it.
+    fn is_open(&self) -> bool {
+        true
+    }
+}
+
+impl<F: FnMut(&str, Option<models::KvEntry>)> KeyHandler for F {
+    fn handle(&mut self, key: &str, entry: Option<models::KvEntry>) {
+        self(key, entry)
+    }
+}
+
+/// Watch one key, `handler` gets the entry each time it changes. A dropped
+/// subscription is reopened with backoff and the entry read again, so a
+/// change made meanwhile is still delivered. Returns once the handler is
+/// closed.
+pub fn watch_key(
+    network: &Network,
+    program_account: &Pubkey,
+    queue_pub: &Pubkey,
+    key: &str,
+    mut handler: impl KeyHandler,
+) -> Result<()> {
+    check_key(key)?;
+    let connection = sdk::get_rpc_client(network);
+    let entry_pub = pda_kv_account(program_account, queue_pub, key);
+    // the account data last handed over, empty once deleted
+    let mut last = None;
+    sdk::keep_subscribed(
+        &entry_pub,
+        &mut handler,
+        |handler| handler.is_open(),
+        |handler| watch_entry(&connection, network, &entry_pub, key, &mut last, handler),
+    );
+    Ok(())
+}
+
+/// One subscription, returns once the socket is gone or the handler closed.
+fn watch_entry(
+    connection: &RpcClient,
+    network: &Network,
+    entry_pub: &Pubkey,
+    key: &str,
+    last: &mut Option<Vec<u8>>,
+    handler: &mut impl KeyHandler,
+) -> Result<()> {
+    let (mut _entry_subscription_client, entry_receiver) = PubsubClient::account_subscribe(
+        &network.get_ws_url(),
+        entry_pub,
+        Some(sdk::account_config()),
+    )?;
+
+    // subscribed first, so nothing changed after this read is missed
+    let current = connection
+        .get_account_with_commitment(entry_pub, CommitmentConfig::confirmed())?
+        .value
+        .map(|account| account.data)
+        .unwrap_or_default();
+    if last.is_some() {
+        hand_over(key, last, current, handler);
+    } else {
+        *last = Some(current);
+    }
+
+    info!("begin watch key {} with : {}", key, entry_pub);
+    while handler.is_open() {
+        match entry_receiver.recv_timeout(sdk::OPEN_CHECK_INTERVAL) {
+            Ok(response) => match decode_notification(response) {
+                Ok(data) => hand_over(key, last, data, handler),
+                Err(e) => error!("decode entry error : {:?}", e),
+            },
+            Err(e) if e.is_timeout() => {}
+            Err(e) => {
+                error!("entry subscription error: {:?}", e);
+                return Ok(());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Hand the entry in `data` over unless it is the one handed over last.
+fn hand_over(key: &str, last: &mut Option<Vec<u8>>, data: Vec<u8>, handler: &mut impl KeyHandler) {
+    if last.as_ref() == Some(&data) {
+        return;
+    }
+    handler.handle(key, decode_entry(&data, Some(key)));
+    *last = Some(data);
+}
+
+fn decode_notification(response: RpcResponse<UiAccount>) -> Result<Vec<u8>> {
+    let UiAccountData::Binary(b64_str, _encoding) = response.value.data else {
+        return Err(DdMonitorError::decode(
+            "entry account is not base64 encoded",
+        ));
+    };
+    sdk::base64_decode(&b64_str)
+}
@@ -1,4 +1,23 @@
+pub mod actions;
+pub mod client;
+pub mod cluster;
+pub mod config;
+pub mod consumer;
 pub mod r#custom_macro;
+pub mod error;
+pub mod exec;
+pub mod fees;
+pub mod funding;
 pub mod handlers;
+pub mod history;
+pub mod inspect;
+pub mod keystore;
+pub mod kv;
+pub mod metadata;
+pub mod payload;
+pub mod reply;
 pub mod runtime;
 pub mod sdk;
+pub mod sender;
+pub mod wallet;
+pub mod webhook;
@@ -0,0 +1,71 @@
+use {
+    crate::{
+        error::{DdMonitorError, Result},
+        sdk,
+        sender::{SendConfig, SendResult},
+    },
+    borsh::BorshDeserialize,
+    contract::{instruction, models},
+    log::info,
+    solana_client::rpc_client::RpcClient,
+    solana_program::pubkey::Pubkey,
+    solana_sdk::{signer::keypair::Keypair, signer::Signer},
+};
+
+pub fn pda_metadata_account(program_account: &Pubkey, queue_pub: &Pubkey) -> Pubkey {
+    let (pda, _nonce) = models::Metadata::find_address(queue_pub, program_account);
+    pda
+}
+
+/// Metadata of a queue, `None` when the creator never set any.
+pub fn fetch_metadata(
+    connection: &RpcClient,
+    program_account: &Pubkey,
+    queue_pub: &Pubkey,
+) -> Result<Option<models::Metadata>> {
+    let metadata_pub = pda_metadata_account(program_account, queue_pub);
+    let account = connection
+        .get_account_with_commitment(&metadata_pub, connection.commitment())?
+        .value;
+    match account {
+        Some(account) if !account.data.is_empty() => {
+            models::Metadata::try_from_slice(&account.data)
+                .map(Some)
+                .map_err(DdMonitorError::decode)
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Replace the metadata of queue `name`, only its creator may do so.
+pub fn set(
+    connection: &RpcClient,
+    payer: &Keypair,
+    program_account: &Pubkey,
+    name: &str,
+    metadata: &models::Metadata,
+    config: &SendConfig,
+) -> Result<SendResult> {
+    if !metadata.is_valid() {
+        return Err(DdMonitorError::config("metadata field is too long"));
+    }
+    let queue_pub = sdk::pda_queue_account(program_account, name);
+    info!("you will set metadata of : {}", name);
+    sdk::send_instruction(
+        connection,
+        &payer.pubkey(),
+        &vec![payer],
+        instruction::set_metadata(program_account, &payer.pubkey(), &queue_pub, name, metadata),
+        config,
+    )
+}
+
+/// Split a comma separated tag list, dropping empty entries.
+pub fn parse_tags(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect()
+}
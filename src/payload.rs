@@ -0,0 +1,100 @@
+use {
+    crate::{
+        error::{DdMonitorError, Result},
+        handlers::ActionInfo,
+        sdk,
+    },
+    contract::models::{ContentType, Queue},
+};
+
+/// Queue data decoded according to the queue's declared content type.
+#[derive(Debug, Clone)]
+pub enum Payload {
+    Raw(Vec<u8>),
+    Text(String),
+    Action(ActionInfo),
+    Json(serde_json::Value),
+    Custom(u32, Vec<u8>),
+}
+
+impl Payload {
+    /// JSON form for scripts, bytes are base64 encoded.
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            Payload::Raw(data) => serde_json::json!({ "raw": sdk::base64_encode(data) }),
+            Payload::Text(text) => serde_json::json!({ "text": text }),
+            Payload::Action(action) => serde_json::json!({ "action": format!("{:?}", action) }),
+            Payload::Json(value) => serde_json::json!({ "json": value }),
+            Payload::Custom(id, data) => serde_json::json!({
+                "schema": id,
+                "data": sdk::base64_encode(data),
+            }),
+        }
+    }
+}
+
+/// Parse `raw`, `text`, `action`, `json` or `schema:<id>`.
+pub fn parse_content_type(value: &str) -> Result<ContentType> {
+    match value {
+        "raw" => Ok(ContentType::Raw),
+        "text" => Ok(ContentType::Text),
+        "action" => Ok(ContentType::Action),
+        "json" => Ok(ContentType::Json),
+        _ => match value.strip_prefix("schema:").map(str::parse::<u32>) {
+            Some(Ok(id)) => Ok(ContentType::Schema(id)),
+            _ => Err(DdMonitorError::Config(format!(
+                "unknown content type : {}",
+                value
+            ))),
+        },
+    }
+}
+
+pub fn content_type_name(content_type: &ContentType) -> String {
+    match content_type {
+        ContentType::Raw => "raw".to_string(),
+        ContentType::Text => "text".to_string(),
+        ContentType::Action => "action".to_string(),
+        ContentType::Json => "json".to_string(),
+        ContentType::Schema(id) => format!("schema:{}", id),
+    }
+}
+
+/// The queue pads its data with zeros up to the declared size.
+pub fn trim_padding(data: &[u8]) -> &[u8] {
+    let end = data.iter().rposition(|b| *b != 0).map_or(0, |i| i + 1);
+    &data[..end]
+}
+
+pub fn decode(content_type: &ContentType, data: &[u8]) -> Result<Payload> {
+    match content_type {
+        ContentType::Raw => Ok(Payload::Raw(data.to_vec())),
+        ContentType::Text => String::from_utf8(trim_padding(data).to_vec())
+            .map(Payload::Text)
+            .map_err(DdMonitorError::decode),
+        ContentType::Action => ActionInfo::decode(data)
+            .map(Payload::Action)
+            .ok_or_else(|| DdMonitorError::decode("invalid action payload")),
+        ContentType::Json => serde_json::from_slice(trim_padding(data))
+            .map(Payload::Json)
+            .map_err(DdMonitorError::decode),
+        ContentType::Schema(id) => Ok(Payload::Custom(*id, data.to_vec())),
+    }
+}
+
+pub fn decode_queue(queue: &Queue) -> Result<Payload> {
+    decode(&queue.content_type, &queue.data)
+}
+
+/// Encode a line typed by a user for a queue of `content_type`.
+pub fn encode_text(content_type: &ContentType, text: &str) -> Result<Vec<u8>> {
+    match content_type {
+        ContentType::Action => Ok(ActionInfo::Raw(text.to_string()).into()),
+        ContentType::Json => {
+            let value: serde_json::Value =
+                serde_json::from_str(text).map_err(DdMonitorError::config)?;
+            Ok(value.to_string().into_bytes())
+        }
+        _ => Ok(text.as_bytes().to_vec()),
+    }
+}
@@ -0,0 +1,236 @@
+use {
+    crate::{
+        error::{DdMonitorError, Result},
+        history, runtime,
+        sdk::{self, Network, QueueHandler, QueueUpdate},
+        sender::SendConfig,
+    },
+    borsh::{BorshDeserialize, BorshSerialize},
+    log::{error, info, warn},
+    solana_client::rpc_client::RpcClient,
+    solana_program::pubkey::Pubkey,
+    solana_sdk::{signer::keypair::Keypair, signer::Signer},
+    std::time::{Duration, Instant},
+};
+
+/// Request/reply framing carried in the queue data. A request names the
+/// queue the answer must be pushed to, the response echoes its correlation id.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub enum Envelope {
+    Request {
+        correlation_id: u64,
+        reply_to: String,
+        body: Vec<u8>,
+    },
+    Response {
+        correlation_id: u64,
+        body: Vec<u8>,
+    },
+}
+
+impl Envelope {
+    pub fn correlation_id(&self) -> u64 {
+        match self {
+            Envelope::Request { correlation_id, .. } => *correlation_id,
+            Envelope::Response { correlation_id, .. } => *correlation_id,
+        }
+    }
+
+    /// Decode a length prefixed envelope from the (zero padded) queue data.
+    pub fn decode(payload: &[u8]) -> Option<Self> {
+        if payload.len() < 4 {
+            return None;
+        }
+        let len = u32::try_from_slice(&payload[0..4]).ok()? as usize;
+        let data = payload.get(4..4 + len)?;
+        Envelope::try_from_slice(data).ok()
+    }
+}
+
+impl From<Envelope> for Vec<u8> {
+    fn from(val: Envelope) -> Self {
+        let mut v = val.try_to_vec().unwrap();
+        let mut x = borsh::BorshSerialize::try_to_vec(&(v.len() as u32)).unwrap();
+        x.append(&mut v);
+        x
+    }
+}
+
+/// Watches the reply queue for the response to one request. Every delivered
+/// update is checked, so a response followed quickly by other pushes is not
+/// missed. Closes once the response is found or the deadline passed.
+pub struct ResponseMatcher<'a> {
+    pub correlation_id: u64,
+    pub deadline: Instant,
+    pub response: &'a mut Option<Vec<u8>>,
+}
+
+impl QueueHandler for ResponseMatcher<'_> {
+    fn handle(&mut self, update: &QueueUpdate) {
+        if self.response.is_some() {
+            return;
+        }
+        if let Some(Envelope::Response {
+            correlation_id,
+            body,
+        }) = Envelope::decode(&update.queue.data)
+        {
+            if correlation_id == self.correlation_id {
+                *self.response = Some(body);
+            }
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        self.response.is_none() && Instant::now() < self.deadline
+    }
+}
+
+/// Client side of the request/reply pattern, answers come back on `reply_to`.
+pub struct Requester<'a> {
+    pub network: &'a Network,
+    pub connection: &'a RpcClient,
+    pub payer: &'a Keypair,
+    pub program_id: String,
+    pub reply_to: String,
+    pub send_config: SendConfig,
+}
+
+impl<'a> Requester<'a> {
+    /// Push `request` to `queue` and wait until the matching response shows
+    /// up on the reply queue, or fail with `Timeout`. Replies pushed after
+    /// the request are delivered in order, backfilled when the watch missed
+    /// them.
+    pub fn call(&self, queue: &str, request: Vec<u8>, timeout: Duration) -> Result<Vec<u8>> {
+        let program_account = runtime::program_account(self.program_id.clone())?;
+        let queue_pub = sdk::pda_queue_account(&program_account, queue);
+        let reply_pub = sdk::pda_queue_account(&program_account, &self.reply_to);
+        let correlation_id: u64 = rand::random();
+
+        // anchored before sending, so a fast responder is backfilled
+        let current = sdk::fetch_queue_update(self.connection, &reply_pub)?;
+        let anchor = history::ChainAnchor {
+            seq: current.seq(),
+            hash: current.queue.hash,
+        };
+
+        info!(
+            "call {} with correlation id : {} , reply to : {}",
+            queue, correlation_id, self.reply_to
+        );
+        sdk::send_instruction(
+            self.connection,
+            &self.payer.pubkey(),
+            &vec![self.payer],
+            sdk::create_instruction(
+                self.payer.pubkey(),
+                queue_pub,
+                self.program_id.clone(),
+                queue.to_string(),
+                Envelope::Request {
+                    correlation_id,
+                    reply_to: self.reply_to.clone(),
+                    body: request,
+                }
+                .into(),
+            )?,
+            &self.send_config,
+        )?;
+
+        let mut response = None;
+        sdk::get_account_updates_from(
+            self.network,
+            &reply_pub,
+            anchor,
+            ResponseMatcher {
+                correlation_id,
+                deadline: Instant::now() + timeout,
+                response: &mut response,
+            },
+        )?;
+        response.ok_or_else(|| {
+            DdMonitorError::Timeout(format!(
+                "no response for correlation id : {}",
+                correlation_id
+            ))
+        })
+    }
+}
+
+/// Serve requests pushed to `queue`, `handler` maps a request body to the
+/// response body that is pushed to the request's reply queue. The request
+/// subscription reconnects like `sdk::get_account_updates`, a request that
+/// can not be answered is logged and skipped.
+pub fn respond(
+    network: &Network,
+    connection: &RpcClient,
+    payer: &Keypair,
+    program_id: String,
+    queue: &str,
+    config: &SendConfig,
+    mut handler: impl FnMut(&[u8]) -> Vec<u8>,
+) -> Result<()> {
+    let program_account = runtime::program_account(program_id.clone())?;
+    let queue_pub = sdk::pda_queue_account(&program_account, queue);
+
+    info!("responder is waiting for requests on : {}", queue);
+    sdk::get_account_updates(network, &queue_pub, |update: &QueueUpdate| {
+        let Some(Envelope::Request {
+            correlation_id,
+            reply_to,
+            body,
+        }) = Envelope::decode(&update.queue.data)
+        else {
+            return;
+        };
+        info!(
+            "request {} received , reply to : {}",
+            correlation_id, reply_to
+        );
+        let response = Envelope::Response {
+            correlation_id,
+            body: handler(&body),
+        };
+        if let Err(e) = reply(
+            connection,
+            payer,
+            &program_id,
+            &program_account,
+            &reply_to,
+            response,
+            config,
+        ) {
+            error!("reply to {} error : {:?}", correlation_id, e);
+        }
+    })
+}
+
+fn reply(
+    connection: &RpcClient,
+    payer: &Keypair,
+    program_id: &str,
+    program_account: &Pubkey,
+    reply_to: &str,
+    response: Envelope,
+    config: &SendConfig,
+) -> Result<()> {
+    let reply_pub = sdk::pda_queue_account(program_account, reply_to);
+    if sdk::fetch_queue(connection, &reply_pub).is_err() {
+        warn!("reply queue {} is not available , skip", reply_to);
+        return Ok(());
+    }
+    sdk::send_instruction(
+        connection,
+        &payer.pubkey(),
+        &vec![payer],
+        sdk::create_instruction(
+            payer.pubkey(),
+            reply_pub,
+            program_id.to_string(),
+            reply_to.to_string(),
+            response.into(),
+        )?,
+        config,
+    )?;
+    Ok(())
+}
@@ -1,9 +1,10 @@
 use {
+    crate::error::{DdMonitorError, Result},
     chrono::{DateTime, Local},
     log::info,
     serde::{Deserialize, Serialize},
     solana_program::pubkey::Pubkey,
-    std::{env, fs, path, str::FromStr},
+    std::{env, fs, io, path, str::FromStr},
 };
 
 pub const DEFAULT_PROGRAM: &str = "CkJ4NC4KCQfoXvyYj9Xxs4LkGDi34zNzE1e2EEeq1h9x";
@@ -21,33 +22,84 @@ pub fn get_base_dir() -> String {
     dir_path
 }
 
-pub fn init_app() {
-    confirm_dir();
+pub fn init_app() -> Result<()> {
+    confirm_dir()
 }
 
-pub fn confirm_dir() {
-    confirm_app_dir("");
-    confirm_app_dir("private");
-    confirm_app_dir("public");
+pub fn confirm_dir() -> Result<()> {
+    confirm_app_dir("")?;
+    confirm_app_dir("private")?;
+    confirm_app_dir("public")
 }
 
-pub fn confirm_app_dir(sub: &str) {
+pub fn confirm_app_dir(sub: &str) -> Result<()> {
     let check_path = app_path(sub);
     let path = path::Path::new(&check_path);
     info!("check path : [ {} ] is dir...", path.display());
     if !path.exists() {
-        fs::create_dir_all(path).unwrap();
+        fs::create_dir_all(path)?;
     }
+    Ok(())
 }
 
 pub fn app_path(sub: &str) -> String {
     let app_dir = get_base_dir();
     let path = path::Path::new(&app_dir);
-    path.join(sub).to_str().unwrap().to_string()
+    path.join(sub).to_string_lossy().to_string()
 }
 
-pub fn program_account(program_id: String) -> Pubkey {
-    Pubkey::from_str(&program_id).unwrap()
+/// Write a secret file readable by the owner only. The data goes to a temp
+/// file first, so an interrupted write never leaves half a key behind.
+pub fn write_private(path: &path::Path, data: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp_path = path.with_extension("tmp");
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(&tmp_path)?;
+    io::Write::write_all(&mut file, data)?;
+    file.sync_all()?;
+    fs::rename(&tmp_path, path)?;
+    restrict_permissions(path)
+}
+
+/// Append to a file readable by the owner only, created when missing.
+pub fn append_private(path: &path::Path, data: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut options = fs::OpenOptions::new();
+    options.create(true).append(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    restrict_permissions(path)?;
+    io::Write::write_all(&mut file, data)?;
+    Ok(())
+}
+
+/// Make an existing secret file owner only.
+pub fn restrict_permissions(path: &path::Path) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(path)?.permissions().mode();
+        if mode & 0o077 != 0 {
+            info!("restrict permissions of : {}", path.display());
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+pub fn program_account(program_id: String) -> Result<Pubkey> {
+    Pubkey::from_str(&program_id)
+        .map_err(|e| DdMonitorError::Config(format!("invalid program id {} : {}", program_id, e)))
 }
 
 pub fn current_timestamp() -> i64 {
@@ -1,44 +1,32 @@
 use {
-    crate::{runtime, runtime::ServerPrivate},
+    crate::{
+        config::Settings,
+        error::{DdMonitorError, Result},
+        funding, history, runtime,
+        sender::{self, SendConfig, SendResult},
+        wallet::{self, WalletArgs},
+    },
     base64::{
         alphabet,
         engine::{self, general_purpose},
         Engine as _,
     },
-    contract::instruction::InstructionData,
-    log::{error, info},
-    solana_account_decoder::{UiAccountData, UiAccountEncoding},
+    contract::{instruction, models},
+    log::{error, info, warn},
+    solana_account_decoder::{UiAccount, UiAccountData, UiAccountEncoding},
     solana_client::{pubsub_client::PubsubClient, rpc_client::RpcClient},
-    solana_program::{instruction::AccountMeta, pubkey::Pubkey, system_program},
-    solana_rpc_client_api::config::RpcAccountInfoConfig,
+    solana_program::{instruction::AccountMeta, pubkey::Pubkey},
+    solana_rpc_client_api::{config::RpcAccountInfoConfig, response::Response as RpcResponse},
     solana_sdk::{
         commitment_config::CommitmentConfig, instruction::Instruction, signer::keypair::Keypair,
-        signer::Signer, transaction::Transaction,
+        signer::Signer,
     },
-    std::{io::Result, thread, time},
+    std::{thread, time},
 };
 
-const PRIVATE_PATH: &str = "./private/private.json";
-
-pub fn init_solana_wallet() -> std::io::Result<Keypair> {
-    // open config file
-    let config_path = runtime::app_path(PRIVATE_PATH);
-    match std::fs::read_to_string(config_path) {
-        Ok(config) => {
-            let config = serde_json::from_str::<ServerPrivate>(&config).unwrap();
-            Ok(Keypair::from_base58_string(&config.secret))
-        }
-        Err(e) => {
-            error!("error reading config file : {}", e);
-            let wallet = Keypair::new();
-            let s = &ServerPrivate {
-                secret: wallet.to_base58_string(),
-            };
-            let secret_config: String = serde_json::to_string(s).unwrap();
-            std::fs::write(PRIVATE_PATH, secret_config)?;
-            Ok(wallet)
-        }
-    }
+/// Load the wallet chosen by `wallet`, see `wallet::load_keypair`.
+pub fn init_solana_wallet(wallet: &WalletArgs) -> Result<Keypair> {
+    wallet::load_keypair(wallet)
 }
 
 pub fn get_rpc_client(network: &Network) -> RpcClient {
@@ -48,65 +36,348 @@ pub fn get_rpc_client(network: &Network) -> RpcClient {
 pub fn pda_queue_account(program_account: &Pubkey, name: &str) -> Pubkey {
     info!(
         "program_account is : {} , name is : {}",
-        program_account.to_string(),
-        name
+        program_account, name
     );
-    let (pda, _nonce) = Pubkey::find_program_address(&[name.as_bytes()], &program_account);
+    let (pda, _nonce) = models::Queue::find_address(name, program_account);
     pda
 }
 
+pub fn fetch_queue(connection: &RpcClient, queue_pub: &Pubkey) -> Result<models::Queue> {
+    let account = connection.get_account(queue_pub)?;
+    models::Queue::unpack(&account.data).map_err(DdMonitorError::decode)
+}
+
+/// A queue account received from a subscription, already decoded.
+#[derive(Debug, Clone)]
+pub struct QueueUpdate {
+    pub slot: u64,
+    pub queue: models::Queue,
+    /// Rebuilt from transaction history rather than read from the account.
+    pub backfilled: bool,
+    /// The address that pushed, known for rebuilt updates and for live ones
+    /// that follow a delivered update on the hash chain.
+    pub sender: Option<Pubkey>,
+}
+
+impl QueueUpdate {
+    pub fn seq(&self) -> u64 {
+        self.queue.seq
+    }
+}
+
+/// Receives decoded queue updates. Closures taking `&QueueUpdate` are
+/// handlers, so they can capture whatever state they need.
+pub trait QueueHandler {
+    fn handle(&mut self, update: &QueueUpdate);
+
+    /// Whether the watch goes on, a closed handler ends it.
+    fn is_open(&self) -> bool {
+        true
+    }
+}
+
+impl<F: FnMut(&QueueUpdate)> QueueHandler for F {
+    fn handle(&mut self, update: &QueueUpdate) {
+        self(update)
+    }
+}
+
+/// The current state of a queue, with the slot it was read at.
+pub fn fetch_queue_update(connection: &RpcClient, queue_pub: &Pubkey) -> Result<QueueUpdate> {
+    let response = connection.get_account_with_commitment(queue_pub, connection.commitment())?;
+    let account = response
+        .value
+        .ok_or_else(|| DdMonitorError::NotFound(format!("queue account {}", queue_pub)))?;
+    Ok(QueueUpdate {
+        slot: response.context.slot,
+        queue: models::Queue::unpack(&account.data).map_err(DdMonitorError::decode)?,
+        backfilled: false,
+        sender: None,
+    })
+}
+
+/// Decode a base64 encoded queue account notification.
+pub fn decode_queue_update(response: RpcResponse<UiAccount>) -> Result<QueueUpdate> {
+    let UiAccountData::Binary(b64_str, _encoding) = response.value.data else {
+        return Err(DdMonitorError::decode(
+            "queue account is not base64 encoded",
+        ));
+    };
+    let data = base64_decode(&b64_str)?;
+    let queue = models::Queue::unpack(&data).map_err(DdMonitorError::decode)?;
+    Ok(QueueUpdate {
+        slot: response.context.slot,
+        queue,
+        backfilled: false,
+        sender: None,
+    })
+}
+
+const RECONNECT_DELAY_MIN: time::Duration = time::Duration::from_secs(1);
+const RECONNECT_DELAY_MAX: time::Duration = time::Duration::from_secs(30);
+/// How often an idle subscription checks that its handler is still open.
+pub const OPEN_CHECK_INTERVAL: time::Duration = time::Duration::from_secs(1);
+
+/// Call `handler` with every new message of the queue at `account_pubkey`,
+/// in sequence order. A dropped subscription is reopened with backoff, and
+/// pushes missed meanwhile, or merged into one notification, are rebuilt
+/// from transaction history before the current state is delivered. Returns
+/// once the handler is closed.
 pub fn get_account_updates(
     network: &Network,
     account_pubkey: &Pubkey,
-    callback: fn(String),
+    handler: impl QueueHandler,
+) -> Result<()> {
+    watch_queue_after(network, account_pubkey, None, handler)
+}
+
+/// Like `get_account_updates`, but every message after `seq` is delivered,
+/// the ones pushed before subscribing included.
+pub fn get_account_updates_after(
+    network: &Network,
+    account_pubkey: &Pubkey,
+    seq: u64,
+    handler: impl QueueHandler,
+) -> Result<()> {
+    let last = Delivered { seq, hash: None };
+    watch_queue_after(network, account_pubkey, Some(last), handler)
+}
+
+/// Like `get_account_updates_after`, resuming from a known chain state so
+/// backfills verify from it instead of the first push.
+pub fn get_account_updates_from(
+    network: &Network,
+    account_pubkey: &Pubkey,
+    anchor: history::ChainAnchor,
+    handler: impl QueueHandler,
+) -> Result<()> {
+    let last = Delivered {
+        seq: anchor.seq,
+        hash: Some(anchor.hash),
+    };
+    watch_queue_after(network, account_pubkey, Some(last), handler)
+}
+
+/// The last message handed to the handler. Its hash is unknown when the
+/// watch resumes from a sequence, backfills then verify from the first push.
+#[derive(Debug, Clone, Copy)]
+struct Delivered {
+    seq: u64,
+    hash: Option<[u8; 32]>,
+}
+
+impl Delivered {
+    fn anchor(&self) -> history::ChainAnchor {
+        match self.hash {
+            Some(hash) => history::ChainAnchor {
+                seq: self.seq,
+                hash,
+            },
+            None => history::ChainAnchor::GENESIS,
+        }
+    }
+}
+
+fn watch_queue_after(
+    network: &Network,
+    account_pubkey: &Pubkey,
+    mut last: Option<Delivered>,
+    mut handler: impl QueueHandler,
+) -> Result<()> {
+    let connection = get_rpc_client(network);
+    keep_subscribed(
+        account_pubkey,
+        &mut handler,
+        |handler| handler.is_open(),
+        |handler| watch_queue(&connection, network, account_pubkey, &mut last, handler),
+    );
+    Ok(())
+}
+
+/// Run `subscribe` again each time its subscription ends, waiting longer
+/// after every failure, until `is_open` says the handler is done.
+pub fn keep_subscribed<H>(
+    account_pubkey: &Pubkey,
+    handler: &mut H,
+    is_open: impl Fn(&H) -> bool,
+    mut subscribe: impl FnMut(&mut H) -> Result<()>,
+) {
+    let mut delay = RECONNECT_DELAY_MIN;
+    loop {
+        match subscribe(handler) {
+            Ok(()) => delay = RECONNECT_DELAY_MIN,
+            Err(e) => {
+                error!("account subscription error : {:?}", e);
+                delay = (delay * 2).min(RECONNECT_DELAY_MAX);
+            }
+        }
+        if !is_open(handler) {
+            info!("handler closed , stop watching : {}", account_pubkey);
+            return;
+        }
+        warn!(
+            "reconnect subscription of {} in {:?}",
+            account_pubkey, delay
+        );
+        thread::sleep(delay);
+    }
+}
+
+/// One subscription, returns once the socket is gone or the handler closed.
+fn watch_queue(
+    connection: &RpcClient,
+    network: &Network,
+    account_pubkey: &Pubkey,
+    last: &mut Option<Delivered>,
+    handler: &mut impl QueueHandler,
 ) -> Result<()> {
     let (mut _account_subscription_client, account_subscription_receiver) =
         PubsubClient::account_subscribe(
             &network.get_ws_url(),
             account_pubkey,
-            Some(RpcAccountInfoConfig {
-                encoding: Some(UiAccountEncoding::JsonParsed),
-                data_slice: None,
-                commitment: Some(CommitmentConfig::confirmed()),
-                min_context_slot: None,
-            }),
-        )
-        .unwrap();
+            Some(account_config()),
+        )?;
 
-    info!(
-        "begin loop account event with : {}",
-        account_pubkey.to_string()
-    );
-    loop {
-        match account_subscription_receiver.recv() {
+    // subscribed first, so nothing pushed after this read is missed
+    let current = fetch_queue_update(connection, account_pubkey)?;
+    let delivered = match last {
+        Some(delivered) => {
+            deliver_in_order(connection, account_pubkey, delivered, current, handler)?;
+            delivered
+        }
+        None => last.insert(Delivered {
+            seq: current.seq(),
+            hash: Some(current.queue.hash),
+        }),
+    };
+
+    info!("begin loop account event with : {}", account_pubkey);
+    while handler.is_open() {
+        match account_subscription_receiver.recv_timeout(OPEN_CHECK_INTERVAL) {
             Ok(response) => {
                 info!("account subscription received");
-                let ui_account = response.value;
-                match ui_account.data {
-                    UiAccountData::Binary(b64_str, _encoding) => {
-                        callback(b64_str);
+                match decode_queue_update(response) {
+                    Ok(update) => {
+                        deliver_in_order(connection, account_pubkey, delivered, update, handler)?
                     }
-                    _ => {}
+                    Err(e) => error!("decode queue error : {:?}", e),
                 }
             }
+            Err(e) if e.is_timeout() => {}
             Err(e) => {
                 error!("account subscription error: {:?}", e);
-                break;
+                return Ok(());
             }
         }
     }
     Ok(())
 }
 
+/// Hand `update` over unless it was seen already, backfilling any pushes
+/// between the last delivered sequence and it. A failed backfill delivers
+/// nothing, the subscription is reopened and the gap tried again.
+fn deliver_in_order(
+    connection: &RpcClient,
+    account_pubkey: &Pubkey,
+    last: &mut Delivered,
+    update: QueueUpdate,
+    handler: &mut impl QueueHandler,
+) -> Result<()> {
+    if update.seq() <= last.seq {
+        return Ok(());
+    }
+    let missed = if has_gap(last, &update) {
+        backfill(connection, account_pubkey, &update, last)?
+    } else {
+        vec![]
+    };
+    hand_over(last, missed, update, handler);
+    Ok(())
+}
+
+/// Whether pushes between `last` and `update` were missed or merged.
+fn has_gap(last: &Delivered, update: &QueueUpdate) -> bool {
+    let gap = update.seq() > last.seq + 1;
+    if gap {
+        warn!(
+            "queue seq jumped from {} to {} , backfill from history",
+            last.seq,
+            update.seq()
+        );
+    }
+    gap
+}
+
+/// Deliver the backfilled `missed` pushes, then `update`.
+fn hand_over(
+    last: &mut Delivered,
+    missed: Vec<QueueUpdate>,
+    mut update: QueueUpdate,
+    handler: &mut impl QueueHandler,
+) {
+    for missed in missed {
+        handler.handle(&missed);
+        *last = Delivered {
+            seq: missed.seq(),
+            hash: Some(missed.queue.hash),
+        };
+    }
+    if update.sender.is_none() && update.seq() == last.seq + 1 {
+        update.sender = last
+            .hash
+            .and_then(|hash| history::chain_sender(&hash, &update.queue));
+    }
+    *last = Delivered {
+        seq: update.seq(),
+        hash: Some(update.queue.hash),
+    };
+    handler.handle(&update);
+}
+
+/// Queue states for the pushes after `last` and before `current`, verified
+/// against the hash chain. Only the history back to the anchor is fetched.
+fn backfill(
+    connection: &RpcClient,
+    account_pubkey: &Pubkey,
+    current: &QueueUpdate,
+    last: &Delivered,
+) -> Result<Vec<QueueUpdate>> {
+    let program_account = connection.get_account(account_pubkey)?.owner;
+    let anchor = last.anchor();
+    let records = history::fetch_recent_pushes(
+        connection,
+        &program_account,
+        account_pubkey,
+        (current.seq() - anchor.seq) as usize,
+        current.slot,
+    )?;
+    missed_states(&records, current, last)
+}
+
+fn missed_states(
+    records: &[history::PushRecord],
+    current: &QueueUpdate,
+    last: &Delivered,
+) -> Result<Vec<QueueUpdate>> {
+    Ok(history::queue_states(records, current, last.anchor())?
+        .into_iter()
+        .filter(|update| update.seq() > last.seq && update.seq() < current.seq())
+        .collect())
+}
+
+/// Base64 encoded, confirmed account subscriptions.
+pub fn account_config() -> RpcAccountInfoConfig {
+    RpcAccountInfoConfig {
+        encoding: Some(UiAccountEncoding::Base64),
+        data_slice: None,
+        commitment: Some(CommitmentConfig::confirmed()),
+        min_context_slot: None,
+    }
+}
+
 pub fn base64_decode(data_b64: &str) -> Result<Vec<u8>> {
     let engine = engine::GeneralPurpose::new(&alphabet::STANDARD, general_purpose::PAD);
-    match engine.decode(data_b64) {
-        Ok(data) => Ok(data),
-        Err(e) => Err(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            format!("base64 decode error : {:?}", e),
-        )),
-    }
+    engine.decode(data_b64).map_err(DdMonitorError::decode)
 }
 
 pub fn base64_encode(data: &[u8]) -> String {
@@ -114,17 +385,14 @@ pub fn base64_encode(data: &[u8]) -> String {
     engine.encode(data)
 }
 
-pub fn program_available(connection: &RpcClient, program_id: &Pubkey) -> bool {
-    match connection.get_account(program_id) {
-        Ok(program_info) => {
-            info!("program_info is : {:?}", program_info);
-            program_info.lamports > 0 && program_info.executable && program_info.data.len() > 0
-        }
-        Err(e) => {
-            error!("get program info error : {:?}", e);
-            false
-        }
-    }
+pub fn program_available(connection: &RpcClient, program_id: &Pubkey) -> Result<bool> {
+    let program_info = connection
+        .get_account_with_commitment(program_id, connection.commitment())?
+        .value;
+    info!("program_info is : {:?}", program_info);
+    Ok(program_info.is_some_and(|program_info| {
+        program_info.lamports > 0 && program_info.executable && !program_info.data.is_empty()
+    }))
 }
 
 pub fn create_instruction(
@@ -133,124 +401,193 @@ pub fn create_instruction(
     program_id: String,
     name: String,
     data: Vec<u8>,
-) -> Instruction {
+) -> Result<Instruction> {
     info!(
         "you will push message with length {} to : {}, queue account : {}",
         data.len(),
         name,
-        queue_pub.to_string()
+        queue_pub
     );
-    let accounts = vec![
-        AccountMeta::new(payer_pub, true),
-        AccountMeta::new(queue_pub, false),
-        AccountMeta::new_readonly(system_program::ID, false),
-    ];
-    Instruction::new_with_borsh(
-        runtime::program_account(program_id),
-        &InstructionData::PushMessage { name, data },
-        accounts,
-    )
+    Ok(instruction::push_message(
+        &runtime::program_account(program_id)?,
+        &payer_pub,
+        &queue_pub,
+        &name,
+        data,
+    ))
+}
+
+/// One extra account for a queue hook : `ADDRESS` is passed read only,
+/// `ADDRESS:w` writable.
+pub fn parse_hook_account(arg: &str) -> Result<AccountMeta> {
+    let (address, writable) = match arg.split_once(':') {
+        Some((address, "w")) => (address, true),
+        Some((_, marker)) => {
+            return Err(DdMonitorError::Config(format!(
+                "unknown hook account marker {:?} , use ADDRESS or ADDRESS:w",
+                marker
+            )))
+        }
+        None => (arg, false),
+    };
+    let address: Pubkey = address.parse().map_err(DdMonitorError::config)?;
+    Ok(if writable {
+        AccountMeta::new(address, false)
+    } else {
+        AccountMeta::new_readonly(address, false)
+    })
 }
 
+/// Push instruction for a queue with a hook program, `remaining` are the
+/// extra accounts the hook needs and are passed along in the CPI.
+pub fn create_hook_instruction(
+    payer_pub: Pubkey,
+    queue_pub: Pubkey,
+    program_id: String,
+    name: String,
+    data: Vec<u8>,
+    hook_program: Pubkey,
+    remaining: Vec<AccountMeta>,
+) -> Result<Instruction> {
+    info!(
+        "you will push message with length {} to : {}, hook program : {}",
+        data.len(),
+        name,
+        hook_program
+    );
+    Ok(instruction::push_message_with_hook(
+        &runtime::program_account(program_id)?,
+        &payer_pub,
+        &queue_pub,
+        &name,
+        data,
+        &hook_program,
+        remaining,
+    ))
+}
+
+pub fn create_set_hook_instruction(
+    payer_pub: Pubkey,
+    queue_pub: Pubkey,
+    program_id: String,
+    name: String,
+    hook: Pubkey,
+) -> Result<Instruction> {
+    info!("you will set hook {} to queue : {}", hook, name);
+    Ok(instruction::set_hook(
+        &runtime::program_account(program_id)?,
+        &payer_pub,
+        &queue_pub,
+        &name,
+        &hook,
+    ))
+}
+
+/// Send one ddmonitor instruction with the compute budget, retries and
+/// confirmation level of `config`.
 pub fn send_instruction(
     connection: &RpcClient,
     payer: &Pubkey,
     singers: &Vec<&Keypair>,
     instruction: Instruction,
-) {
-    let blockhash = connection.get_latest_blockhash().unwrap();
-    let transaction =
-        Transaction::new_signed_with_payer(&[instruction], Some(&payer), singers, blockhash);
-
-    match connection.send_and_confirm_transaction(&transaction) {
-        Ok(tx) => {
-            info!("send message tx : {:?}", tx);
-        }
-        Err(e) => {
-            error!("send message error : {:?}", e);
-        }
-    }
+    config: &SendConfig,
+) -> Result<SendResult> {
+    let program_id = instruction.program_id;
+    let result = sender::send_transaction(
+        connection,
+        payer,
+        singers,
+        &[instruction],
+        &program_id,
+        config,
+    )?;
+    info!(
+        "send message tx : {} at slot {}",
+        result.signature, result.slot
+    );
+    Ok(result)
 }
 
 pub fn connection_available(connection: &RpcClient) -> Result<bool> {
-    match connection.get_version() {
-        Ok(version) => {
-            info!("solana version : {}", version.solana_core);
-            Ok(true)
-        }
-        Err(e) => {
-            error!("get solana version error : {:?}", e);
-            Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!("get solana version error : {:?}", e),
-            ))
-        }
-    }
-}
-
-pub fn confirm_balance(
-    connection: &RpcClient,
-    network: &Network,
-    pub_key: &Pubkey,
-    air_drop_sol: u64,
-) {
-    loop {
-        let balance = connection.get_balance(&pub_key).unwrap();
-        info!("current balance is : {}", balance);
-        if balance >= runtime::LAMPORTS_PER_SOL {
-            break;
-        } else {
-            if network.airdrop_enable() {
-                info!("airdrop sol : {}", air_drop_sol);
-                let _ =
-                    connection.request_airdrop(&pub_key, runtime::LAMPORTS_PER_SOL * air_drop_sol);
-            }
-            let delay = time::Duration::from_secs(3);
-            thread::sleep(delay);
-        }
-    }
+    let version = connection.get_version()?;
+    info!("solana version : {}", version.solana_core);
+    Ok(true)
 }
 
 pub fn ddmonitor_init(
-    network: &str,
-    program: &str,
+    settings: &Settings,
 ) -> Result<(Network, Keypair, Pubkey, RpcClient, Pubkey)> {
-    let network: Network = Network::from_string(network);
+    let network = settings.network.clone();
     info!("network is : <{:?}> ", network);
-    let pair = init_solana_wallet()?;
+    let pair = settings.keypair()?;
     let pub_key = pair.pubkey();
     let connection = get_rpc_client(&network);
     info!("current wallet address : {}", &pub_key);
     connection_available(&connection)?;
-    confirm_balance(&connection, &network, &pub_key, 5);
-    let program_account = runtime::program_account(program.to_string());
-    if !program_available(&connection, &program_account) {
-        error!("program account is not available , exit...");
-        return Err(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            format!("program account is not available , exit..."),
-        ));
+    funding::fund_wallet(
+        &connection,
+        &network,
+        &pub_key,
+        Some(settings.queue.size()),
+        &settings.funding,
+        &settings.send.compute_budget,
+    )?;
+    let program_account = settings.program_account()?;
+    if !program_available(&connection, &program_account)? {
+        return Err(DdMonitorError::NotFound(format!(
+            "program account {}",
+            program_account
+        )));
     }
     Ok((network, pair, pub_key, connection, program_account))
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Network {
     Local,
     Dev,
     Test,
     MainBeta,
+    Custom { rpc: String, ws: String },
 }
 
 impl Network {
-    pub fn from_string(network_name: &str) -> Self {
-        match network_name {
-            "local" => Self::Local,
-            "dev" => Self::Dev,
-            "test" => Self::Test,
-            "main-beta" => Self::MainBeta,
-            _ => Self::Local,
+    /// A custom cluster, both urls must be http(s) and ws(s) urls.
+    pub fn custom(rpc: &str, ws: &str) -> Result<Self> {
+        if !(rpc.starts_with("http://") || rpc.starts_with("https://")) {
+            return Err(DdMonitorError::Config(format!("invalid rpc url : {}", rpc)));
+        }
+        if !(ws.starts_with("ws://") || ws.starts_with("wss://")) {
+            return Err(DdMonitorError::Config(format!("invalid ws url : {}", ws)));
         }
+        Ok(Self::Custom {
+            rpc: rpc.to_string(),
+            ws: ws.to_string(),
+        })
+    }
+
+    /// Websocket url the way the solana cli derives it : same host, ws(s)
+    /// scheme and the rpc port plus one when a port is given.
+    pub fn websocket_url(rpc: &str) -> Result<String> {
+        let (scheme, rest) = match rpc.split_once("://") {
+            Some(("http", rest)) => ("ws", rest),
+            Some(("https", rest)) => ("wss", rest),
+            _ => return Err(DdMonitorError::Config(format!("invalid rpc url : {}", rpc))),
+        };
+        let (host, path) = match rest.find('/') {
+            Some(index) => rest.split_at(index),
+            None => (rest, ""),
+        };
+        let host = match host.rsplit_once(':') {
+            Some((name, port)) if !host.ends_with(']') => {
+                let port: u16 = port.parse().map_err(|_| {
+                    DdMonitorError::Config(format!("invalid port in rpc url : {}", rpc))
+                })?;
+                format!("{}:{}", name, port.saturating_add(1))
+            }
+            _ => host.to_string(),
+        };
+        Ok(format!("{}://{}{}", scheme, host, path))
     }
 
     pub fn get_ws_url(&self) -> String {
@@ -259,6 +596,7 @@ impl Network {
             Self::Dev => "wss://api.devnet.solana.com/".to_string(),
             Self::Test => "wss://api.testnet.solana.com/".to_string(),
             Self::MainBeta => "wss://api.mainnet-beta.solana.com/".to_string(),
+            Self::Custom { ws, .. } => ws.clone(),
         }
     }
 
@@ -268,15 +606,204 @@ impl Network {
             Self::Dev => "https://api.devnet.solana.com".to_string(),
             Self::Test => "https://api.testnet.solana.com".to_string(),
             Self::MainBeta => "https://api.mainnet-beta.solana.com".to_string(),
+            Self::Custom { rpc, .. } => rpc.clone(),
         }
     }
 
+    /// Whether the cluster hands out airdrops. A custom url can point
+    /// anywhere, airdrops there are opt-in through
+    /// `FundingConfig::airdrop_custom`.
     pub fn airdrop_enable(&self) -> bool {
         match self {
             Self::Local => true,
             Self::Dev => true,
             Self::Test => true,
             Self::MainBeta => false,
+            Self::Custom { .. } => false,
+        }
+    }
+
+    /// The named cluster served at `rpc`, the solana cli config stores
+    /// clusters as urls.
+    fn cluster_at(rpc: &str) -> Option<Self> {
+        let rpc = rpc.trim_end_matches('/');
+        if rpc == "http://localhost:8899" {
+            return Some(Self::Local);
+        }
+        [Self::Local, Self::Dev, Self::Test, Self::MainBeta]
+            .into_iter()
+            .find(|network| network.get_rpc_url() == rpc)
+    }
+}
+
+impl std::str::FromStr for Network {
+    type Err = DdMonitorError;
+
+    /// A cluster name (`local`, `dev`, `test`, `main-beta` or the solana cli
+    /// monikers), an rpc url, or `<rpc url>,<ws url>`.
+    fn from_str(network_name: &str) -> Result<Self> {
+        let network_name = network_name.trim();
+        match network_name {
+            "local" | "localhost" | "l" => Ok(Self::Local),
+            "dev" | "devnet" | "d" => Ok(Self::Dev),
+            "test" | "testnet" | "t" => Ok(Self::Test),
+            "main-beta" | "mainnet-beta" | "m" => Ok(Self::MainBeta),
+            _ if network_name.contains("://") => match network_name.split_once(',') {
+                Some((rpc, ws)) => Self::custom(rpc.trim(), ws.trim()),
+                None => match Self::cluster_at(network_name) {
+                    Some(network) => Ok(network),
+                    None => Self::custom(network_name, &Self::websocket_url(network_name)?),
+                },
+            },
+            _ => Err(DdMonitorError::Config(format!(
+                "unknown network : {} , expected local, dev, test, main-beta or an rpc url",
+                network_name
+            ))),
+        }
+    }
+}
+
+/// The queue watcher over the nonblocking RPC and pubsub clients, it waits
+/// on the runtime instead of parking a thread.
+pub mod nonblocking {
+    use {
+        super::*,
+        futures::StreamExt,
+        solana_client::nonblocking::{pubsub_client::PubsubClient, rpc_client::RpcClient},
+    };
+
+    pub async fn fetch_queue_update(
+        connection: &RpcClient,
+        queue_pub: &Pubkey,
+    ) -> Result<QueueUpdate> {
+        let response = connection
+            .get_account_with_commitment(queue_pub, connection.commitment())
+            .await?;
+        let account = response
+            .value
+            .ok_or_else(|| DdMonitorError::NotFound(format!("queue account {}", queue_pub)))?;
+        Ok(QueueUpdate {
+            slot: response.context.slot,
+            queue: models::Queue::unpack(&account.data).map_err(DdMonitorError::decode)?,
+            backfilled: false,
+            sender: None,
+        })
+    }
+
+    /// Like `sdk::get_account_updates_from`, every message after `anchor`
+    /// in sequence order. A dropped subscription is reopened with backoff
+    /// and missed pushes are backfilled, returns once the handler is closed.
+    pub async fn get_account_updates_from(
+        network: &Network,
+        account_pubkey: &Pubkey,
+        anchor: history::ChainAnchor,
+        mut handler: impl QueueHandler,
+    ) {
+        let connection =
+            RpcClient::new_with_commitment(network.get_rpc_url(), CommitmentConfig::confirmed());
+        let mut last = Delivered {
+            seq: anchor.seq,
+            hash: Some(anchor.hash),
+        };
+        let mut delay = RECONNECT_DELAY_MIN;
+        loop {
+            match watch_queue(
+                &connection,
+                network,
+                account_pubkey,
+                &mut last,
+                &mut handler,
+            )
+            .await
+            {
+                Ok(()) => delay = RECONNECT_DELAY_MIN,
+                Err(e) => {
+                    error!("queue subscription error : {:?}", e);
+                    delay = (delay * 2).min(RECONNECT_DELAY_MAX);
+                }
+            }
+            if !handler.is_open() {
+                info!("queue handler closed , stop watching : {}", account_pubkey);
+                return;
+            }
+            warn!("reconnect queue subscription in {:?}", delay);
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// One subscription, returns once the socket is gone or the handler closed.
+    async fn watch_queue(
+        connection: &RpcClient,
+        network: &Network,
+        account_pubkey: &Pubkey,
+        last: &mut Delivered,
+        handler: &mut impl QueueHandler,
+    ) -> Result<()> {
+        let pubsub = PubsubClient::new(&network.get_ws_url()).await?;
+        let (mut notifications, unsubscribe) = pubsub
+            .account_subscribe(account_pubkey, Some(account_config()))
+            .await?;
+
+        // subscribed first, so nothing pushed after this read is missed
+        let current = fetch_queue_update(connection, account_pubkey).await?;
+        deliver_in_order(connection, account_pubkey, last, current, handler).await?;
+
+        info!("begin stream account event with : {}", account_pubkey);
+        while handler.is_open() {
+            match tokio::time::timeout(OPEN_CHECK_INTERVAL, notifications.next()).await {
+                Ok(Some(response)) => match decode_queue_update(response) {
+                    Ok(update) => {
+                        deliver_in_order(connection, account_pubkey, last, update, handler).await?
+                    }
+                    Err(e) => error!("decode queue error : {:?}", e),
+                },
+                Ok(None) => {
+                    error!("account subscription closed : {}", account_pubkey);
+                    break;
+                }
+                Err(_) => {}
+            }
+        }
+        drop(notifications);
+        unsubscribe().await;
+        Ok(())
+    }
+
+    async fn deliver_in_order(
+        connection: &RpcClient,
+        account_pubkey: &Pubkey,
+        last: &mut Delivered,
+        update: QueueUpdate,
+        handler: &mut impl QueueHandler,
+    ) -> Result<()> {
+        if update.seq() <= last.seq {
+            return Ok(());
         }
+        let missed = if has_gap(last, &update) {
+            backfill(connection, account_pubkey, &update, last).await?
+        } else {
+            vec![]
+        };
+        hand_over(last, missed, update, handler);
+        Ok(())
+    }
+
+    async fn backfill(
+        connection: &RpcClient,
+        account_pubkey: &Pubkey,
+        current: &QueueUpdate,
+        last: &Delivered,
+    ) -> Result<Vec<QueueUpdate>> {
+        let program_account = connection.get_account(account_pubkey).await?.owner;
+        let anchor = last.anchor();
+        let records = history::nonblocking::fetch_recent_pushes(
+            connection,
+            &program_account,
+            account_pubkey,
+            (current.seq() - anchor.seq) as usize,
+            current.slot,
+        )
+        .await?;
+        missed_states(&records, current, last)
     }
 }
@@ -0,0 +1,390 @@
+use {
+    crate::{
+        error::{DdMonitorError, Result},
+        fees::{self, ComputeBudget, Setting},
+    },
+    clap::Args,
+    log::warn,
+    serde::{Deserialize, Serialize},
+    solana_client::{client_error::ClientErrorKind, rpc_client::RpcClient},
+    solana_program::pubkey::Pubkey,
+    solana_sdk::{
+        commitment_config::{CommitmentConfig, CommitmentLevel},
+        hash::Hash,
+        instruction::Instruction,
+        signature::Signature,
+        signer::keypair::Keypair,
+        transaction::TransactionError,
+    },
+    solana_transaction_status::TransactionStatus,
+    std::{thread, time},
+};
+
+/// How the SDK sends transactions and when it considers them landed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SendConfig {
+    pub max_retries: usize,
+    pub commitment: CommitmentLevel,
+    pub poll_interval_ms: u64,
+    pub compute_budget: ComputeBudget,
+}
+
+impl Default for SendConfig {
+    fn default() -> Self {
+        DEFAULT_SEND_CONFIG
+    }
+}
+
+const DEFAULT_SEND_CONFIG: SendConfig = SendConfig {
+    max_retries: 3,
+    commitment: CommitmentLevel::Confirmed,
+    poll_interval_ms: 500,
+    compute_budget: ComputeBudget {
+        compute_unit_limit: None,
+        compute_unit_price: None,
+    },
+};
+
+/// Send settings as flags or as the `[send]` table of the config file, an
+/// unset value falls through to the next layer and then to the default.
+#[derive(Args, Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SendArgs {
+    /// Times a transaction is sent again after its blockhash expired or
+    /// the RPC failed [default: 3]
+    #[arg(long, global = true)]
+    pub max_retries: Option<usize>,
+
+    /// Confirmation level to wait for: processed, confirmed or finalized
+    /// [default: confirmed]
+    #[arg(long, global = true)]
+    pub commitment: Option<CommitmentLevel>,
+
+    /// Milliseconds between confirmation checks and before a retry
+    /// [default: 500]
+    #[arg(long, global = true)]
+    pub poll_interval_ms: Option<u64>,
+
+    /// Compute unit limit, a number or auto to right size it by simulation
+    #[arg(long, global = true)]
+    pub compute_unit_limit: Option<Setting<u32>>,
+
+    /// Compute unit price in micro lamports, a number or auto to estimate it
+    /// from recent prioritization fees
+    #[arg(long, global = true)]
+    pub compute_unit_price: Option<Setting<u64>>,
+}
+
+impl SendArgs {
+    /// The values of `self`, unset ones taken from `fallback`.
+    pub fn or(self, fallback: Self) -> Self {
+        Self {
+            max_retries: self.max_retries.or(fallback.max_retries),
+            commitment: self.commitment.or(fallback.commitment),
+            poll_interval_ms: self.poll_interval_ms.or(fallback.poll_interval_ms),
+            compute_unit_limit: self.compute_unit_limit.or(fallback.compute_unit_limit),
+            compute_unit_price: self.compute_unit_price.or(fallback.compute_unit_price),
+        }
+    }
+
+    /// The config with defaults for the unset values.
+    pub fn config(self) -> SendConfig {
+        let default = DEFAULT_SEND_CONFIG;
+        SendConfig {
+            max_retries: self.max_retries.unwrap_or(default.max_retries),
+            commitment: self.commitment.unwrap_or(default.commitment),
+            poll_interval_ms: self.poll_interval_ms.unwrap_or(default.poll_interval_ms),
+            compute_budget: ComputeBudget {
+                compute_unit_limit: self.compute_unit_limit,
+                compute_unit_price: self.compute_unit_price,
+            },
+        }
+    }
+}
+
+/// A transaction that reached the requested confirmation level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SendResult {
+    pub signature: Signature,
+    pub slot: u64,
+    pub attempts: usize,
+}
+
+/// How one send attempt ended, errors are returned separately.
+enum Attempt {
+    Landed(SendResult),
+    Expired,
+}
+
+/// Send `instructions` with the compute budget of `config` and wait for its
+/// confirmation level.
+/// Custom errors of the instructions to `program_id` are read as ddmonitor
+/// errors.
+/// A transaction is signed again only once its blockhash expired without it
+/// landing, transient RPC errors in between keep polling the same signature.
+/// A transaction the program rejected is returned as an error.
+pub fn send_transaction(
+    connection: &RpcClient,
+    payer: &Pubkey,
+    signers: &[&Keypair],
+    instructions: &[Instruction],
+    program_id: &Pubkey,
+    config: &SendConfig,
+) -> Result<SendResult> {
+    let mut last_error = String::new();
+    for attempt in 1..=config.max_retries + 1 {
+        let sent = send_once(
+            connection,
+            payer,
+            signers,
+            instructions,
+            program_id,
+            config,
+            attempt,
+        );
+        match sent {
+            Ok(Attempt::Landed(result)) => return Ok(result),
+            Ok(Attempt::Expired) => {
+                last_error = "blockhash expired before confirmation".to_string();
+            }
+            Err(e) if is_retryable(&e) => {
+                last_error = e.to_string();
+            }
+            Err(e) => return Err(e),
+        }
+        warn!(
+            "send attempt {} failed : {} , retry...",
+            attempt, last_error
+        );
+        thread::sleep(time::Duration::from_millis(config.poll_interval_ms));
+    }
+    Err(expired_error(config, &last_error))
+}
+
+fn send_once(
+    connection: &RpcClient,
+    payer: &Pubkey,
+    signers: &[&Keypair],
+    instructions: &[Instruction],
+    program_id: &Pubkey,
+    config: &SendConfig,
+    attempt: usize,
+) -> Result<Attempt> {
+    let transaction = fees::build_transaction(
+        connection,
+        payer,
+        signers,
+        instructions,
+        &config.compute_budget,
+        program_id,
+    )?;
+    let programs = fees::transaction_programs(&transaction);
+    let blockhash = transaction.message.recent_blockhash;
+    let signature = transaction.signatures[0];
+    let failed = |e: DdMonitorError| e.for_program(&programs, program_id);
+    if let Err(e) = connection.send_transaction(&transaction) {
+        unless_transient::<()>(Err(e.into()), &signature).map_err(failed)?;
+    }
+    let commitment = CommitmentConfig {
+        commitment: config.commitment,
+    };
+    loop {
+        let checked = check(connection, &signature, &blockhash, commitment, attempt);
+        if let Some(attempt) = unless_transient(checked, &signature)
+            .map_err(failed)?
+            .flatten()
+        {
+            return Ok(attempt);
+        }
+        thread::sleep(time::Duration::from_millis(config.poll_interval_ms));
+    }
+}
+
+/// Look up `signature` once, `None` while it may still land.
+fn check(
+    connection: &RpcClient,
+    signature: &Signature,
+    blockhash: &Hash,
+    commitment: CommitmentConfig,
+    attempt: usize,
+) -> Result<Option<Attempt>> {
+    let status = connection.get_signature_statuses(&[*signature])?.value[0].clone();
+    match status {
+        Some(status) => landed(status, commitment, signature, attempt),
+        None if connection.is_blockhash_valid(blockhash, CommitmentConfig::processed())? => {
+            Ok(None)
+        }
+        None => Ok(Some(Attempt::Expired)),
+    }
+}
+
+/// The attempt once `status` satisfies `commitment`.
+fn landed(
+    status: TransactionStatus,
+    commitment: CommitmentConfig,
+    signature: &Signature,
+    attempt: usize,
+) -> Result<Option<Attempt>> {
+    if let Some(err) = status.err {
+        return Err(err.into());
+    }
+    Ok(status
+        .satisfies_commitment(commitment)
+        .then_some(Attempt::Landed(SendResult {
+            signature: *signature,
+            slot: status.slot,
+            attempts: attempt,
+        })))
+}
+
+/// A transport or RPC failure while sending or polling says nothing about
+/// whether the transaction landed, so it is logged and the same signature
+/// is polled again instead of signing a new transaction.
+fn unless_transient<T>(result: Result<T>, signature: &Signature) -> Result<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(e) if is_transient(&e) => {
+            warn!(
+                "rpc call for {} failed : {} , polling it again",
+                signature, e
+            );
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Transport and RPC failures are worth another try, a transaction the
+/// cluster rejected is not unless its blockhash was unknown.
+fn is_retryable(error: &DdMonitorError) -> bool {
+    match error {
+        DdMonitorError::Program { error, .. } => *error == TransactionError::BlockhashNotFound,
+        _ => is_transient(error),
+    }
+}
+
+fn is_transient(error: &DdMonitorError) -> bool {
+    match error {
+        DdMonitorError::Rpc(error) => matches!(
+            error.kind(),
+            ClientErrorKind::Io(_) | ClientErrorKind::Reqwest(_) | ClientErrorKind::RpcError(_)
+        ),
+        _ => false,
+    }
+}
+
+fn expired_error(config: &SendConfig, last_error: &str) -> DdMonitorError {
+    DdMonitorError::Timeout(format!(
+        "transaction not confirmed after {} attempts : {}",
+        config.max_retries + 1,
+        last_error
+    ))
+}
+
+/// The same sender over the nonblocking RPC client.
+pub mod nonblocking {
+    use {super::*, solana_client::nonblocking::rpc_client::RpcClient};
+
+    pub async fn send_transaction(
+        connection: &RpcClient,
+        payer: &Pubkey,
+        signers: &[&Keypair],
+        instructions: &[Instruction],
+        program_id: &Pubkey,
+        config: &SendConfig,
+    ) -> Result<SendResult> {
+        let mut last_error = String::new();
+        for attempt in 1..=config.max_retries + 1 {
+            let sent = send_once(
+                connection,
+                payer,
+                signers,
+                instructions,
+                program_id,
+                config,
+                attempt,
+            )
+            .await;
+            match sent {
+                Ok(Attempt::Landed(result)) => return Ok(result),
+                Ok(Attempt::Expired) => {
+                    last_error = "blockhash expired before confirmation".to_string();
+                }
+                Err(e) if is_retryable(&e) => {
+                    last_error = e.to_string();
+                }
+                Err(e) => return Err(e),
+            }
+            warn!(
+                "send attempt {} failed : {} , retry...",
+                attempt, last_error
+            );
+            tokio::time::sleep(time::Duration::from_millis(config.poll_interval_ms)).await;
+        }
+        Err(expired_error(config, &last_error))
+    }
+
+    async fn send_once(
+        connection: &RpcClient,
+        payer: &Pubkey,
+        signers: &[&Keypair],
+        instructions: &[Instruction],
+        program_id: &Pubkey,
+        config: &SendConfig,
+        attempt: usize,
+    ) -> Result<Attempt> {
+        let transaction = fees::nonblocking::build_transaction(
+            connection,
+            payer,
+            signers,
+            instructions,
+            &config.compute_budget,
+            program_id,
+        )
+        .await?;
+        let programs = fees::transaction_programs(&transaction);
+        let blockhash = transaction.message.recent_blockhash;
+        let signature = transaction.signatures[0];
+        let failed = |e: DdMonitorError| e.for_program(&programs, program_id);
+        if let Err(e) = connection.send_transaction(&transaction).await {
+            unless_transient::<()>(Err(e.into()), &signature).map_err(failed)?;
+        }
+        let commitment = CommitmentConfig {
+            commitment: config.commitment,
+        };
+        loop {
+            let checked = check(connection, &signature, &blockhash, commitment, attempt).await;
+            if let Some(attempt) = unless_transient(checked, &signature)
+                .map_err(failed)?
+                .flatten()
+            {
+                return Ok(attempt);
+            }
+            tokio::time::sleep(time::Duration::from_millis(config.poll_interval_ms)).await;
+        }
+    }
+
+    async fn check(
+        connection: &RpcClient,
+        signature: &Signature,
+        blockhash: &Hash,
+        commitment: CommitmentConfig,
+        attempt: usize,
+    ) -> Result<Option<Attempt>> {
+        let status = connection
+            .get_signature_statuses(&[*signature])
+            .await?
+            .value[0]
+            .clone();
+        match status {
+            Some(status) => landed(status, commitment, signature, attempt),
+            None if connection
+                .is_blockhash_valid(blockhash, CommitmentConfig::processed())
+                .await? =>
+            {
+                Ok(None)
+            }
+            None => Ok(Some(Attempt::Expired)),
+        }
+    }
+}
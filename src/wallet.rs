@@ -0,0 +1,158 @@
+use {
+    crate::{
+        cluster::SolanaCliConfig,
+        error::{DdMonitorError, Result},
+        keystore::{self, Keystore},
+        runtime::{self, ServerPrivate},
+    },
+    clap::Args,
+    log::{info, warn},
+    solana_sdk::{
+        bs58,
+        signature::{read_keypair_file, write_keypair_file},
+        signer::{keypair::Keypair, Signer},
+    },
+    std::{
+        env, fs,
+        path::{Path, PathBuf},
+    },
+};
+
+/// Path to a keypair file, used when `--keypair` is not given.
+pub const KEYPAIR_ENV: &str = "DDMONITOR_KEYPAIR";
+
+/// Where generated wallets are written, under `app_path`.
+pub const KEYPAIR_PATH: &str = "private/keypair.json";
+
+/// The `{"secret": base58}` wallet older versions wrote, still read.
+pub const LEGACY_PRIVATE_PATH: &str = "private/private.json";
+
+const APP_WALLET: &str = "app wallet";
+
+/// Wallet selection shared by the binaries.
+#[derive(Args, Debug, Clone, Default)]
+pub struct WalletArgs {
+    /// Keypair file, a solana cli json byte array. Defaults to
+    /// `DDMONITOR_KEYPAIR`, the app wallet, then the solana cli keypair
+    #[arg(long, global = true)]
+    pub keypair: Option<String>,
+
+    /// Generate a new keypair when none is found
+    #[arg(long, global = true, default_value_t = false)]
+    pub generate_keypair: bool,
+
+    /// Keep the app wallet in an encrypted keystore, generated keys are
+    /// encrypted and a plaintext app wallet is migrated in place
+    #[arg(long, global = true, default_value_t = false)]
+    pub encrypt_keypair: bool,
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Read a solana cli keypair (json byte array), an encrypted keystore or a
+/// legacy `{"secret": base58}` wallet.
+pub fn read_keypair(path: &Path) -> Result<Keypair> {
+    let text = fs::read_to_string(path)?;
+    if Keystore::is_keystore(&text) {
+        return keystore::unlock(path);
+    }
+    if text.trim_start().starts_with('[') {
+        return read_keypair_file(path).map_err(|e| {
+            DdMonitorError::Config(format!("invalid keypair file {} : {}", path.display(), e))
+        });
+    }
+    let config = serde_json::from_str::<ServerPrivate>(&text).map_err(|e| {
+        DdMonitorError::Config(format!("invalid keypair file {} : {}", path.display(), e))
+    })?;
+    let secret = bs58::decode(&config.secret)
+        .into_vec()
+        .map_err(DdMonitorError::config)?;
+    Keypair::from_bytes(&secret).map_err(DdMonitorError::config)
+}
+
+/// Write `keypair` as a solana cli json byte array.
+pub fn write_keypair(keypair: &Keypair, path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    write_keypair_file(keypair, path).map_err(|e| {
+        DdMonitorError::Config(format!("write keypair file {} : {}", path.display(), e))
+    })?;
+    Ok(())
+}
+
+/// The keypair file to use and where it came from, first match wins.
+pub fn keypair_path(args: &WalletArgs) -> Result<Option<(PathBuf, &'static str)>> {
+    if let Some(path) = &args.keypair {
+        return Ok(Some((expand_home(path), "--keypair")));
+    }
+    if let Ok(path) = env::var(KEYPAIR_ENV) {
+        return Ok(Some((expand_home(&path), KEYPAIR_ENV)));
+    }
+    for sub in [KEYPAIR_PATH, LEGACY_PRIVATE_PATH] {
+        let path = PathBuf::from(runtime::app_path(sub));
+        if path.exists() {
+            return Ok(Some((path, APP_WALLET)));
+        }
+    }
+    let cli_path = SolanaCliConfig::load()?
+        .and_then(|config| config.keypair_path)
+        .map(|path| expand_home(&path))
+        .filter(|path| path.exists());
+    Ok(cli_path.map(|path| (path, "solana cli config")))
+}
+
+/// Load the wallet chosen by `args`. A missing keypair is an error unless
+/// `generate_keypair` is set, new keys go to the `--keypair` path or the
+/// app wallet path.
+pub fn load_keypair(args: &WalletArgs) -> Result<Keypair> {
+    let found = keypair_path(args)?;
+    if let Some((path, source)) = &found {
+        if path.exists() {
+            let keypair = read_keypair(path)?;
+            info!(
+                "wallet {} from {} : {}",
+                keypair.pubkey(),
+                source,
+                path.display()
+            );
+            if *source == APP_WALLET {
+                runtime::restrict_permissions(path)?;
+                if args.encrypt_keypair && !Keystore::is_keystore(&fs::read_to_string(path)?) {
+                    keystore::migrate(&keypair, path)?;
+                }
+            }
+            return Ok(keypair);
+        }
+    }
+    if !args.generate_keypair {
+        return Err(DdMonitorError::NotFound(match found {
+            Some((path, source)) => format!("keypair {} (from {})", path.display(), source),
+            None => format!(
+                "keypair , pass --keypair, set {} or use --generate-keypair",
+                KEYPAIR_ENV
+            ),
+        }));
+    }
+    let path = match found {
+        Some((path, _)) => path,
+        None => PathBuf::from(runtime::app_path(KEYPAIR_PATH)),
+    };
+    let keypair = Keypair::new();
+    if args.encrypt_keypair {
+        keystore::create(&keypair, &path)?;
+    } else {
+        write_keypair(&keypair, &path)?;
+    }
+    warn!(
+        "generated new wallet {} at : {}",
+        keypair.pubkey(),
+        path.display()
+    );
+    Ok(keypair)
+}
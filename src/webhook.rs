@@ -0,0 +1,441 @@
+use {
+    crate::{
+        error::{DdMonitorError, Result},
+        inspect, payload, runtime,
+        sdk::{self, QueueHandler, QueueUpdate},
+    },
+    chrono::Utc,
+    log::{error, info, warn},
+    ring::hmac,
+    serde::{Deserialize, Serialize},
+    solana_program::pubkey::Pubkey,
+    std::{
+        path::PathBuf,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            mpsc, Arc,
+        },
+        thread::{self, JoinHandle},
+        time::{Duration, Instant},
+    },
+};
+
+/// Overrides the `secret` of the `[webhook]` table.
+pub const SECRET_ENV: &str = "DDMONITOR_WEBHOOK_SECRET";
+/// `sha256=<hex>` HMAC of `<timestamp>.<body>`, set when a secret is.
+pub const SIGNATURE_HEADER: &str = "X-DdMonitor-Signature";
+/// Unix seconds the request was signed at.
+pub const TIMESTAMP_HEADER: &str = "X-DdMonitor-Timestamp";
+/// `<queue>:<seq>`, the same for every retry of one update.
+pub const DELIVERY_HEADER: &str = "X-DdMonitor-Delivery";
+const DEAD_LETTER_PATH: &str = "webhook-dead-letter.jsonl";
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// The `[webhook]` table :
+///
+/// ```toml
+/// [webhook]
+/// urls = ["https://hooks.example.com/ddmonitor"]
+/// secret = "shared secret"
+/// retries = 5
+/// backoff_ms = 500
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebhookConfig {
+    pub urls: Vec<String>,
+    /// Key requests are signed with, unsigned without one.
+    pub secret: Option<String>,
+    /// Attempts after the first one before an update is dead lettered.
+    pub retries: u32,
+    /// Delay before the first retry, doubled for each next one.
+    pub backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    pub timeout_secs: u64,
+    /// File failed deliveries are appended to, one JSON line each,
+    /// defaults to webhook-dead-letter.jsonl under the app path. It holds
+    /// the events, so only the owner may read it.
+    pub dead_letter: Option<String>,
+    /// Seconds a stopping forwarder waits for the deliveries already
+    /// queued, the ones left are dropped unacked.
+    pub shutdown_timeout_secs: u64,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            urls: Vec::new(),
+            secret: None,
+            retries: 5,
+            backoff_ms: 500,
+            max_backoff_ms: 30_000,
+            timeout_secs: 10,
+            dead_letter: None,
+            shutdown_timeout_secs: 30,
+        }
+    }
+}
+
+impl WebhookConfig {
+    pub fn dead_letter_path(&self) -> PathBuf {
+        PathBuf::from(
+            self.dead_letter
+                .clone()
+                .unwrap_or_else(|| runtime::app_path(DEAD_LETTER_PATH)),
+        )
+    }
+
+    /// Delay before retry `attempt`, counted from 1.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self
+            .backoff_ms
+            .saturating_mul(1 << attempt.saturating_sub(1).min(32));
+        Duration::from_millis(delay.min(self.max_backoff_ms))
+    }
+}
+
+fn signing_key(secret: &str) -> hmac::Key {
+    hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes())
+}
+
+fn signed_message(timestamp: i64, body: &[u8]) -> Vec<u8> {
+    let mut message = format!("{}.", timestamp).into_bytes();
+    message.extend_from_slice(body);
+    message
+}
+
+/// Hex HMAC-SHA256 of `<timestamp>.<body>`.
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let tag = hmac::sign(&signing_key(secret), &signed_message(timestamp, body));
+    inspect::to_hex(tag.as_ref())
+}
+
+/// Check a `SIGNATURE_HEADER` value, for receivers written in Rust.
+pub fn verify(secret: &str, timestamp: i64, body: &[u8], signature: &str) -> bool {
+    let Some(hex) = signature.strip_prefix("sha256=") else {
+        return false;
+    };
+    let tag: Option<Vec<u8>> = (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok())
+        .collect();
+    tag.is_some_and(|tag| {
+        hmac::verify(&signing_key(secret), &signed_message(timestamp, body), &tag).is_ok()
+    })
+}
+
+/// The JSON body POSTed for one queue update.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct QueueEvent {
+    pub queue: String,
+    pub address: String,
+    pub seq: u64,
+    pub slot: u64,
+    /// The address that pushed, when it could be found.
+    pub sender: Option<String>,
+    pub content_type: String,
+    pub last_change: i64,
+    /// Rebuilt from transaction history rather than read live.
+    pub backfilled: bool,
+    /// The data decoded for the queue content type.
+    pub payload: serde_json::Value,
+    /// The data as the account holds it, padding included.
+    pub data_base64: String,
+}
+
+impl QueueEvent {
+    pub fn new(queue: &str, address: &Pubkey, update: &QueueUpdate) -> Self {
+        Self {
+            queue: queue.to_string(),
+            address: address.to_string(),
+            seq: update.seq(),
+            slot: update.slot,
+            sender: update.sender.map(|sender| sender.to_string()),
+            content_type: payload::content_type_name(&update.queue.content_type),
+            last_change: update.queue.last_change,
+            backfilled: update.backfilled,
+            payload: match payload::decode_queue(&update.queue) {
+                Ok(payload) => payload.to_json(),
+                Err(e) => serde_json::json!({ "error": e.to_string() }),
+            },
+            data_base64: sdk::base64_encode(&update.queue.data),
+        }
+    }
+
+    pub fn delivery_id(&self) -> String {
+        format!("{}:{}", self.queue, self.seq)
+    }
+}
+
+/// A delivery that failed for good, as written to the dead letter file.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeadLetter {
+    pub url: String,
+    pub delivery: String,
+    pub attempts: u32,
+    pub error: String,
+    pub failed_at: String,
+    pub event: QueueEvent,
+}
+
+/// Why one POST failed, and whether trying again may help.
+struct PostError {
+    retry: bool,
+    message: String,
+}
+
+/// POSTs queue events to the configured URLs.
+pub struct Webhook {
+    config: WebhookConfig,
+    client: reqwest::blocking::Client,
+}
+
+impl Webhook {
+    pub fn new(config: WebhookConfig) -> Result<Self> {
+        if config.urls.is_empty() {
+            return Err(DdMonitorError::config("no webhook urls configured"));
+        }
+        let client = reqwest::blocking::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()
+            .map_err(DdMonitorError::config)?;
+        Ok(Self { config, client })
+    }
+
+    /// POST `event` to every URL, retrying with backoff. Deliveries still
+    /// failing are dead lettered. Returns how many URLs took the event, an
+    /// error means the event is neither delivered nor dead lettered.
+    pub fn deliver(&self, event: &QueueEvent) -> Result<usize> {
+        let body = serde_json::to_vec(event).map_err(DdMonitorError::decode)?;
+        let mut delivered = 0;
+        for url in &self.config.urls {
+            match self.deliver_to(url, event, &body) {
+                Ok(()) => delivered += 1,
+                Err((attempts, e)) => {
+                    error!(
+                        "deliver {} to {} failed after {} attempts : {}",
+                        event.delivery_id(),
+                        url,
+                        attempts,
+                        e
+                    );
+                    self.dead_letter(url, event, attempts, e)?;
+                }
+            }
+        }
+        Ok(delivered)
+    }
+
+    fn deliver_to(
+        &self,
+        url: &str,
+        event: &QueueEvent,
+        body: &[u8],
+    ) -> std::result::Result<(), (u32, String)> {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            match self.post(url, &event.delivery_id(), body) {
+                Ok(()) => {
+                    info!("delivered {} to {}", event.delivery_id(), url);
+                    return Ok(());
+                }
+                Err(e) if !e.retry || attempt > self.config.retries => {
+                    return Err((attempt, e.message));
+                }
+                Err(e) => {
+                    let delay = self.config.backoff(attempt);
+                    warn!(
+                        "deliver {} to {} error , retry in {:?} : {}",
+                        event.delivery_id(),
+                        url,
+                        delay,
+                        e.message
+                    );
+                    thread::sleep(delay);
+                }
+            }
+        }
+    }
+
+    fn post(&self, url: &str, delivery: &str, body: &[u8]) -> std::result::Result<(), PostError> {
+        let timestamp = Utc::now().timestamp();
+        let mut request = self
+            .client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(DELIVERY_HEADER, delivery)
+            .header(TIMESTAMP_HEADER, timestamp.to_string())
+            .body(body.to_vec());
+        if let Some(secret) = &self.config.secret {
+            request = request.header(
+                SIGNATURE_HEADER,
+                format!("sha256={}", sign(secret, timestamp, body)),
+            );
+        }
+        let response = request.send().map_err(|e| PostError {
+            retry: true,
+            message: e.to_string(),
+        })?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        Err(PostError {
+            // other client errors will fail the same way again
+            retry: status.is_server_error()
+                || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                || status == reqwest::StatusCode::REQUEST_TIMEOUT,
+            message: format!("status {}", status),
+        })
+    }
+
+    fn dead_letter(
+        &self,
+        url: &str,
+        event: &QueueEvent,
+        attempts: u32,
+        error: String,
+    ) -> Result<()> {
+        let path = self.config.dead_letter_path();
+        let letter = DeadLetter {
+            url: url.to_string(),
+            delivery: event.delivery_id(),
+            attempts,
+            error,
+            failed_at: Utc::now().to_rfc3339(),
+            event: event.clone(),
+        };
+        let mut line = serde_json::to_vec(&letter).map_err(DdMonitorError::decode)?;
+        line.push(b'\n');
+        runtime::append_private(&path, &line)?;
+        warn!("dead lettered {} to {}", letter.delivery, path.display());
+        Ok(())
+    }
+}
+
+/// One update for the worker, with where to report the delivery when the
+/// forwarder waits for it.
+type Delivery = (QueueEvent, Option<mpsc::Sender<Result<usize>>>);
+
+/// A `QueueHandler` forwarding every update of one queue to the webhooks.
+/// Deliveries run in order on a worker thread, so a slow endpoint does not
+/// hold up the subscription, unless the forwarder waits for each of them.
+pub struct WebhookForwarder {
+    queue: String,
+    queue_pub: Pubkey,
+    events: Option<mpsc::Sender<Delivery>>,
+    worker: Option<JoinHandle<()>>,
+    /// Set once the shutdown timeout passed, the worker then drops what is
+    /// left.
+    abandoned: Arc<AtomicBool>,
+    shutdown_timeout: Duration,
+    wait: bool,
+}
+
+impl WebhookForwarder {
+    pub fn new(config: WebhookConfig, program_account: Pubkey, queue: &str) -> Result<Self> {
+        info!("forward updates of {} to : {:?}", queue, config.urls);
+        let (events, received) = mpsc::channel::<Delivery>();
+        let (ready, started) = mpsc::channel();
+        let shutdown_timeout = Duration::from_secs(config.shutdown_timeout_secs);
+        let abandoned = Arc::new(AtomicBool::new(false));
+        let stopping = abandoned.clone();
+        // the blocking client must not be built or dropped on a runtime thread
+        let worker = thread::spawn(move || {
+            let webhook = match Webhook::new(config) {
+                Ok(webhook) => webhook,
+                Err(e) => {
+                    let _ = ready.send(Err(e));
+                    return;
+                }
+            };
+            let _ = ready.send(Ok(()));
+            for (event, done) in received {
+                if stopping.load(Ordering::Relaxed) {
+                    warn!("update {} not delivered at shutdown", event.delivery_id());
+                    continue;
+                }
+                let result = webhook.deliver(&event);
+                if let Err(e) = &result {
+                    error!("update {} lost : {}", event.delivery_id(), e);
+                }
+                if let Some(done) = done {
+                    let _ = done.send(result);
+                }
+            }
+        });
+        started
+            .recv()
+            .map_err(|_| DdMonitorError::config("webhook worker stopped"))??;
+        Ok(Self {
+            queue: queue.to_string(),
+            queue_pub: sdk::pda_queue_account(&program_account, queue),
+            events: Some(events),
+            worker: Some(worker),
+            abandoned,
+            shutdown_timeout,
+            wait: false,
+        })
+    }
+
+    /// Make `forward` return only once the update was delivered or dead
+    /// lettered, so a consumer acks it after that.
+    pub fn wait_for_delivery(mut self) -> Self {
+        self.wait = true;
+        self
+    }
+
+    /// Hand `update` to the worker, and wait for its delivery when asked to.
+    pub fn forward(&mut self, update: &QueueUpdate) -> Result<()> {
+        let event = QueueEvent::new(&self.queue, &self.queue_pub, update);
+        let (done, delivered) = if self.wait {
+            let (done, delivered) = mpsc::channel();
+            (Some(done), Some(delivered))
+        } else {
+            (None, None)
+        };
+        let stopped = || DdMonitorError::config("webhook worker stopped");
+        self.events
+            .as_ref()
+            .ok_or_else(stopped)?
+            .send((event, done))
+            .map_err(|_| stopped())?;
+        if let Some(delivered) = delivered {
+            delivered.recv().map_err(|_| stopped())??;
+        }
+        Ok(())
+    }
+}
+
+impl QueueHandler for WebhookForwarder {
+    fn handle(&mut self, update: &QueueUpdate) {
+        if let Err(e) = self.forward(update) {
+            error!("forward update {} error : {}", update.seq(), e);
+        }
+    }
+}
+
+impl Drop for WebhookForwarder {
+    /// Let the worker finish the deliveries already queued, for at most
+    /// the shutdown timeout.
+    fn drop(&mut self) {
+        drop(self.events.take());
+        let Some(worker) = self.worker.take() else {
+            return;
+        };
+        let deadline = Instant::now() + self.shutdown_timeout;
+        while !worker.is_finished() {
+            if Instant::now() >= deadline {
+                warn!(
+                    "webhook deliveries of {} still running after {:?} , stop waiting",
+                    self.queue, self.shutdown_timeout
+                );
+                self.abandoned.store(true, Ordering::Relaxed);
+                return;
+            }
+            thread::sleep(SHUTDOWN_POLL_INTERVAL);
+        }
+        let _ = worker.join();
+    }
+}
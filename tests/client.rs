@@ -0,0 +1,249 @@
+use {
+    contract::models,
+    ddmonitor::{client::DdMonitorClient, sdk, sdk::Network},
+    futures::StreamExt,
+    serde_json::{json, Value},
+    solana_program::pubkey::Pubkey,
+    solana_sdk::signer::keypair::Keypair,
+    std::{
+        collections::HashMap,
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            mpsc, Arc, Mutex,
+        },
+        thread,
+        time::{Duration, Instant},
+    },
+    tungstenite::Message,
+};
+
+const DATA_SIZE: usize = 8;
+
+/// Queue states chained from genesis, one per push of `sender`.
+fn chain(owner: &Pubkey, sender: &Pubkey, count: u64) -> Vec<models::Queue> {
+    let mut hash = [0; 32];
+    (1..=count)
+        .map(|seq| {
+            let data = vec![seq as u8; DATA_SIZE];
+            hash = models::Queue::chain_hash(&hash, sender, seq, &data);
+            models::Queue {
+                creator: *owner,
+                allow: vec![*sender],
+                data,
+                need_data_size: DATA_SIZE,
+                created_at: 0,
+                last_change: 0,
+                seq,
+                hook: Pubkey::default(),
+                hash,
+                content_type: models::ContentType::Raw,
+            }
+        })
+        .collect()
+}
+
+fn account_json(owner: &Pubkey, queue: &models::Queue) -> Value {
+    let data = queue.pack();
+    json!({
+        "lamports": 1_000_000,
+        "data": [sdk::base64_encode(&data), "base64"],
+        "owner": owner.to_string(),
+        "executable": false,
+        "rentEpoch": 0,
+        "space": data.len(),
+    })
+}
+
+/// The queue account served by the stand-in rpc, and how often it was read.
+struct Account {
+    owner: Pubkey,
+    queue: Mutex<models::Queue>,
+    reads: AtomicUsize,
+}
+
+impl Account {
+    fn wait_for_reads(&self, reads: usize) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while self.reads.load(Ordering::SeqCst) < reads {
+            assert!(Instant::now() < deadline, "queue account was not read");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+/// A local JSON-RPC endpoint answering `getAccountInfo` with `account`, and
+/// the version query the client makes first.
+fn stand_in_rpc(account: Arc<Account>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut reader = BufReader::new(stream.unwrap());
+            let mut headers = HashMap::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let line = line.trim_end();
+                if line.is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    headers.insert(name.to_lowercase(), value.trim().to_string());
+                }
+            }
+            let length = headers["content-length"].parse().unwrap();
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            let request: Value = serde_json::from_slice(&body).unwrap();
+            let result = match request["method"].as_str().unwrap() {
+                "getVersion" => json!({ "solana-core": "1.16.27", "feature-set": 0 }),
+                "getAccountInfo" => {
+                    let queue = account.queue.lock().unwrap().clone();
+                    account.reads.fetch_add(1, Ordering::SeqCst);
+                    json!({
+                        "context": { "slot": queue.seq * 10 },
+                        "value": account_json(&account.owner, &queue),
+                    })
+                }
+                method => panic!("unexpected rpc method {}", method),
+            };
+            let response =
+                json!({ "jsonrpc": "2.0", "id": request["id"], "result": result }).to_string();
+            write!(
+                reader.get_mut(),
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                response.len(),
+                response
+            )
+            .unwrap();
+        }
+    });
+    url
+}
+
+/// One subscription served by the stand-in pubsub : it waits until the
+/// account was read `reads` times, sends `notifications`, then serves
+/// `next` from the rpc and closes, or keeps the socket open when `next` is
+/// `None` until the client leaves.
+struct Session {
+    reads: usize,
+    notifications: Vec<models::Queue>,
+    next: Option<models::Queue>,
+}
+
+/// A local pubsub endpoint serving `sessions` in order, it reports once a
+/// client left the last one.
+fn stand_in_pubsub(account: Arc<Account>, sessions: Vec<Session>) -> (String, mpsc::Receiver<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    let (left_sender, left_receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for (sid, session) in (1..).zip(sessions) {
+            let (stream, _) = listener.accept().unwrap();
+            let mut ws = tungstenite::accept(stream).unwrap();
+            let request: Value =
+                serde_json::from_str(ws.read_message().unwrap().to_text().unwrap()).unwrap();
+            assert_eq!(request["method"], "accountSubscribe");
+            let reply = json!({ "jsonrpc": "2.0", "result": sid, "id": request["id"] });
+            ws.write_message(Message::Text(reply.to_string())).unwrap();
+
+            account.wait_for_reads(session.reads);
+            for queue in &session.notifications {
+                let notification = json!({
+                    "jsonrpc": "2.0",
+                    "method": "accountNotification",
+                    "params": {
+                        "result": {
+                            "context": { "slot": queue.seq * 10 },
+                            "value": account_json(&account.owner, queue),
+                        },
+                        "subscription": sid,
+                    },
+                });
+                ws.write_message(Message::Text(notification.to_string()))
+                    .unwrap();
+            }
+            match session.next {
+                Some(next) => {
+                    *account.queue.lock().unwrap() = next;
+                    ws.close(None).unwrap();
+                    while ws.read_message().is_ok() {}
+                }
+                None => {
+                    while let Ok(message) = ws.read_message() {
+                        let Ok(text) = message.to_text() else {
+                            continue;
+                        };
+                        let Ok(request) = serde_json::from_str::<Value>(text) else {
+                            continue;
+                        };
+                        if request["method"] == "accountUnsubscribe" {
+                            let reply =
+                                json!({ "jsonrpc": "2.0", "result": true, "id": request["id"] });
+                            let _ = ws.write_message(Message::Text(reply.to_string()));
+                        }
+                    }
+                    left_sender.send(()).unwrap();
+                }
+            }
+        }
+    });
+    (url, left_receiver)
+}
+
+#[tokio::test]
+async fn streams_updates_in_order_across_reconnects() {
+    let program_id = Pubkey::new_unique();
+    let sender = Pubkey::new_unique();
+    let states = chain(&program_id, &sender, 4);
+    let account = Arc::new(Account {
+        owner: program_id,
+        queue: Mutex::new(states[0].clone()),
+        reads: AtomicUsize::new(0),
+    });
+    let rpc_url = stand_in_rpc(account.clone());
+    let (ws_url, left) = stand_in_pubsub(
+        account.clone(),
+        vec![
+            // read by `updates` and by the first subscription
+            Session {
+                reads: 2,
+                notifications: vec![states[1].clone(), states[1].clone(), states[2].clone()],
+                next: Some(states[3].clone()),
+            },
+            // the reconnect reads seq 4 and delivers it
+            Session {
+                reads: 3,
+                notifications: vec![],
+                next: None,
+            },
+        ],
+    );
+
+    let network = Network::custom(&rpc_url, &ws_url).unwrap();
+    let client = DdMonitorClient::new(network, program_id, Keypair::new());
+    let mut updates = client.updates("sensors").await.unwrap();
+
+    let mut received = vec![];
+    while received.len() < 3 {
+        let update = tokio::time::timeout(Duration::from_secs(10), updates.next())
+            .await
+            .expect("no queue update in time")
+            .expect("stream ended");
+        received.push(update);
+    }
+    let seqs: Vec<u64> = received.iter().map(|update| update.seq()).collect();
+    assert_eq!(seqs, vec![2, 3, 4]);
+    assert!(received
+        .iter()
+        .all(|update| update.sender == Some(sender) && !update.backfilled));
+
+    // dropping the stream closes the subscription
+    drop(updates);
+    tokio::task::spawn_blocking(move || left.recv_timeout(Duration::from_secs(10)))
+        .await
+        .unwrap()
+        .expect("subscription still open after the stream was dropped");
+}
@@ -0,0 +1,163 @@
+use {
+    ddmonitor::{
+        config::{self, CommonArgs, ConfigFile, Profile},
+        error::DdMonitorError,
+        fees::Setting,
+        sdk::Network,
+    },
+    solana_sdk::commitment_config::CommitmentLevel,
+    std::{
+        fs,
+        path::{Path, PathBuf},
+    },
+};
+
+const CONFIG: &str = r#"
+profile = "dev"
+
+[profiles.dev]
+program = "CkJ4NC4KCQfoXvyYj9Xxs4LkGDi34zNzE1e2EEeq1h9x"
+
+[send]
+max_retries = 7
+commitment = "finalized"
+compute_unit_price = "auto"
+
+[funding]
+fee_count = 20
+"#;
+
+fn config_path(test: &str, text: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "ddmonitor-config-{}-{}.toml",
+        test,
+        std::process::id()
+    ));
+    fs::write(&path, text).unwrap();
+    path
+}
+
+fn args(path: &Path) -> CommonArgs {
+    CommonArgs {
+        config: Some(path.display().to_string()),
+        ..CommonArgs::default()
+    }
+}
+
+#[test]
+fn parses_the_send_and_funding_tables() {
+    let file = ConfigFile::parse(CONFIG).unwrap();
+    assert_eq!(file.profile.as_deref(), Some("dev"));
+    assert_eq!(file.send.max_retries, Some(7));
+    assert_eq!(file.send.commitment, Some(CommitmentLevel::Finalized));
+    assert_eq!(file.send.compute_unit_price, Some(Setting::Auto));
+    assert_eq!(file.send.compute_unit_limit, None);
+    assert_eq!(file.funding.fee_count, Some(20));
+    assert_eq!(file.funding.airdrop_retries, None);
+
+    let file = ConfigFile::parse("[send]\ncompute_unit_limit = 200000\n").unwrap();
+    assert_eq!(file.send.compute_unit_limit, Some(Setting::Fixed(200_000)));
+}
+
+#[test]
+fn rejects_unknown_fields() {
+    for text in [
+        "profil = \"dev\"\n",
+        "[profiles.dev]\nnetwork = \"dev\"\nwallet = \"id.json\"\n",
+        "[send]\nretries = 3\n",
+        "[funding]\nfees = 3\n",
+    ] {
+        assert!(
+            matches!(ConfigFile::parse(text), Err(DdMonitorError::Config(_))),
+            "{}",
+            text
+        );
+    }
+}
+
+#[test]
+fn flags_override_the_config_file() {
+    let path = config_path("layers", CONFIG);
+    let mut args = args(&path);
+    args.network = Some("dev".to_string());
+    args.send.max_retries = Some(1);
+    args.funding.fee_count = Some(5);
+    let settings = config::load(&args).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(settings.profile, "dev");
+    assert_eq!(settings.network, Network::Dev);
+    assert_eq!(
+        settings.program_id,
+        "CkJ4NC4KCQfoXvyYj9Xxs4LkGDi34zNzE1e2EEeq1h9x"
+    );
+    assert_eq!(settings.send.max_retries, 1);
+    assert_eq!(settings.send.commitment, CommitmentLevel::Finalized);
+    assert_eq!(
+        settings.send.compute_budget.compute_unit_price,
+        Some(Setting::Auto)
+    );
+    // unset everywhere, the default
+    assert_eq!(settings.send.poll_interval_ms, 500);
+    assert_eq!(settings.funding.fee_count, 5);
+    assert_eq!(settings.funding.airdrop_retries, 3);
+}
+
+#[test]
+fn an_unknown_explicit_profile_is_not_found() {
+    let path = config_path("profile", CONFIG);
+    let mut args = args(&path);
+    args.profile = Some("missing".to_string());
+    let result = config::load(&args);
+    fs::remove_file(&path).unwrap();
+    assert!(matches!(result, Err(DdMonitorError::NotFound(_))));
+}
+
+#[test]
+fn profile_network_uses_the_ws_url_when_given() {
+    let profile = Profile {
+        network: Some("https://rpc.example.com/key".to_string()),
+        ws_url: Some("wss://ws.example.com/key".to_string()),
+        ..Profile::default()
+    };
+    let network = config::profile_network("private", &profile)
+        .unwrap()
+        .unwrap();
+    assert_eq!(network.get_rpc_url(), "https://rpc.example.com/key");
+    assert_eq!(network.get_ws_url(), "wss://ws.example.com/key");
+
+    let profile = Profile {
+        network: Some("https://rpc.example.com/key".to_string()),
+        ..Profile::default()
+    };
+    let network = config::profile_network("private", &profile)
+        .unwrap()
+        .unwrap();
+    assert_eq!(network.get_ws_url(), "wss://rpc.example.com/key");
+
+    let profile = Profile {
+        network: Some("main-beta".to_string()),
+        ..Profile::default()
+    };
+    assert_eq!(
+        config::profile_network("private", &profile).unwrap(),
+        Some(Network::MainBeta)
+    );
+}
+
+#[test]
+fn profile_network_falls_back_to_the_profile_name() {
+    let profile = Profile::default();
+    assert_eq!(
+        config::profile_network("dev", &profile).unwrap(),
+        Some(Network::Dev)
+    );
+    assert_eq!(config::profile_network("private", &profile).unwrap(), None);
+
+    let bad = Profile {
+        network: Some("https://rpc.example.com".to_string()),
+        ws_url: Some("https://ws.example.com".to_string()),
+        ..Profile::default()
+    };
+    assert!(config::profile_network("private", &bad).is_err());
+}
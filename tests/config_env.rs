@@ -0,0 +1,73 @@
+use {
+    ddmonitor::config::{self, CommonArgs},
+    solana_sdk::commitment_config::CommitmentLevel,
+    std::{env, fs},
+};
+
+// The environment is shared by the whole process, so every case that sets
+// it runs in this single test.
+#[test]
+fn flags_override_the_environment_over_the_profile() {
+    let path = env::temp_dir().join(format!("ddmonitor-config-env-{}.toml", std::process::id()));
+    fs::write(
+        &path,
+        r#"
+[profiles.default]
+network = "dev"
+program = "CkJ4NC4KCQfoXvyYj9Xxs4LkGDi34zNzE1e2EEeq1h9x"
+
+[send]
+max_retries = 7
+commitment = "finalized"
+poll_interval_ms = 100
+
+[funding]
+fee_count = 20
+airdrop_retries = 4
+"#,
+    )
+    .unwrap();
+    let env_program = "11111111111111111111111111111111";
+    let flag_program = "SysvarC1ock11111111111111111111111111111111";
+    env::set_var(config::PROGRAM_ENV, env_program);
+    env::set_var("DDMONITOR_MAX_RETRIES", "5");
+    env::set_var("DDMONITOR_COMMITMENT", "processed");
+    env::set_var("DDMONITOR_FEE_COUNT", "15");
+
+    let mut args = CommonArgs {
+        config: Some(path.display().to_string()),
+        ..CommonArgs::default()
+    };
+    let settings = config::load(&args).unwrap();
+    assert_eq!(settings.profile, config::DEFAULT_PROFILE);
+    assert_eq!(settings.program_id, env_program);
+    assert_eq!(settings.send.max_retries, 5);
+    assert_eq!(settings.send.commitment, CommitmentLevel::Processed);
+    assert_eq!(settings.send.poll_interval_ms, 100);
+    assert_eq!(settings.funding.fee_count, 15);
+    assert_eq!(settings.funding.airdrop_retries, 4);
+
+    args.program = Some(flag_program.to_string());
+    args.send.max_retries = Some(1);
+    args.funding.fee_count = Some(2);
+    let settings = config::load(&args).unwrap();
+    assert_eq!(settings.program_id, flag_program);
+    assert_eq!(settings.send.max_retries, 1);
+    assert_eq!(settings.send.commitment, CommitmentLevel::Processed);
+    assert_eq!(settings.funding.fee_count, 2);
+
+    env::remove_var(config::PROGRAM_ENV);
+    env::remove_var("DDMONITOR_MAX_RETRIES");
+    env::remove_var("DDMONITOR_COMMITMENT");
+    let settings = config::load(&args).unwrap();
+    assert_eq!(settings.send.commitment, CommitmentLevel::Finalized);
+
+    env::set_var("DDMONITOR_FEE_COUNT", "many");
+    assert!(config::load(&CommonArgs {
+        config: args.config.clone(),
+        ..CommonArgs::default()
+    })
+    .is_err());
+    env::remove_var("DDMONITOR_FEE_COUNT");
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,110 @@
+use {
+    contract::{entrypoint, models},
+    ddmonitor::{
+        consumer::Consumer,
+        error::DdMonitorError,
+        sdk::{self, QueueUpdate},
+        sender::SendConfig,
+    },
+    solana_client::rpc_client::RpcClient,
+    solana_program::pubkey::Pubkey,
+    solana_sdk::signer::keypair::Keypair,
+    std::cell::Cell,
+};
+
+fn consumer() -> Consumer {
+    let program_id = entrypoint::ID;
+    Consumer {
+        name: "orders".to_string(),
+        group: "workers".to_string(),
+        program_id: program_id.to_string(),
+        queue_pub: sdk::pda_queue_account(&program_id, "orders"),
+        cursor_pub: Pubkey::new_unique(),
+        acked_seq: 1,
+        acked_hash: Some([1; 32]),
+        send_config: SendConfig {
+            max_retries: 0,
+            poll_interval_ms: 0,
+            ..SendConfig::default()
+        },
+    }
+}
+
+fn update(seq: u64) -> QueueUpdate {
+    QueueUpdate {
+        slot: seq,
+        queue: models::Queue {
+            creator: Pubkey::new_unique(),
+            allow: vec![],
+            data: vec![seq as u8],
+            need_data_size: 1,
+            created_at: 0,
+            last_change: 0,
+            seq,
+            hook: Pubkey::default(),
+            hash: [seq as u8; 32],
+            content_type: models::ContentType::Raw,
+        },
+        backfilled: false,
+        sender: None,
+    }
+}
+
+#[test]
+fn deliver_acks_a_handled_message_with_its_hash() {
+    let connection = RpcClient::new_mock("succeeds".to_string());
+    let mut consumer = consumer();
+    consumer
+        .deliver(
+            &connection,
+            &Keypair::new(),
+            &update(2),
+            |_: &QueueUpdate| Ok(()),
+        )
+        .unwrap();
+    assert_eq!(
+        (consumer.acked_seq, consumer.acked_hash),
+        (2, Some([2; 32]))
+    );
+}
+
+#[test]
+fn deliver_skips_acked_messages() {
+    let connection = RpcClient::new_mock("succeeds".to_string());
+    let mut consumer = consumer();
+    let calls = Cell::new(0);
+    consumer
+        .deliver(
+            &connection,
+            &Keypair::new(),
+            &update(1),
+            |_: &QueueUpdate| {
+                calls.set(calls.get() + 1);
+                Ok(())
+            },
+        )
+        .unwrap();
+    assert_eq!(calls.get(), 0);
+}
+
+#[test]
+fn deliver_retries_then_returns_the_error_unacked() {
+    let connection = RpcClient::new_mock("succeeds".to_string());
+    let mut consumer = consumer();
+    let calls = Cell::new(0);
+    let result = consumer.deliver(
+        &connection,
+        &Keypair::new(),
+        &update(2),
+        |_: &QueueUpdate| {
+            calls.set(calls.get() + 1);
+            Err(DdMonitorError::NotFound("handler".to_string()))
+        },
+    );
+    assert!(matches!(result, Err(DdMonitorError::NotFound(_))));
+    assert_eq!(calls.get(), 4);
+    assert_eq!(
+        (consumer.acked_seq, consumer.acked_hash),
+        (1, Some([1; 32]))
+    );
+}
@@ -0,0 +1,38 @@
+use {
+    contract::errors::ContractError,
+    ddmonitor::error::DdMonitorError,
+    solana_program::{instruction::InstructionError, pubkey::Pubkey},
+    solana_sdk::transaction::TransactionError,
+};
+
+fn failed(index: u8, code: u32) -> DdMonitorError {
+    TransactionError::InstructionError(index, InstructionError::Custom(code)).into()
+}
+
+fn contract(error: DdMonitorError) -> Option<ContractError> {
+    match error {
+        DdMonitorError::Program { contract, .. } => contract,
+        other => panic!("unexpected error {}", other),
+    }
+}
+
+#[test]
+fn custom_codes_map_only_for_ddmonitor_instructions() {
+    let program_id = Pubkey::new_unique();
+    let budget = Pubkey::new_unique();
+    let programs = [budget, program_id];
+
+    assert_eq!(
+        contract(failed(1, 0).for_program(&programs, &program_id)),
+        Some(ContractError::SenderNotAllowed)
+    );
+    assert_eq!(
+        contract(failed(0, 0).for_program(&programs, &program_id)),
+        None
+    );
+    assert_eq!(
+        contract(failed(2, 0).for_program(&programs, &program_id)),
+        None
+    );
+    assert_eq!(contract(failed(1, 0)), None);
+}
@@ -0,0 +1,301 @@
+use {
+    contract::models,
+    ddmonitor::{
+        actions::{ActionContext, ActionHandler},
+        exec::{ExecHandler, ExecSettings, ExecState},
+        handlers::ActionInfo,
+        sdk::QueueUpdate,
+    },
+    solana_program::pubkey::Pubkey,
+    std::{fs, path::PathBuf, time::Duration},
+};
+
+const COMMANDS: &str = r#"
+max_output_bytes = 8
+
+[commands.echo]
+program = "/bin/echo"
+max_args = 2
+
+[commands.df]
+program = "/bin/df"
+max_args = 1
+allowed_args = ["/", "/var"]
+
+[commands.sleep]
+program = "/bin/sleep"
+args = ["5"]
+timeout_secs = 1
+"#;
+
+fn state_path(test: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "ddmonitor-exec-{}-{}.json",
+        test,
+        std::process::id()
+    ));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+fn handler(test: &str, extra: &str) -> (ExecHandler, PathBuf) {
+    let path = state_path(test);
+    let text = format!(
+        "state_file = {:?}\n{}\n{}",
+        path.to_string_lossy(),
+        extra,
+        COMMANDS
+    );
+    let settings: ExecSettings = toml::from_str(&text).unwrap();
+    (ExecHandler::new(settings).unwrap(), path)
+}
+
+fn update(seq: u64, backfilled: bool) -> QueueUpdate {
+    QueueUpdate {
+        slot: seq,
+        queue: models::Queue {
+            creator: Pubkey::new_unique(),
+            allow: vec![],
+            data: vec![],
+            need_data_size: 0,
+            created_at: 0,
+            last_change: 0,
+            seq,
+            hook: Pubkey::default(),
+            hash: [0; 32],
+            content_type: models::ContentType::Action,
+        },
+        backfilled,
+        sender: None,
+    }
+}
+
+/// Address of the queue named "box" in these tests.
+const BOX: Pubkey = Pubkey::new_from_array([7; 32]);
+
+fn context(update: &QueueUpdate) -> ActionContext<'_> {
+    ActionContext {
+        queue: "box",
+        address: &BOX,
+        update,
+    }
+}
+
+fn echo(args: &[&str]) -> ActionInfo {
+    ActionInfo::Exec {
+        command_id: "echo".to_string(),
+        args: args.iter().map(|arg| arg.to_string()).collect(),
+    }
+}
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+#[test]
+fn rejects_unsafe_command_lines() {
+    let (handler, _path) = handler("reject", "");
+    assert!(handler.command_line("rm", &[]).is_err());
+    assert!(handler
+        .command_line("echo", &args(&["a", "b", "c"]))
+        .is_err());
+    for arg in [
+        "-rf",
+        "/etc/passwd",
+        "../secret",
+        "a/../../b",
+        "$(id)",
+        "a b",
+        "",
+    ] {
+        assert!(
+            handler.command_line("echo", &args(&[arg])).is_err(),
+            "{:?} accepted",
+            arg
+        );
+    }
+    let (program, line) = handler
+        .command_line("echo", &args(&["hello", "dir/file.txt"]))
+        .unwrap();
+    assert_eq!(program, PathBuf::from("/bin/echo"));
+    assert_eq!(line, args(&["hello", "dir/file.txt"]));
+
+    // allowed_args is the only way to pass absolute paths
+    assert!(handler.command_line("df", &args(&["/"])).is_ok());
+    assert!(handler.command_line("df", &args(&["/etc"])).is_err());
+}
+
+#[test]
+fn kills_commands_at_their_timeout() {
+    let (handler, _path) = handler("timeout", "");
+    let result = handler.run("sleep", &[]).unwrap();
+    assert!(result.timed_out);
+    assert!(!result.success());
+    assert_eq!(result.signal, Some(9));
+    assert!(Duration::from_millis(result.duration_ms) < Duration::from_secs(4));
+}
+
+#[test]
+fn truncates_output() {
+    let (handler, _path) = handler("truncate", "");
+    let result = handler.run("echo", &args(&["0123456789abcdef"])).unwrap();
+    assert!(result.success());
+    assert_eq!(result.stdout, "01234567");
+    assert!(result.truncated);
+
+    let result = handler.run("echo", &args(&["short"])).unwrap();
+    assert_eq!(result.stdout, "short\n");
+    assert!(!result.truncated);
+}
+
+#[test]
+fn skips_backfilled_updates() {
+    let backfilled = update(3, true);
+    let context = context(&backfilled);
+    let (mut skipping, path) = handler("backfilled", "");
+    let outcome = skipping.handle(&echo(&["hi"]), &context).unwrap();
+    assert_eq!(outcome.message, "skipped backfilled command");
+    assert_eq!(skipping.last_run(&BOX), None);
+    assert!(!path.exists());
+
+    let (mut running, path) = handler("run-backfilled", "run_backfilled = true");
+    let outcome = running.handle(&echo(&["hi"]), &context).unwrap();
+    assert_eq!(outcome.message, "echo exited with 0");
+    assert_eq!(running.last_run(&BOX).map(|run| run.seq), Some(3));
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn remembers_run_sequences_across_restarts() {
+    let (mut handler, path) = handler("restart", "");
+    let live = update(4, false);
+    let context = context(&live);
+    assert_eq!(
+        handler.handle(&echo(&["once"]), &context).unwrap().message,
+        "echo exited with 0"
+    );
+    assert_eq!(handler.last_run(&BOX).map(|run| run.seq), Some(4));
+
+    let settings: ExecSettings = toml::from_str(&format!(
+        "state_file = {:?}\n{}",
+        path.to_string_lossy(),
+        COMMANDS
+    ))
+    .unwrap();
+    let mut restarted = ExecHandler::new(settings).unwrap();
+    assert_eq!(
+        restarted.last_run(&BOX),
+        Some(ExecState {
+            created_at: 0,
+            seq: 4
+        })
+    );
+    assert_eq!(restarted.last_run(&Pubkey::new_unique()), None);
+    assert_eq!(
+        restarted
+            .handle(&echo(&["once"]), &context)
+            .unwrap()
+            .message,
+        "seq 4 already ran"
+    );
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn a_recreated_queue_starts_over() {
+    let (mut handler, path) = handler("recreated", "");
+    let first = update(4, false);
+    handler.handle(&echo(&["first"]), &context(&first)).unwrap();
+
+    // the same name under another program is another queue
+    let other = Pubkey::new_unique();
+    let elsewhere = ActionContext {
+        queue: "box",
+        address: &other,
+        update: &first,
+    };
+    assert_eq!(
+        handler
+            .handle(&echo(&["other"]), &elsewhere)
+            .unwrap()
+            .message,
+        "echo exited with 0"
+    );
+
+    // closed and created again at the same address
+    let mut recreated = update(1, false);
+    recreated.queue.created_at = 10;
+    assert_eq!(
+        handler
+            .handle(&echo(&["again"]), &context(&recreated))
+            .unwrap()
+            .message,
+        "echo exited with 0"
+    );
+    assert_eq!(
+        handler.last_run(&BOX),
+        Some(ExecState {
+            created_at: 10,
+            seq: 1
+        })
+    );
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn adopts_a_legacy_state_of_the_queue_name() {
+    let path = state_path("legacy");
+    fs::write(&path, r#"{ "box": 4 }"#).unwrap();
+    let settings: ExecSettings = toml::from_str(&format!(
+        "state_file = {:?}\n{}",
+        path.to_string_lossy(),
+        COMMANDS
+    ))
+    .unwrap();
+    let mut handler = ExecHandler::new(settings).unwrap();
+    assert_eq!(
+        handler
+            .handle(&echo(&["once"]), &context(&update(4, false)))
+            .unwrap()
+            .message,
+        "seq 4 already ran"
+    );
+    handler
+        .handle(&echo(&["next"]), &context(&update(5, false)))
+        .unwrap();
+    let saved: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(
+        saved,
+        serde_json::json!({ BOX.to_string(): { "created_at": 0, "seq": 5 } })
+    );
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn runs_only_allowed_senders() {
+    let allowed = Pubkey::new_unique();
+    let (mut handler, path) = handler(
+        "senders",
+        &format!("allowed_senders = [{:?}]", allowed.to_string()),
+    );
+    let mut pushed = update(1, false);
+    assert!(handler.handle(&echo(&["hi"]), &context(&pushed)).is_err());
+    pushed.sender = Some(Pubkey::new_unique());
+    assert!(handler.handle(&echo(&["hi"]), &context(&pushed)).is_err());
+    assert_eq!(handler.last_run(&BOX), None);
+
+    pushed.sender = Some(allowed);
+    assert_eq!(
+        handler
+            .handle(&echo(&["hi"]), &context(&pushed))
+            .unwrap()
+            .message,
+        "echo exited with 0"
+    );
+    fs::remove_file(path).unwrap();
+
+    let settings: ExecSettings =
+        toml::from_str(&format!("allowed_senders = [\"nope\"]\n{}", COMMANDS)).unwrap();
+    assert!(ExecHandler::new(settings).is_err());
+}
@@ -0,0 +1,144 @@
+use {
+    contract::errors::ContractError,
+    ddmonitor::{
+        error::DdMonitorError,
+        fees::{self, ComputeBudget, Setting, MAX_COMPUTE_UNIT_LIMIT},
+    },
+    serde_json::json,
+    solana_client::rpc_client::{Mocks, RpcClient},
+    solana_program::{instruction::InstructionError, pubkey::Pubkey},
+    solana_rpc_client_api::request::RpcRequest,
+    solana_sdk::{
+        compute_budget::{self, ComputeBudgetInstruction},
+        instruction::Instruction,
+        signer::{keypair::Keypair, Signer},
+        transaction::TransactionError,
+    },
+};
+
+/// A client whose next simulation consumed `units` or failed with `err`.
+fn simulating(units: u64, err: Option<TransactionError>) -> RpcClient {
+    let mut mocks = Mocks::default();
+    mocks.insert(
+        RpcRequest::SimulateTransaction,
+        json!({
+            "context": { "slot": 1 },
+            "value": {
+                "err": err,
+                "logs": null,
+                "accounts": null,
+                "unitsConsumed": units,
+                "returnData": null,
+            },
+        }),
+    );
+    RpcClient::new_mock_with_mocks("succeeds".to_string(), mocks)
+}
+
+fn budget(limit: Setting<u32>, price: Option<Setting<u64>>) -> ComputeBudget {
+    ComputeBudget {
+        compute_unit_limit: Some(limit),
+        compute_unit_price: price,
+    }
+}
+
+fn call(program_id: &Pubkey) -> Instruction {
+    Instruction::new_with_bytes(*program_id, &[1], vec![])
+}
+
+#[test]
+fn auto_limit_adds_the_margin_up_to_the_maximum() {
+    let program_id = Pubkey::new_unique();
+    let payer = Pubkey::new_unique();
+    for (units, limit) in [(1_000, 1_100), (1_390_000, MAX_COMPUTE_UNIT_LIMIT)] {
+        let budget_instructions = fees::budget_instructions(
+            &simulating(units, None),
+            &payer,
+            &[call(&program_id)],
+            &budget(Setting::Auto, None),
+            &program_id,
+        )
+        .unwrap();
+        assert_eq!(
+            budget_instructions,
+            vec![ComputeBudgetInstruction::set_compute_unit_limit(limit)]
+        );
+    }
+}
+
+#[test]
+fn budget_instructions_put_the_limit_first() {
+    let program_id = Pubkey::new_unique();
+    let budget_instructions = fees::budget_instructions(
+        &RpcClient::new_mock("succeeds".to_string()),
+        &Pubkey::new_unique(),
+        &[call(&program_id)],
+        &budget(Setting::Fixed(5_000), Some(Setting::Fixed(7))),
+        &program_id,
+    )
+    .unwrap();
+    assert_eq!(
+        budget_instructions,
+        vec![
+            ComputeBudgetInstruction::set_compute_unit_limit(5_000),
+            ComputeBudgetInstruction::set_compute_unit_price(7),
+        ]
+    );
+}
+
+#[test]
+fn simulation_lays_out_instructions_as_the_sent_transaction() {
+    let program_id = Pubkey::new_unique();
+    let payer = Keypair::new();
+    let instructions = [call(&program_id)];
+    let transaction = fees::build_transaction(
+        &simulating(1_000, None),
+        &payer.pubkey(),
+        &[&payer],
+        &instructions,
+        &budget(Setting::Auto, Some(Setting::Fixed(7))),
+        &program_id,
+    )
+    .unwrap();
+    let simulated = fees::simulation_transaction(
+        &payer.pubkey(),
+        &[ComputeBudgetInstruction::set_compute_unit_price(7)],
+        &instructions,
+    );
+    let programs = vec![compute_budget::id(), compute_budget::id(), program_id];
+    assert_eq!(fees::transaction_programs(&transaction), programs);
+    assert_eq!(fees::transaction_programs(&simulated), programs);
+}
+
+#[test]
+fn failed_simulation_reads_contract_errors_at_the_sent_index() {
+    let program_id = Pubkey::new_unique();
+    let payer = Pubkey::new_unique();
+    let failed = |index: u8| {
+        let err = TransactionError::InstructionError(index, InstructionError::Custom(0));
+        fees::budget_instructions(
+            &simulating(0, Some(err)),
+            &payer,
+            &[call(&program_id)],
+            &budget(Setting::Auto, Some(Setting::Fixed(7))),
+            &program_id,
+        )
+        .unwrap_err()
+    };
+
+    match failed(2) {
+        DdMonitorError::Program { error, contract } => {
+            assert_eq!(
+                error,
+                TransactionError::InstructionError(2, InstructionError::Custom(0))
+            );
+            assert_eq!(contract, Some(ContractError::SenderNotAllowed));
+        }
+        other => panic!("unexpected error {}", other),
+    }
+    // index 1 is the price instruction, its codes are not ours
+    assert!(matches!(
+        failed(1),
+        DdMonitorError::Program { contract: None, .. }
+    ));
+}
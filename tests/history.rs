@@ -0,0 +1,118 @@
+use {
+    contract::models,
+    ddmonitor::{
+        history::{self, ChainAnchor, PushRecord},
+        sdk::QueueUpdate,
+    },
+    solana_program::pubkey::Pubkey,
+    solana_sdk::signature::Signature,
+};
+
+fn record(slot: u64, sender: Pubkey, data: &[u8]) -> PushRecord {
+    PushRecord {
+        signature: Signature::default(),
+        slot,
+        block_time: None,
+        sender,
+        data: data.to_vec(),
+    }
+}
+
+/// The queue after pushing `records` on top of `anchor`.
+fn current(records: &[PushRecord], anchor: ChainAnchor, slot: u64) -> QueueUpdate {
+    let mut hash = anchor.hash;
+    for (record, seq) in records.iter().zip(anchor.seq + 1..) {
+        hash = models::Queue::chain_hash(&hash, &record.sender, seq, &record.data);
+    }
+    QueueUpdate {
+        slot,
+        queue: models::Queue {
+            creator: Pubkey::new_unique(),
+            allow: vec![],
+            data: records.last().unwrap().data.clone(),
+            need_data_size: 4,
+            created_at: 0,
+            last_change: 0,
+            seq: anchor.seq + records.len() as u64,
+            hook: Pubkey::default(),
+            hash,
+            content_type: models::ContentType::Raw,
+        },
+        backfilled: false,
+        sender: None,
+    }
+}
+
+fn pushes(count: u64) -> Vec<PushRecord> {
+    let sender = Pubkey::new_unique();
+    (1..=count)
+        .map(|slot| record(slot, sender, &[slot as u8; 4]))
+        .collect()
+}
+
+#[test]
+fn rebuilds_states_from_genesis() {
+    let records = pushes(3);
+    let current = current(&records, ChainAnchor::GENESIS, 3);
+    let states = history::queue_states(&records, &current, ChainAnchor::GENESIS).unwrap();
+    let seqs: Vec<u64> = states.iter().map(|update| update.seq()).collect();
+    assert_eq!(seqs, vec![1, 2, 3]);
+    assert_eq!(states[2].queue.hash, current.queue.hash);
+    assert!(states.iter().all(|update| update.backfilled));
+    assert!(states
+        .iter()
+        .zip(&records)
+        .all(|(update, record)| update.sender == Some(record.sender)));
+    assert_eq!(
+        history::verify_chain(&records, &current).unwrap(),
+        current.queue.hash
+    );
+}
+
+#[test]
+fn numbers_records_back_from_the_queue_seq() {
+    // older pushes, from before an anchor, are not rebuilt
+    let records = pushes(5);
+    let anchor = ChainAnchor {
+        seq: 40,
+        hash: [7; 32],
+    };
+    let current = current(&records[2..], anchor, 5);
+    let states = history::queue_states(&records, &current, anchor).unwrap();
+    let seqs: Vec<u64> = states.iter().map(|update| update.seq()).collect();
+    assert_eq!(seqs, vec![41, 42, 43]);
+    assert_eq!(states[0].queue.data, vec![3; 4]);
+}
+
+#[test]
+fn ignores_pushes_after_the_current_slot() {
+    let records = pushes(4);
+    let current = current(&records[..3], ChainAnchor::GENESIS, 3);
+    let states = history::queue_states(&records, &current, ChainAnchor::GENESIS).unwrap();
+    assert_eq!(states.len(), 3);
+}
+
+#[test]
+fn rejects_history_that_misses_the_anchor() {
+    // the first two pushes are no longer in the history
+    let records = pushes(5);
+    let current = current(&records, ChainAnchor::GENESIS, 5);
+    assert!(history::queue_states(&records[2..], &current, ChainAnchor::GENESIS).is_err());
+    assert!(history::verify_chain(&records[2..], &current).is_err());
+}
+
+#[test]
+fn rejects_a_broken_chain() {
+    let records = pushes(3);
+    let current = current(&records, ChainAnchor::GENESIS, 3);
+    let mut reordered = records.clone();
+    reordered.swap(0, 1);
+    reordered[0].slot = 1;
+    reordered[1].slot = 2;
+    assert!(history::queue_states(&reordered, &current, ChainAnchor::GENESIS).is_err());
+    let wrong_anchor = ChainAnchor {
+        seq: 0,
+        hash: [1; 32],
+    };
+    assert!(history::queue_states(&records, &current, wrong_anchor).is_err());
+}
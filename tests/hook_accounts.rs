@@ -0,0 +1,16 @@
+use {ddmonitor::sdk, solana_program::pubkey::Pubkey};
+
+#[test]
+fn hook_accounts_are_read_only_unless_marked() {
+    let address = Pubkey::new_unique();
+    let readonly = sdk::parse_hook_account(&address.to_string()).unwrap();
+    assert_eq!(readonly.pubkey, address);
+    assert!(!readonly.is_writable && !readonly.is_signer);
+
+    let writable = sdk::parse_hook_account(&format!("{}:w", address)).unwrap();
+    assert_eq!(writable.pubkey, address);
+    assert!(writable.is_writable && !writable.is_signer);
+
+    assert!(sdk::parse_hook_account(&format!("{}:s", address)).is_err());
+    assert!(sdk::parse_hook_account("not-an-address").is_err());
+}
@@ -0,0 +1,61 @@
+use {
+    ddmonitor::keystore::Keystore,
+    solana_sdk::signer::{keypair::Keypair, Signer},
+    std::{fs, path::PathBuf},
+};
+
+fn keystore_path(test: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "ddmonitor-keystore-{}-{}.json",
+        test,
+        std::process::id()
+    ));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+#[test]
+fn decrypts_what_it_encrypts() {
+    let keypair = Keypair::new();
+    let keystore = Keystore::encrypt(&keypair, "correct horse").unwrap();
+    assert_eq!(keystore.pubkey, keypair.pubkey().to_string());
+    assert!(!keystore.ciphertext.contains(&keypair.to_base58_string()));
+
+    let unlocked = keystore.decrypt("correct horse").unwrap();
+    assert_eq!(unlocked.to_bytes(), keypair.to_bytes());
+}
+
+#[test]
+fn rejects_a_wrong_password() {
+    let keystore = Keystore::encrypt(&Keypair::new(), "correct horse").unwrap();
+    assert!(keystore.decrypt("battery staple").is_err());
+
+    let mut tampered = keystore.clone();
+    tampered.pubkey = Keypair::new().pubkey().to_string();
+    assert!(tampered.decrypt("correct horse").is_err());
+}
+
+#[test]
+fn writes_a_private_file() {
+    let keypair = Keypair::new();
+    let path = keystore_path("mode");
+    Keystore::encrypt(&keypair, "correct horse")
+        .unwrap()
+        .write(&path)
+        .unwrap();
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+    let text = fs::read_to_string(&path).unwrap();
+    assert!(Keystore::is_keystore(&text));
+    let unlocked = Keystore::read(&path)
+        .unwrap()
+        .decrypt("correct horse")
+        .unwrap();
+    assert_eq!(unlocked.pubkey(), keypair.pubkey());
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,42 @@
+use {
+    borsh::to_vec,
+    contract::models::KvEntry,
+    ddmonitor::{error::DdMonitorError, kv},
+    solana_program::pubkey::Pubkey,
+};
+
+fn entry(key: &str) -> KvEntry {
+    KvEntry {
+        queue: Pubkey::new_unique(),
+        key_hash: KvEntry::key_hash(key),
+        key: key.to_string(),
+        value: b"value".to_vec(),
+        updated_by: Pubkey::new_unique(),
+        updated_at: 1,
+    }
+}
+
+#[test]
+fn keys_must_fit_the_entry() {
+    kv::check_key("k").unwrap();
+    kv::check_key(&"k".repeat(KvEntry::MAX_KEY_LEN)).unwrap();
+    for key in [String::new(), "k".repeat(KvEntry::MAX_KEY_LEN + 1)] {
+        assert!(matches!(
+            kv::check_key(&key),
+            Err(DdMonitorError::Config(_))
+        ));
+    }
+}
+
+#[test]
+fn entries_decode_only_for_their_key() {
+    let data = to_vec(&entry("color")).unwrap();
+    assert_eq!(
+        kv::decode_entry(&data, Some("color")).unwrap().value,
+        b"value"
+    );
+    assert_eq!(kv::decode_entry(&data, None).unwrap().key, "color");
+    assert!(kv::decode_entry(&data, Some("size")).is_none());
+    // a deleted entry has no data
+    assert!(kv::decode_entry(&[], Some("color")).is_none());
+}
@@ -0,0 +1,108 @@
+use ddmonitor::{cluster::SolanaCliConfig, funding::FundingConfig, sdk::Network};
+
+#[test]
+fn parses_cluster_names_and_urls() {
+    for (name, network) in [
+        ("local", Network::Local),
+        ("l", Network::Local),
+        (" devnet ", Network::Dev),
+        ("test", Network::Test),
+        ("mainnet-beta", Network::MainBeta),
+        ("m", Network::MainBeta),
+        ("https://api.devnet.solana.com/", Network::Dev),
+        ("http://localhost:8899", Network::Local),
+    ] {
+        assert_eq!(name.parse::<Network>().unwrap(), network, "{}", name);
+    }
+
+    assert_eq!(
+        "https://rpc.example.com/key , wss://ws.example.com/key"
+            .parse::<Network>()
+            .unwrap(),
+        Network::Custom {
+            rpc: "https://rpc.example.com/key".to_string(),
+            ws: "wss://ws.example.com/key".to_string(),
+        }
+    );
+    for name in [
+        "mainnet",
+        "ftp://rpc.example.com",
+        "https://rpc.example.com,https://ws",
+    ] {
+        assert!(name.parse::<Network>().is_err(), "{}", name);
+    }
+}
+
+#[test]
+fn derives_the_websocket_url_from_the_rpc_url() {
+    for (rpc, ws) in [
+        ("http://rpc.example.com", "ws://rpc.example.com"),
+        ("https://rpc.example.com/key", "wss://rpc.example.com/key"),
+        ("http://127.0.0.1:8899", "ws://127.0.0.1:8900"),
+        ("http://localhost:8899/", "ws://localhost:8900/"),
+        ("http://[::1]", "ws://[::1]"),
+    ] {
+        assert_eq!(Network::websocket_url(rpc).unwrap(), ws, "{}", rpc);
+    }
+    assert!(Network::websocket_url("rpc.example.com").is_err());
+    assert!(Network::websocket_url("http://rpc.example.com:port").is_err());
+}
+
+#[test]
+fn custom_clusters_airdrop_only_when_opted_in() {
+    let custom: Network = "https://rpc.example.com".parse().unwrap();
+    assert!(!custom.airdrop_enable());
+    assert!(!Network::MainBeta.airdrop_enable());
+    assert!(Network::Dev.airdrop_enable());
+
+    let config = FundingConfig::default();
+    assert!(!config.airdrop_allowed(&custom));
+    assert!(config.airdrop_allowed(&Network::Local));
+    let opted_in = FundingConfig {
+        airdrop_custom: true,
+        ..config
+    };
+    assert!(opted_in.airdrop_allowed(&custom));
+    assert!(!opted_in.airdrop_allowed(&Network::MainBeta));
+}
+
+#[test]
+fn parses_the_solana_cli_config() {
+    let config = SolanaCliConfig::parse(
+        r#"---
+json_rpc_url: "https://api.devnet.solana.com"
+websocket_url: ""
+keypair_path: /home/user/.config/solana/id.json
+address_labels:
+  "11111111111111111111111111111111": System Program
+# a comment
+commitment: 'confirmed'
+"#,
+    )
+    .unwrap();
+    assert_eq!(
+        config.json_rpc_url.as_deref(),
+        Some("https://api.devnet.solana.com")
+    );
+    assert_eq!(config.websocket_url, None);
+    assert_eq!(
+        config.keypair_path.as_deref(),
+        Some("/home/user/.config/solana/id.json")
+    );
+    assert_eq!(config.commitment.as_deref(), Some("confirmed"));
+    assert_eq!(config.network().unwrap(), Some(Network::Dev));
+
+    let config = SolanaCliConfig::parse(
+        "json_rpc_url: http://10.0.0.1:8899\nwebsocket_url: ws://10.0.0.1:9000\n",
+    )
+    .unwrap();
+    assert_eq!(
+        config.network().unwrap(),
+        Some(Network::Custom {
+            rpc: "http://10.0.0.1:8899".to_string(),
+            ws: "ws://10.0.0.1:9000".to_string(),
+        })
+    );
+    assert_eq!(SolanaCliConfig::default().network().unwrap(), None);
+    assert!(SolanaCliConfig::parse("json_rpc_url\n").is_err());
+}
@@ -0,0 +1,115 @@
+use {
+    contract::models,
+    ddmonitor::{
+        reply::{Envelope, ResponseMatcher},
+        sdk::{QueueHandler, QueueUpdate},
+    },
+    solana_program::pubkey::Pubkey,
+    std::time::{Duration, Instant},
+};
+
+/// A reply queue update holding `envelope`, zero padded like queue data.
+fn update(seq: u64, envelope: Envelope) -> QueueUpdate {
+    let mut data: Vec<u8> = envelope.into();
+    data.resize(64, 0);
+    QueueUpdate {
+        slot: seq,
+        queue: models::Queue {
+            creator: Pubkey::new_unique(),
+            allow: vec![],
+            data,
+            need_data_size: 64,
+            created_at: 0,
+            last_change: 0,
+            seq,
+            hook: Pubkey::default(),
+            hash: [seq as u8; 32],
+            content_type: models::ContentType::Raw,
+        },
+        backfilled: false,
+        sender: None,
+    }
+}
+
+fn response(correlation_id: u64, body: &[u8]) -> Envelope {
+    Envelope::Response {
+        correlation_id,
+        body: body.to_vec(),
+    }
+}
+
+#[test]
+fn envelopes_round_trip_through_padded_queue_data() {
+    let request = Envelope::Request {
+        correlation_id: 7,
+        reply_to: "replies".to_string(),
+        body: b"ping".to_vec(),
+    };
+    let data = update(1, request).queue.data;
+    match Envelope::decode(&data) {
+        Some(Envelope::Request {
+            correlation_id,
+            reply_to,
+            body,
+        }) => {
+            assert_eq!(correlation_id, 7);
+            assert_eq!(reply_to, "replies");
+            assert_eq!(body, b"ping");
+        }
+        other => panic!("unexpected envelope {:?}", other),
+    }
+
+    let data = update(2, response(8, b"pong")).queue.data;
+    let decoded = Envelope::decode(&data).unwrap();
+    assert_eq!(decoded.correlation_id(), 8);
+    assert!(matches!(decoded, Envelope::Response { body, .. } if body == b"pong"));
+}
+
+#[test]
+fn malformed_data_is_not_an_envelope() {
+    assert!(Envelope::decode(&[]).is_none());
+    assert!(Envelope::decode(&[1, 0]).is_none());
+    // a length past the end of the data
+    assert!(Envelope::decode(&[200, 0, 0, 0, 1, 2]).is_none());
+    assert!(Envelope::decode(&[0; 64]).is_none());
+}
+
+#[test]
+fn matcher_takes_the_first_response_with_its_correlation_id() {
+    let mut found = None;
+    let mut matcher = ResponseMatcher {
+        correlation_id: 42,
+        deadline: Instant::now() + Duration::from_secs(60),
+        response: &mut found,
+    };
+    let updates = [
+        update(
+            1,
+            Envelope::Request {
+                correlation_id: 42,
+                reply_to: "replies".to_string(),
+                body: b"not an answer".to_vec(),
+            },
+        ),
+        update(2, response(41, b"someone else")),
+        update(3, response(42, b"answer")),
+        update(4, response(42, b"late duplicate")),
+    ];
+    for (index, update) in updates.iter().enumerate() {
+        assert_eq!(matcher.is_open(), index < 3);
+        matcher.handle(update);
+    }
+    assert!(!matcher.is_open());
+    assert_eq!(found.as_deref(), Some(&b"answer"[..]));
+}
+
+#[test]
+fn matcher_closes_at_the_deadline() {
+    let mut found = None;
+    let matcher = ResponseMatcher {
+        correlation_id: 42,
+        deadline: Instant::now(),
+        response: &mut found,
+    };
+    assert!(!matcher.is_open());
+}
@@ -0,0 +1,303 @@
+use {
+    contract::models,
+    ddmonitor::{
+        history,
+        sdk::QueueUpdate,
+        webhook::{self, DeadLetter, QueueEvent, Webhook, WebhookConfig, WebhookForwarder},
+    },
+    solana_program::pubkey::Pubkey,
+    std::{
+        collections::HashMap,
+        fs,
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        path::{Path, PathBuf},
+        thread::{self, JoinHandle},
+        time::{Duration, Instant},
+    },
+};
+
+const SECRET: &str = "stand-in secret";
+
+/// One request received by the stand-in, header names lowercased.
+struct Received {
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+/// A local HTTP endpoint answering one request per status in `statuses`.
+fn stand_in(statuses: Vec<u16>) -> (String, JoinHandle<Vec<Received>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    let server = thread::spawn(move || {
+        statuses
+            .into_iter()
+            .map(|status| {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut headers = HashMap::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim_end();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        headers.insert(name.to_lowercase(), value.trim().to_string());
+                    }
+                }
+                let length = headers
+                    .get("content-length")
+                    .map(|value| value.parse().unwrap())
+                    .unwrap_or(0);
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                write!(
+                    reader.get_mut(),
+                    "HTTP/1.1 {} Stand-In\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    status
+                )
+                .unwrap();
+                Received { headers, body }
+            })
+            .collect()
+    });
+    (url, server)
+}
+
+fn dead_letter_path(test: &str) -> PathBuf {
+    let path =
+        std::env::temp_dir().join(format!("ddmonitor-{}-{}.jsonl", test, std::process::id()));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+fn config(urls: Vec<String>, retries: u32, dead_letter: &Path) -> WebhookConfig {
+    WebhookConfig {
+        urls,
+        secret: Some(SECRET.to_string()),
+        retries,
+        backoff_ms: 10,
+        max_backoff_ms: 40,
+        timeout_secs: 5,
+        dead_letter: Some(dead_letter.to_string_lossy().to_string()),
+        shutdown_timeout_secs: 5,
+    }
+}
+
+fn event_queue() -> models::Queue {
+    let mut data = br#"{"temperature":21}"#.to_vec();
+    data.resize(32, 0);
+    models::Queue {
+        creator: Pubkey::new_unique(),
+        allow: vec![],
+        data,
+        need_data_size: 32,
+        created_at: 0,
+        last_change: 1_700_000_000,
+        seq: 7,
+        hook: Pubkey::default(),
+        hash: [0; 32],
+        content_type: models::ContentType::Json,
+    }
+}
+
+fn event() -> QueueEvent {
+    let update = QueueUpdate {
+        slot: 42,
+        queue: event_queue(),
+        backfilled: false,
+        sender: Some(Pubkey::new_unique()),
+    };
+    QueueEvent::new("sensors", &Pubkey::new_unique(), &update)
+}
+
+#[test]
+fn delivers_signed_events() {
+    let (url, server) = stand_in(vec![200]);
+    let path = dead_letter_path("delivers");
+    let event = event();
+    let webhook = Webhook::new(config(vec![url], 3, &path)).unwrap();
+    assert_eq!(webhook.deliver(&event).unwrap(), 1);
+
+    let received = server.join().unwrap();
+    let request = &received[0];
+    let body: QueueEvent = serde_json::from_slice(&request.body).unwrap();
+    assert_eq!(body, event);
+    assert_eq!(body.payload["json"]["temperature"], 21);
+    assert_eq!(
+        request.headers[&webhook::DELIVERY_HEADER.to_lowercase()],
+        "sensors:7"
+    );
+    let timestamp: i64 = request.headers[&webhook::TIMESTAMP_HEADER.to_lowercase()]
+        .parse()
+        .unwrap();
+    let signature = &request.headers[&webhook::SIGNATURE_HEADER.to_lowercase()];
+    assert!(webhook::verify(SECRET, timestamp, &request.body, signature));
+    assert!(!webhook::verify(SECRET, timestamp, b"tampered", signature));
+    assert!(!webhook::verify(
+        "other",
+        timestamp,
+        &request.body,
+        signature
+    ));
+    assert!(!path.exists());
+}
+
+#[test]
+fn retries_server_errors() {
+    let (url, server) = stand_in(vec![503, 500, 200]);
+    let path = dead_letter_path("retries");
+    let webhook = Webhook::new(config(vec![url], 3, &path)).unwrap();
+    assert_eq!(webhook.deliver(&event()).unwrap(), 1);
+
+    let received = server.join().unwrap();
+    assert_eq!(received.len(), 3);
+    assert!(received
+        .iter()
+        .all(|request| request.body == received[0].body));
+    assert!(!path.exists());
+}
+
+#[test]
+fn dead_letters_failed_deliveries() {
+    let (failing, failing_server) = stand_in(vec![500, 502]);
+    let (rejecting, rejecting_server) = stand_in(vec![400]);
+    let path = dead_letter_path("dead-letter");
+    let event = event();
+    let webhook = Webhook::new(config(vec![failing.clone(), rejecting.clone()], 1, &path)).unwrap();
+    assert_eq!(webhook.deliver(&event).unwrap(), 0);
+
+    // client errors are not retried
+    assert_eq!(failing_server.join().unwrap().len(), 2);
+    assert_eq!(rejecting_server.join().unwrap().len(), 1);
+    let letters: Vec<DeadLetter> = fs::read_to_string(&path)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(letters.len(), 2);
+    assert_eq!(
+        (letters[0].url.as_str(), letters[0].attempts),
+        (failing.as_str(), 2)
+    );
+    assert_eq!(
+        (letters[1].url.as_str(), letters[1].attempts),
+        (rejecting.as_str(), 1)
+    );
+    assert!(letters.iter().all(|letter| letter.event == event));
+    assert_eq!(letters[0].delivery, "sensors:7");
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn backoff_doubles_up_to_the_cap() {
+    let config = config(vec![], 5, &dead_letter_path("backoff"));
+    let delays: Vec<u128> = (1..=4)
+        .map(|attempt| config.backoff(attempt).as_millis())
+        .collect();
+    assert_eq!(delays, vec![10, 20, 40, 40]);
+    assert!(Webhook::new(config).is_err());
+}
+
+#[test]
+fn chain_sender_finds_the_pusher() {
+    let sender = Pubkey::new_unique();
+    let other = Pubkey::new_unique();
+    let prev_hash = [3; 32];
+    let pushed = b"ends with zero\0".to_vec();
+    let mut data = pushed.clone();
+    data.resize(24, 0);
+    let queue = models::Queue {
+        creator: other,
+        allow: vec![other, sender, Pubkey::default()],
+        data,
+        need_data_size: 24,
+        created_at: 0,
+        last_change: 0,
+        seq: 5,
+        hook: Pubkey::default(),
+        hash: models::Queue::chain_hash(&prev_hash, &sender, 5, &pushed),
+        content_type: models::ContentType::Raw,
+    };
+    assert_eq!(history::chain_sender(&prev_hash, &queue), Some(sender));
+    assert_eq!(history::chain_sender(&[0; 32], &queue), None);
+}
+
+#[test]
+fn waiting_forwarder_returns_after_delivery() {
+    let (url, server) = stand_in(vec![503, 200]);
+    let path = dead_letter_path("forwarder");
+    let mut forwarder =
+        WebhookForwarder::new(config(vec![url], 3, &path), Pubkey::new_unique(), "sensors")
+            .unwrap()
+            .wait_for_delivery();
+    let update = QueueUpdate {
+        slot: 1,
+        queue: event_queue(),
+        backfilled: false,
+        sender: None,
+    };
+    forwarder.forward(&update).unwrap();
+    // both attempts were made before forward returned
+    assert_eq!(server.join().unwrap().len(), 2);
+    assert!(!path.exists());
+}
+
+#[test]
+fn waiting_forwarder_returns_after_dead_lettering() {
+    let (url, server) = stand_in(vec![400]);
+    let path = dead_letter_path("forwarder-dead-letter");
+    let mut forwarder =
+        WebhookForwarder::new(config(vec![url], 3, &path), Pubkey::new_unique(), "sensors")
+            .unwrap()
+            .wait_for_delivery();
+    let update = QueueUpdate {
+        slot: 1,
+        queue: event_queue(),
+        backfilled: false,
+        sender: None,
+    };
+    forwarder.forward(&update).unwrap();
+    assert_eq!(server.join().unwrap().len(), 1);
+    assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 1);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn stopping_forwarder_waits_at_most_the_shutdown_timeout() {
+    // accepts connections but never answers
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    let path = dead_letter_path("forwarder-shutdown");
+    let config = WebhookConfig {
+        timeout_secs: 30,
+        shutdown_timeout_secs: 1,
+        ..config(vec![url], 3, &path)
+    };
+    let mut forwarder = WebhookForwarder::new(config, Pubkey::new_unique(), "sensors").unwrap();
+    let update = QueueUpdate {
+        slot: 1,
+        queue: event_queue(),
+        backfilled: false,
+        sender: None,
+    };
+    forwarder.forward(&update).unwrap();
+    forwarder.forward(&update).unwrap();
+
+    let start = Instant::now();
+    drop(forwarder);
+    let waited = start.elapsed();
+    assert!(waited >= Duration::from_secs(1));
+    assert!(waited < Duration::from_secs(5), "waited {:?}", waited);
+    assert!(!path.exists());
+    // the abandoned delivery must not fail over to the dead letter file
+    std::mem::forget(listener);
+}